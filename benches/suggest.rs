@@ -0,0 +1,140 @@
+//! Suggestion pipeline benchmarks over synthetic histories of 1k, 10k and
+//! 100k lines: `cargo bench`, or `cargo bench -- fuzzy/10000` for one case.
+
+use std::hint::black_box;
+use std::sync::Arc;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use ghosttype::model::ensemble::{AggregationStrategy, WeightedModel};
+use ghosttype::prelude::*;
+use libsql::Value;
+
+const SIZES: [usize; 3] = [1_000, 10_000, 100_000];
+const QUERY: &str = "git ch";
+
+/// `n` distinct commands spread over a handful of tools, so prefix queries
+/// like [`QUERY`] match a realistic fraction of them
+fn synthetic_history(n: usize) -> Vec<String> {
+    const COMMANDS: &[&str] = &[
+        "git checkout feature/",
+        "git commit -m 'fix ",
+        "git cherry-pick ",
+        "docker run --rm -p 8080:80 app:",
+        "kubectl get pods -n team-",
+        "cargo test --package crate_",
+        "npm run build:",
+        "ssh deploy@host-",
+        "ls -la /var/log/app-",
+        "make target-",
+    ];
+    (0..n)
+        .map(|i| format!("{}{}", COMMANDS[i % COMMANDS.len()], i / COMMANDS.len()))
+        .collect()
+}
+
+/// In-memory store holding `lines` as history, each run a few times, for the
+/// SQLite-backed models
+fn history_pool(lines: &[String]) -> SqlitePool {
+    let pool = SqlitePool::open_memory_migrated().expect("in-memory store");
+    let statements = lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            (
+                "INSERT INTO history (command, hash, count) VALUES (?1, ?1, ?2)",
+                vec![Value::Text(line.clone()), Value::Integer(1 + (i % 7) as i64)],
+            )
+        })
+        .collect();
+    pool.execute_in_transaction(statements).expect("populating history");
+    pool
+}
+
+fn light_ensemble(lines: Arc<[String]>, pool: &SqlitePool) -> Ensemble {
+    EnsembleBuilder::new()
+        .with_light_model(FuzzyHistoryModel::new(lines).with_limit(100))
+        .with_light_model(PrefixModel::new(pool.clone()))
+        .with_light_model(FreqModel::new(pool.clone()))
+        .with_light_model(FrecencyModel::new(pool.clone()))
+        .with_light_model(FtsModel::new(pool.clone()))
+        .build()
+}
+
+/// Returns the same suggestions for every input, isolating aggregation cost
+#[derive(Debug)]
+struct FixedModel(Vec<Suggestion>);
+
+impl SuggestModel for FixedModel {
+    fn predict(&self, _input: &str) -> anyhow::Result<Vec<Suggestion>> {
+        Ok(self.0.clone())
+    }
+
+    fn name(&self) -> &str {
+        "fixed"
+    }
+}
+
+fn bench_fuzzy(c: &mut Criterion) {
+    let mut group = c.benchmark_group("fuzzy");
+    for n in SIZES {
+        let model = FuzzyHistoryModel::new(synthetic_history(n)).with_limit(100);
+        group.bench_with_input(BenchmarkId::from_parameter(n), &model, |b, model| {
+            b.iter(|| model.predict(black_box(QUERY)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_light_models(c: &mut Criterion) {
+    let mut group = c.benchmark_group("predict_light_models");
+    group.sample_size(20);
+    for n in SIZES {
+        let lines = synthetic_history(n);
+        let pool = history_pool(&lines);
+        let ensemble = light_ensemble(lines.into(), &pool);
+        group.bench_with_input(BenchmarkId::from_parameter(n), &ensemble, |b, ensemble| {
+            b.iter(|| ensemble.predict_light_models(black_box(QUERY)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_aggregate(c: &mut Criterion) {
+    let mut group = c.benchmark_group("aggregate_predictions");
+    for n in SIZES {
+        // Five models returning overlapping slices of the corpus
+        let lines = synthetic_history(n);
+        let models: Vec<WeightedModel> = (0..5)
+            .map(|m| {
+                let suggestions = lines
+                    .iter()
+                    .skip(m * n / 10)
+                    .take(n / 2)
+                    .enumerate()
+                    .map(|(rank, line)| Suggestion::with_source(line.clone(), 1.0 / (rank + 1) as f64, "fixed"))
+                    .collect();
+                WeightedModel::new(Arc::new(FixedModel(suggestions)), None)
+            })
+            .collect();
+        for (name, strategy) in [
+            ("weighted_sum", AggregationStrategy::WeightedSum),
+            ("rrf", AggregationStrategy::ReciprocalRankFusion { k: 60.0 }),
+        ] {
+            group.bench_with_input(BenchmarkId::new(name, n), &models, |b, models| {
+                b.iter(|| {
+                    Ensemble::aggregate_predictions(
+                        models.iter().cloned(),
+                        black_box(QUERY),
+                        strategy,
+                        DedupMode::NormalizedWhitespace,
+                    )
+                    .unwrap()
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_fuzzy, bench_light_models, bench_aggregate);
+criterion_main!(benches);
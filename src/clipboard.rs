@@ -0,0 +1,51 @@
+use std::io::Write;
+
+use anyhow::{Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use log::debug;
+
+/// Copy `text` to the system clipboard.
+///
+/// Over SSH the local clipboard belongs to the wrong machine, so the text is
+/// handed to the terminal via an OSC 52 escape instead. Locally a native
+/// clipboard is tried first and OSC 52 is the fallback.
+pub fn copy_to_clipboard(text: &str) -> Result<()> {
+    if is_remote_session() {
+        return write_osc52(text);
+    }
+    match arboard::Clipboard::new().and_then(|mut clipboard| clipboard.set_text(text.to_owned())) {
+        Ok(()) => Ok(()),
+        Err(err) => {
+            debug!("native clipboard unavailable, falling back to OSC 52: {err}");
+            write_osc52(text)
+        }
+    }
+}
+
+fn is_remote_session() -> bool {
+    std::env::var_os("SSH_TTY").is_some() || std::env::var_os("SSH_CONNECTION").is_some()
+}
+
+fn write_osc52(text: &str) -> Result<()> {
+    let mut stdout = std::io::stdout();
+    stdout
+        .write_all(osc52_sequence(text).as_bytes())
+        .and_then(|_| stdout.flush())
+        .context("writing OSC 52 clipboard sequence")
+}
+
+/// `ESC ] 52 ; c ; <base64> BEL` sets the terminal's clipboard selection
+fn osc52_sequence(text: &str) -> String {
+    format!("\x1b]52;c;{}\x07", STANDARD.encode(text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn osc52_sequence_base64_encodes_text() {
+        assert_eq!(osc52_sequence("git status"), "\x1b]52;c;Z2l0IHN0YXR1cw==\x07");
+    }
+}
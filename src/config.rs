@@ -0,0 +1,282 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use directories::BaseDirs;
+use serde::Deserialize;
+
+use crate::core::{DangerousCommands, HistoryEncoding, Placeholders};
+use crate::model::{DedupMode, EmbeddingSearch, LlmDevice};
+use crate::secrets::SecretFilter;
+
+/// Defaults for the `tui` subcommand, read from
+/// `<config dir>/ghosttype/config.toml`. Every field is optional and any
+/// flag given on the command line wins over the file.
+///
+/// ```toml
+/// top = 30
+/// enable_llm = true
+/// llm_model = "/models/qwen2.5-1.5b.gguf"
+/// debounce_ms = 150
+///
+/// [weights]
+/// prefix = 1.2
+/// llm = 0.6
+/// ```
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub top: Option<usize>,
+    pub unique: Option<bool>,
+    pub ignore_case: Option<bool>,
+    pub max_corpus: Option<usize>,
+    /// Encoding of history files without a BOM, e.g. `latin1`; unset is UTF-8
+    pub history_encoding: Option<String>,
+    pub enable_embedding: Option<bool>,
+    pub embedding_model: Option<PathBuf>,
+    pub embedding_top_k: Option<usize>,
+    pub embedding_threshold: Option<f64>,
+    pub enable_llm: Option<bool>,
+    pub llm_model: Option<PathBuf>,
+    pub llm_device: Option<String>,
+    pub debounce_ms: Option<u64>,
+    pub heavy_timeout_ms: Option<u64>,
+    pub vim: Option<bool>,
+    /// Rows for the inline (non-fullscreen) TUI; unset takes the whole screen
+    pub inline_rows: Option<u16>,
+    /// Output lines kept per command run from the TUI; older ones are dropped
+    pub max_output_lines: Option<usize>,
+    /// Extra regexes for commands kept out of the history store
+    pub secret_patterns: Vec<String>,
+    pub redact_secrets: Option<bool>,
+    pub ignore_space: Option<bool>,
+    /// Regexes for the placeholders Tab moves the cursor to; replace the defaults
+    pub placeholder_patterns: Option<Vec<String>>,
+    /// Regexes for commands the TUI asks to confirm; replace the defaults
+    pub dangerous_patterns: Option<Vec<String>>,
+    /// Models left out of the ensemble, by name (e.g. `alias`); the TUI's
+    /// model overlay (Ctrl+O) keeps this up to date
+    pub disabled_models: Vec<String>,
+    /// How suggestions from different models are merged: `exact`,
+    /// `whitespace` (the default) or `ignore-case`
+    pub dedup: Option<String>,
+    /// Let the prefix model also match a later word, so `status` finds
+    /// `git status` below the true prefix matches
+    pub prefix_word_boundary: Option<bool>,
+    pub weights: ModelWeights,
+}
+
+/// Per-model ensemble weights; unset models keep their built-in `weight()`
+#[derive(Debug, Default, Clone, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ModelWeights {
+    pub history: Option<f64>,
+    pub prefix: Option<f64>,
+    pub freq: Option<f64>,
+    pub frecency: Option<f64>,
+    pub fts: Option<f64>,
+    pub alias: Option<f64>,
+    pub ngram: Option<f64>,
+    pub acceptance: Option<f64>,
+    pub embedding: Option<f64>,
+    pub llm: Option<f64>,
+}
+
+impl Config {
+    /// Load the config file, or defaults if it does not exist
+    pub fn load() -> Result<Self> {
+        match default_config_path() {
+            Some(path) if path.exists() => Self::load_from(&path),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("reading config file {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("parsing config file {}", path.display()))
+    }
+
+    fn parse(text: &str) -> Result<Self> {
+        Ok(toml::from_str(text)?)
+    }
+
+    /// Embedding neighbour lookup, with unset keys at their defaults
+    pub fn embedding_search(&self) -> EmbeddingSearch {
+        let defaults = EmbeddingSearch::default();
+        EmbeddingSearch {
+            top_k: self.embedding_top_k.unwrap_or(defaults.top_k),
+            threshold: self.embedding_threshold.unwrap_or(defaults.threshold),
+        }
+    }
+
+    /// `placeholder_patterns`, or `<pod>` / `{{host}}` style tokens when unset
+    pub fn placeholders(&self) -> Result<Placeholders> {
+        match &self.placeholder_patterns {
+            Some(patterns) => {
+                Placeholders::new(patterns).context("reading placeholder_patterns from config")
+            }
+            None => Ok(Placeholders::default()),
+        }
+    }
+
+    pub fn history_encoding(&self) -> Result<HistoryEncoding> {
+        match self.history_encoding.as_deref() {
+            Some(label) => label
+                .parse()
+                .context("reading history_encoding from config"),
+            None => Ok(HistoryEncoding::default()),
+        }
+    }
+
+    /// `dedup`, merging whitespace variants when unset
+    pub fn dedup(&self) -> Result<DedupMode> {
+        match self.dedup.as_deref() {
+            Some(mode) => mode.parse().context("reading dedup from config"),
+            None => Ok(DedupMode::NormalizedWhitespace),
+        }
+    }
+
+    pub fn dangerous_commands(&self) -> Result<DangerousCommands> {
+        match &self.dangerous_patterns {
+            Some(patterns) => DangerousCommands::new(patterns)
+                .context("reading dangerous_patterns from config"),
+            None => Ok(DangerousCommands::default()),
+        }
+    }
+
+    /// The default secret patterns plus `secret_patterns`
+    pub fn secret_filter(&self, redact: bool, ignore_space: bool) -> Result<SecretFilter> {
+        Ok(SecretFilter::new(&self.secret_patterns, redact)
+            .context("reading secret_patterns from config")?
+            .with_ignore_space(ignore_space))
+    }
+
+    /// [`Config::secret_filter`] with `redact_secrets` and `ignore_space` as
+    /// configured, for subcommands without flags of their own
+    pub fn configured_secret_filter(&self) -> Result<SecretFilter> {
+        self.secret_filter(
+            self.redact_secrets.unwrap_or(false),
+            self.ignore_space.unwrap_or(true),
+        )
+    }
+}
+
+/// The `--llm-device` flag when given, else `llm_device` from the config,
+/// else the default device
+pub fn resolve_llm_device(cli: Option<LlmDevice>, config: &Config) -> Result<LlmDevice> {
+    match (cli, config.llm_device.as_deref()) {
+        (Some(device), _) => Ok(device),
+        (None, Some(name)) => name.parse().context("reading llm_device from config"),
+        (None, None) => Ok(LlmDevice::default()),
+    }
+}
+
+pub fn default_config_path() -> Option<PathBuf> {
+    BaseDirs::new().map(|dirs| dirs.config_dir().join("ghosttype").join("config.toml"))
+}
+
+/// Set `disabled_models` in the config file at `path`, creating it if
+/// needed and leaving the rest of the file, comments included, untouched
+pub fn save_disabled_models(path: &Path, names: &[String]) -> Result<()> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => {
+            return Err(err).with_context(|| format!("reading config file {}", path.display()))
+        }
+    };
+    let mut doc: toml_edit::DocumentMut = text
+        .parse()
+        .with_context(|| format!("parsing config file {}", path.display()))?;
+    if names.is_empty() {
+        doc.remove("disabled_models");
+    } else {
+        doc["disabled_models"] = toml_edit::value(names.iter().collect::<toml_edit::Array>());
+    }
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+    }
+    std::fs::write(path, doc.to_string())
+        .with_context(|| format!("writing config file {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_defaults_and_weights() {
+        let config = Config::parse(
+            r#"
+            top = 30
+            enable_llm = true
+            llm_model = "/models/llm.gguf"
+            debounce_ms = 150
+
+            [weights]
+            prefix = 1.2
+            llm = 0.6
+        "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.top, Some(30));
+        assert_eq!(config.enable_llm, Some(true));
+        assert_eq!(config.llm_model, Some(PathBuf::from("/models/llm.gguf")));
+        assert_eq!(config.debounce_ms, Some(150));
+        assert_eq!(config.unique, None);
+        assert_eq!(
+            config.weights,
+            ModelWeights {
+                prefix: Some(1.2),
+                llm: Some(0.6),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn saving_disabled_models_keeps_the_rest_of_the_file() {
+        let dir = std::env::temp_dir().join(format!("ghosttype-config-{}", std::process::id()));
+        let path = dir.join("config.toml");
+        let _ = std::fs::remove_dir_all(&dir);
+
+        save_disabled_models(&path, &["alias".to_string()]).unwrap();
+        assert_eq!(Config::load_from(&path).unwrap().disabled_models, vec!["alias"]);
+
+        std::fs::write(&path, "# tuned by hand\ntop = 30\n\n[weights]\nprefix = 1.2\n").unwrap();
+        save_disabled_models(&path, &["alias".to_string(), "prefix".to_string()]).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(text.starts_with("# tuned by hand\ntop = 30\n"));
+        let config = Config::load_from(&path).unwrap();
+        assert_eq!(config.disabled_models, vec!["alias", "prefix"]);
+        assert_eq!(config.weights.prefix, Some(1.2));
+
+        save_disabled_models(&path, &[]).unwrap();
+        assert!(!std::fs::read_to_string(&path).unwrap().contains("disabled_models"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn llm_device_flag_wins_over_config() {
+        let config = Config::parse(r#"llm_device = "metal""#).unwrap();
+        assert_eq!(resolve_llm_device(None, &config).unwrap(), LlmDevice::Metal);
+        assert_eq!(
+            resolve_llm_device(Some(LlmDevice::Cuda(1)), &config).unwrap(),
+            LlmDevice::Cuda(1)
+        );
+        assert_eq!(
+            resolve_llm_device(None, &Config::default()).unwrap(),
+            LlmDevice::default()
+        );
+        assert!(resolve_llm_device(None, &Config::parse(r#"llm_device = "tpu""#).unwrap()).is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        assert!(Config::parse("topp = 3").is_err());
+        assert!(Config::parse("[weights]\nmarkov = 1.0").is_err());
+    }
+}
@@ -1,96 +1,736 @@
 use ahash::AHashSet;
-use anyhow::{bail, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use directories::UserDirs;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
 use fuzzy_matcher::skim::SkimMatcherV2;
 use fuzzy_matcher::FuzzyMatcher;
 use hex::encode;
 use libsql::Value;
 use once_cell::sync::Lazy;
+use regex::{Regex, RegexBuilder};
 use ratatui::layout::Rect;
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
-use log::{info, warn};
-use std::cmp::Ordering;
+use log::{debug, info, warn};
+use std::borrow::Cow;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, VecDeque};
 use std::fs::File;
-use std::io::Read;
+use std::io::{IsTerminal, Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
-use std::time::{Duration, Instant};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::sync::mpsc;
 use tokio::task::JoinHandle;
 
+use crate::config::{save_disabled_models, ModelWeights};
+use crate::secrets::SecretFilter;
 use crate::model::{
-    AliasModel, EmbeddingModel, EmbeddingStore, EnsembleBuilder, FreqModel,
-    LlamaEmbeddingClient, LlmConfig, LlmModel, PrefixModel, SqlitePool,
+    parse_alias_definitions, record_acceptance, AcceptanceModel, AliasModel, DedupMode, SqlAliasStore, EmbeddingModel, EmbeddingSearch, EmbeddingStore, EnsembleBuilder, FreqModel, FrecencyModel, FtsModel,
+    LlamaEmbeddingClient, LlmConfig, LlmDevice, LlmModel, ModelRegistry, NgramModel, PrefixModel, SqlitePool,
     SuggestModel, Suggestion,
 };
-use crate::model::ensemble::Ensemble;
+use crate::model::ensemble::{normalize_scores, Ensemble, WeightedModel};
 
 static MATCHER: Lazy<SkimMatcherV2> = Lazy::new(SkimMatcherV2::default);
-/// Run the fuzzy search over one or more history files
-pub fn run_search(files: Vec<PathBuf>, query: &str, top: usize, unique: bool) -> Result<()> {
+
+/// Output format for the search subcommand
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// One suggestion text per line
+    #[default]
+    Plain,
+    /// JSON array of `{text, score, source}` objects
+    Json,
+}
+
+/// Everything `ghosttype search` takes from its flags and the config file
+#[derive(Clone, Debug)]
+pub struct SearchOptions {
+    pub files: Vec<PathBuf>,
+    pub top: usize,
+    pub unique: bool,
+    pub ignore_case: bool,
+    pub encoding: HistoryEncoding,
+    pub secrets: SecretFilter,
+    pub no_db: bool,
+    /// Embeddings and the LLM are only consulted when enabled here
+    pub models: ModelOptions,
+    pub format: OutputFormat,
+}
+
+/// Run the fuzzy search over one or more history files. Embeddings and the
+/// LLM are opt-in here; `predict` blocks until they answer.
+pub fn run_search(options: SearchOptions, query: Option<&str>) -> Result<()> {
+    let SearchOptions {
+        files,
+        top,
+        unique,
+        ignore_case,
+        encoding,
+        secrets,
+        no_db,
+        models,
+        format,
+    } = options;
     if files.is_empty() {
         bail!("Please specify at least one --file");
     }
+    let stdin = std::io::stdin();
+    let stdin_is_tty = stdin.is_terminal();
+    let query = resolve_query(query, stdin.lock(), stdin_is_tty)?;
+    let query = query.as_str();
 
     // Read all lines from the provided files
     let mut lines = Vec::new();
-    for path in files {
-        lines.extend(read_history_file(&path).with_context(|| format!("reading {:?}", path))?);
+    for path in expand_history_paths(files) {
+        let read =
+            read_history_file(&path, encoding).with_context(|| format!("reading {:?}", path))?;
+        lines.extend(read.iter().filter_map(|line| secrets.apply(line)).map(Cow::into_owned));
     }
 
     // Optionally remove duplicates
     if unique {
-        let mut seen = AHashSet::with_capacity(lines.len());
-        lines.retain(|s| seen.insert(s.to_string()));
+        dedup_lines(&mut lines, ignore_case);
+    }
+
+    let pool = search_pool(no_db);
+    let ensemble = search_models(lines.into(), top, pool.as_ref(), models).build();
+
+    let suggestions: Vec<Suggestion> = ensemble.predict(query)?.into_iter().take(top).collect();
+    match format {
+        OutputFormat::Plain => {
+            for suggestion in suggestions {
+                println!("{}", suggestion.text);
+            }
+        }
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string(&suggestions)?);
+        }
+    }
+    Ok(())
+}
+
+/// The history store for `search`, or `None` with `--no-db` (or when it
+/// cannot be opened), leaving only the fuzzy model over the files
+fn search_pool(no_db: bool) -> Option<SqlitePool> {
+    if no_db {
+        return None;
+    }
+    SqlitePool::open_default()
+        .inspect_err(|err| warn!("failed to open sqlite history store: {err:?}"))
+        .ok()
+}
+
+/// `light_search_models` plus embeddings (which need `pool`) and the LLM
+/// when `models` enables them
+fn search_models(
+    lines: Arc<[String]>,
+    top: usize,
+    pool: Option<&SqlitePool>,
+    models: ModelOptions,
+) -> EnsembleBuilder {
+    let mut builder = light_search_models(lines.clone(), top, pool, models.prefix_word_boundary);
+    if let (true, Some(pool)) = (models.enable_embedding, pool) {
+        if let Some(model) =
+            load_embedding_model(pool, &lines, models.embedding_model, models.embedding_search)
+        {
+            builder = builder.with_heavy_model(model);
+        }
+    }
+    if models.enable_llm {
+        if let Some(model) = load_llm_model(models.llm_model, models.llm_device) {
+            builder = builder.with_heavy_model(model);
+        }
+    }
+    builder
+}
+
+/// A `search --regex` or `--glob` pattern, matched against whole history lines
+#[derive(Debug)]
+pub enum HistoryPattern {
+    Regex(Regex),
+    Glob(glob::Pattern, glob::MatchOptions),
+}
+
+impl HistoryPattern {
+    /// Unanchored, like grep: `docker run .* -p \d+:` matches anywhere in the line
+    pub fn regex(pattern: &str, ignore_case: bool) -> Result<Self> {
+        let regex = RegexBuilder::new(pattern)
+            .case_insensitive(ignore_case)
+            .build()
+            .with_context(|| format!("invalid regex {pattern:?}"))?;
+        Ok(Self::Regex(regex))
+    }
+
+    /// Must match the whole line, so `git *` finds git commands only
+    pub fn glob(pattern: &str, ignore_case: bool) -> Result<Self> {
+        let glob = glob::Pattern::new(pattern).with_context(|| format!("invalid glob {pattern:?}"))?;
+        let options = glob::MatchOptions {
+            case_sensitive: !ignore_case,
+            ..Default::default()
+        };
+        Ok(Self::Glob(glob, options))
+    }
+
+    pub fn is_match(&self, line: &str) -> bool {
+        match self {
+            Self::Regex(regex) => regex.is_match(line),
+            Self::Glob(glob, options) => glob.matches_with(line, *options),
+        }
+    }
+
+    fn source(&self) -> &'static str {
+        match self {
+            Self::Regex(_) => "regex",
+            Self::Glob(..) => "glob",
+        }
+    }
+}
+
+/// The newest `top` of `lines` (oldest first, as read from the history
+/// files) that match `pattern`
+fn grep_history(lines: &[String], pattern: &HistoryPattern, top: usize) -> Vec<Suggestion> {
+    lines
+        .iter()
+        .rev()
+        .filter(|line| pattern.is_match(line))
+        .take(top)
+        .map(|line| Suggestion::with_source(line.clone(), 1.0, pattern.source()))
+        .collect()
+}
+
+/// Print history lines matching `pattern`, newest first, without consulting
+/// the suggestion models
+pub fn run_history_grep(options: SearchOptions, pattern: &HistoryPattern) -> Result<()> {
+    let SearchOptions {
+        files,
+        top,
+        unique,
+        ignore_case,
+        encoding,
+        secrets,
+        format,
+        ..
+    } = options;
+    let lines = load_history_lines(files, unique, ignore_case, None, encoding, &secrets)?;
+    let matches = grep_history(&lines, pattern, top);
+    match format {
+        OutputFormat::Plain => {
+            for suggestion in matches {
+                println!("{}", suggestion.text);
+            }
+        }
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string(&matches)?);
+        }
+    }
+    Ok(())
+}
+
+/// Print only the best suggestion for `query`, for shell widgets that replace
+/// the command line with it. Returns false when there was nothing to suggest.
+pub fn run_complete(
+    files: Vec<PathBuf>,
+    query: Option<&str>,
+    encoding: HistoryEncoding,
+    secrets: &SecretFilter,
+    prefix_word_boundary: bool,
+) -> Result<bool> {
+    let stdin = std::io::stdin();
+    let stdin_is_tty = stdin.is_terminal();
+    let query = resolve_query(query, stdin.lock(), stdin_is_tty)?;
+    // Blank input has no completion; don't load history to find that out
+    if query.trim().is_empty() {
+        return Ok(false);
+    }
+
+    let lines = load_history_lines(files, true, false, None, encoding, secrets)?;
+    let pool = SqlitePool::open_default()
+        .inspect_err(|err| warn!("failed to open sqlite history store: {err:?}"))
+        .ok();
+    let ensemble =
+        light_search_models(lines.into(), 1, pool.as_ref(), prefix_word_boundary).build();
+    match best_completion(&ensemble, &query)? {
+        Some(best) => {
+            println!("{best}");
+            Ok(true)
+        }
+        None => Ok(false),
     }
+}
 
-    let mut builder = EnsembleBuilder::new().with_light_model(FuzzyHistoryModel::new(lines));
+/// The top suggestion for `query`, or `None` for blank input or no match
+fn best_completion(ensemble: &Ensemble, query: &str) -> Result<Option<String>> {
+    if query.trim().is_empty() {
+        return Ok(None);
+    }
+    Ok(ensemble.predict(query)?.into_iter().next().map(|best| best.text))
+}
 
-    if let Ok(pool) = SqlitePool::open_default() {
+/// Fuzzy history over `lines` plus the SQLite-backed light models, as used
+/// by the one-shot `search` and `complete` commands
+fn light_search_models(
+    lines: Arc<[String]>,
+    top: usize,
+    pool: Option<&SqlitePool>,
+    prefix_word_boundary: bool,
+) -> EnsembleBuilder {
+    let mut builder = EnsembleBuilder::new().with_light_model(
+        FuzzyHistoryModel::new(lines).with_limit(top * FUZZY_CANDIDATES_PER_SUGGESTION),
+    );
+    if let Some(pool) = pool {
         builder = builder
-            .with_light_model(PrefixModel::new(pool.clone()))
+            .with_light_model(prefix_model(pool.clone(), prefix_word_boundary))
             .with_light_model(FreqModel::new(pool.clone()))
-            .with_light_model(AliasModel::with_sql_store(pool));
+            .with_light_model(FrecencyModel::new(pool.clone()))
+            .with_light_model(FtsModel::new(pool.clone()))
+            .with_light_model(AliasModel::with_sql_store(pool.clone()))
+            .with_light_model(AcceptanceModel::new(pool.clone()));
+        if let Some(model) = load_ngram_model(pool) {
+            builder = builder.with_light_model(model);
+        }
+    }
+    builder
+}
+
+/// `--query -`, or no `--query` while stdin is piped, reads the query from stdin
+fn resolve_query(query: Option<&str>, mut stdin: impl Read, stdin_is_tty: bool) -> Result<String> {
+    match query {
+        Some(q) if q != "-" => Ok(q.to_string()),
+        None if stdin_is_tty => bail!("Please specify --query, or pipe the query on stdin"),
+        _ => {
+            let mut text = String::new();
+            stdin.read_to_string(&mut text).context("reading query from stdin")?;
+            Ok(text.trim_end_matches(['\n', '\r']).to_string())
+        }
+    }
+}
+
+/// Rows removed by `prune_history_db`
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct PruneReport {
+    pub executions_removed: u64,
+    pub history_removed: u64,
+}
+
+/// Delete `command_executions` older than `days`, optionally keep only the
+/// `keep_top` most frequent `history` rows, then VACUUM
+pub fn prune_history_db(
+    pool: &SqlitePool,
+    days: u32,
+    keep_top: Option<usize>,
+) -> Result<PruneReport> {
+    let executions_removed = pool.execute_changes(
+        "DELETE FROM command_executions WHERE executed_at < datetime('now', ?1);",
+        vec![Value::Text(format!("-{days} days"))],
+    )?;
+
+    let history_removed = match keep_top {
+        Some(keep) => pool.execute_changes(
+            r#"
+            DELETE FROM history
+            WHERE id NOT IN (
+                SELECT id FROM history ORDER BY count DESC, created_at DESC LIMIT ?1
+            );
+        "#,
+            vec![Value::Integer(keep as i64)],
+        )?,
+        None => 0,
+    };
+
+    pool.vacuum()?;
+
+    Ok(PruneReport {
+        executions_removed,
+        history_removed,
+    })
+}
+
+/// Prune the default history database and report what was removed
+pub fn run_prune(days: u32, keep_top: Option<usize>) -> Result<()> {
+    let pool = SqlitePool::open_default()?;
+    let report = prune_history_db(&pool, days, keep_top)?;
+    println!(
+        "Removed {} command executions and {} history entries",
+        report.executions_removed, report.history_removed
+    );
+    Ok(())
+}
+
+/// Embed history commands into the store. `rebuild` discards every stored
+/// vector first, e.g. after switching embedding models.
+pub fn run_embeddings(
+    files: Vec<PathBuf>,
+    rebuild: bool,
+    embedding_model: Option<PathBuf>,
+    encoding: HistoryEncoding,
+    secrets: &SecretFilter,
+) -> Result<()> {
+    let pool = SqlitePool::open_default()?;
+    let corpus = load_history_lines(files, true, false, None, encoding, secrets)?;
+    let client = LlamaEmbeddingClient::from_env_or(embedding_model)?;
+    let model = EmbeddingModel::new(EmbeddingStore::new(pool), client);
+    model.warm_up().context("embedding model health check")?;
+
+    let stored = if rebuild {
+        model.relearn(&corpus)?
+    } else {
+        model.learn(&corpus)?
+    };
+    println!("Stored {stored} new embeddings");
+    Ok(())
+}
+
+/// Import `alias` definitions from shell rc files into the aliases table.
+/// Defaults to `~/.zshrc` and `~/.bashrc` when no files are given.
+pub fn run_alias_import(files: Vec<PathBuf>) -> Result<()> {
+    let mut paths = files;
+    if paths.is_empty() {
+        if let Some(ud) = UserDirs::new() {
+            let home = ud.home_dir();
+            for c in [".zshrc", ".bashrc"] {
+                let candidate = home.join(c);
+                if candidate.exists() {
+                    paths.push(candidate);
+                }
+            }
+        }
+    }
+    if paths.is_empty() {
+        bail!("No rc files found; pass one with --file");
     }
 
-    let ensemble = builder.build();
+    let store = SqlAliasStore::new(SqlitePool::open_default()?);
+    let mut imported = 0usize;
+    for path in paths {
+        let text = std::fs::read_to_string(&path).with_context(|| format!("reading {path:?}"))?;
+        for entry in parse_alias_definitions(&text) {
+            store
+                .upsert(&entry)
+                .with_context(|| format!("saving alias {}", entry.name))?;
+            imported += 1;
+        }
+    }
+
+    println!("Imported {imported} aliases");
+    Ok(())
+}
+
+/// Other tools whose history `ghosttype import` can read
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ImportSource {
+    /// Atuin's SQLite history database
+    Atuin,
+}
 
-    let suggestions = ensemble.predict(query)?;
-    for suggestion in suggestions.into_iter().take(top) {
-        println!("{}", suggestion.text);
+/// Import history from another tool's database. `path` defaults to the
+/// tool's standard location.
+pub fn run_import(from: ImportSource, path: Option<PathBuf>, secrets: &SecretFilter) -> Result<()> {
+    match from {
+        ImportSource::Atuin => {
+            let path = match path {
+                Some(path) => path,
+                None => default_atuin_db_path().context("resolving Atuin database path; pass --path")?,
+            };
+            if !path.exists() {
+                bail!("Atuin database not found at {}", path.display());
+            }
+            let atuin = SqlitePool::open_readonly(&path)?;
+            let pool = SqlitePool::open_default()?;
+            let imported = import_atuin_history(&pool, &atuin, &path.to_string_lossy(), secrets)?;
+            println!("Imported {imported} commands from Atuin");
+        }
     }
     Ok(())
 }
 
+/// `$XDG_DATA_HOME/atuin/history.db`, falling back to `~/.local/share` as Atuin does
+fn default_atuin_db_path() -> Option<PathBuf> {
+    let data_dir = match std::env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => UserDirs::new()?.home_dir().join(".local").join("share"),
+    };
+    Some(data_dir.join("atuin").join("history.db"))
+}
+
+/// `meta` key prefix recording the newest Atuin timestamp already imported,
+/// one row per Atuin database
+const ATUIN_IMPORT_META_PREFIX: &str = "atuin_import:";
+/// Rows written to the store per transaction while importing history
+const IMPORT_BATCH_ROWS: usize = 500;
+
+/// Copy Atuin's non-deleted `history` rows into `history` (counted and
+/// deduplicated by hash) and `command_executions`. Atuin timestamps are
+/// nanoseconds since the epoch; a negative exit code means it was unknown.
+/// Only rows newer than the previous import of `atuin_path` are read, so
+/// running the import again does not double-count. Commands go through
+/// `secrets` like shell history does.
+pub fn import_atuin_history(
+    pool: &SqlitePool,
+    atuin: &SqlitePool,
+    atuin_path: &str,
+    secrets: &SecretFilter,
+) -> Result<usize> {
+    let key = format!("{ATUIN_IMPORT_META_PREFIX}{atuin_path}");
+    let since: i64 = pool
+        .query_collect(
+            "SELECT mtime FROM meta WHERE key = ?1",
+            vec![Value::Text(key.clone())],
+            |row| Ok(row.get::<i64>(0)?),
+        )?
+        .into_iter()
+        .next()
+        .unwrap_or(i64::MIN);
+
+    let rows = atuin.query_collect(
+        r#"
+        SELECT command, cwd, exit, timestamp
+        FROM history
+        WHERE deleted_at IS NULL AND timestamp > ?1
+        ORDER BY timestamp
+    "#,
+        vec![Value::Integer(since)],
+        |row| {
+            let command: String = row.get(0)?;
+            let cwd: String = row.get::<Option<String>>(1)?.unwrap_or_default();
+            let exit: Option<i64> = row.get(2)?;
+            let timestamp: i64 = row.get(3)?;
+            Ok((command, cwd, exit, timestamp))
+        },
+    )?;
+
+    let mut imported = 0usize;
+    let mut newest = since;
+    for batch in rows.chunks(IMPORT_BATCH_ROWS) {
+        let mut statements = Vec::with_capacity(batch.len() * 2);
+        for (command, cwd, exit, timestamp) in batch {
+            newest = newest.max(*timestamp);
+            let Some(command) = secrets.apply(command) else {
+                continue;
+            };
+            let trimmed = command.trim();
+            if trimmed.is_empty() {
+                continue;
+            }
+            let seconds = timestamp / 1_000_000_000;
+            let exit_code = match exit {
+                Some(code) if *code >= 0 => Value::Integer(*code),
+                _ => Value::Null,
+            };
+
+            statements.push((
+                r#"
+                INSERT INTO history (command, hash, count, source, output, cwd, created_at, last_used, last_exit_code)
+                VALUES (?1, ?2, 1, 'atuin', '', ?3, datetime(?4, 'unixepoch'), datetime(?4, 'unixepoch'), ?5)
+                ON CONFLICT(hash) DO UPDATE SET
+                    count = count + 1,
+                    cwd = excluded.cwd,
+                    created_at = MAX(created_at, excluded.created_at),
+                    last_used = MAX(COALESCE(last_used, excluded.last_used), excluded.last_used),
+                    last_exit_code = COALESCE(excluded.last_exit_code, last_exit_code);
+            "#,
+                vec![
+                    Value::Text(trimmed.to_string()),
+                    Value::Text(hash_command(trimmed)),
+                    Value::Text(cwd.clone()),
+                    Value::Integer(seconds),
+                    exit_code.clone(),
+                ],
+            ));
+            statements.push((
+                r#"
+                INSERT INTO command_executions (command, output, session_id, cwd, exit_code, executed_at)
+                VALUES (?1, '', 'atuin', ?2, ?3, datetime(?4, 'unixepoch'));
+            "#,
+                vec![
+                    Value::Text(trimmed.to_string()),
+                    Value::Text(cwd.clone()),
+                    exit_code,
+                    Value::Integer(seconds),
+                ],
+            ));
+            imported += 1;
+        }
+        pool.execute_in_transaction(statements)?;
+    }
+
+    if newest > since {
+        pool.execute(
+            r#"
+            INSERT INTO meta (key, path, mtime) VALUES (?1, ?2, ?3)
+            ON CONFLICT(key) DO UPDATE SET path = excluded.path, mtime = excluded.mtime;
+        "#,
+            vec![
+                Value::Text(key),
+                Value::Text(atuin_path.to_string()),
+                Value::Integer(newest),
+            ],
+        )?;
+    }
+
+    Ok(imported)
+}
+
 // ---------------------
 // TUI model
 // ---------------------
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Tab {
     Main,
     History,
 }
 
+/// Whether keys on the Main tab edit the input or, in vim mode's `Normal`,
+/// move the selection
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EditMode {
+    #[default]
+    Insert,
+    Normal,
+}
+
+/// Tokens in a suggestion meant to be filled in, `<pod>` or `{{host}}` by default
+pub const DEFAULT_PLACEHOLDER_PATTERNS: &[&str] = &[r"<[\w.:/-]+>", r"\{\{[^{}]*\}\}"];
+
+/// Finds placeholders in an accepted suggestion, so the cursor can land on
+/// the first one instead of the end of the line
+#[derive(Clone, Debug)]
+pub struct Placeholders(Vec<Regex>);
+
+impl Default for Placeholders {
+    fn default() -> Self {
+        let patterns: Vec<String> =
+            DEFAULT_PLACEHOLDER_PATTERNS.iter().map(|p| p.to_string()).collect();
+        Self::new(&patterns).expect("default placeholder patterns are valid")
+    }
+}
+
+impl Placeholders {
+    pub fn new(patterns: &[String]) -> Result<Self> {
+        patterns
+            .iter()
+            .map(|p| Regex::new(p).with_context(|| format!("invalid placeholder pattern {p:?}")))
+            .collect::<Result<_>>()
+            .map(Self)
+    }
+
+    /// Byte offset of the earliest placeholder in `text`
+    pub fn first_in(&self, text: &str) -> Option<usize> {
+        self.0.iter().filter_map(|p| p.find(text)).map(|m| m.start()).min()
+    }
+}
+
+/// Commands the TUI asks about before running: recursive or forced `rm`,
+/// history-rewriting git, `kubectl delete`, disk formatting and SQL drops
+pub const DEFAULT_DANGEROUS_PATTERNS: &[&str] = &[
+    r"\brm\s+(?:\S+\s+)*-[a-zA-Z]*[rRf]",
+    r"\bgit\s+reset\s+(?:\S+\s+)*--hard\b",
+    r"\bgit\s+push\s+(?:\S+\s+)*(?:--force\b|-f\b)",
+    r"\bgit\s+clean\s+(?:\S+\s+)*-[a-zA-Z]*f",
+    r"\bkubectl\s+(?:\S+\s+)*delete\b",
+    r"\bdd\s+(?:\S+\s+)*of=",
+    r"\bmkfs\b",
+    r"(?i)\bdrop\s+(?:table|database)\b",
+];
+
+/// Matches commands that need a confirmation before the TUI runs them
+#[derive(Clone, Debug)]
+pub struct DangerousCommands(Vec<Regex>);
+
+impl Default for DangerousCommands {
+    fn default() -> Self {
+        let patterns: Vec<String> =
+            DEFAULT_DANGEROUS_PATTERNS.iter().map(|p| p.to_string()).collect();
+        Self::new(&patterns).expect("default dangerous patterns are valid")
+    }
+}
+
+impl DangerousCommands {
+    pub fn new(patterns: &[String]) -> Result<Self> {
+        patterns
+            .iter()
+            .map(|p| Regex::new(p).with_context(|| format!("invalid dangerous pattern {p:?}")))
+            .collect::<Result<_>>()
+            .map(Self)
+    }
+
+    pub fn is_match(&self, command: &str) -> bool {
+        self.0.iter().any(|p| p.is_match(command))
+    }
+}
+
 #[derive(Clone)]
 pub struct HistoryEntry {
     pub cmd: String,
     pub output_lines: Vec<String>,
+    pub exit_code: Option<i32>, // None for runs recorded before exit codes were kept
+}
+
+/// One occurrence of the output search text: a line index and the byte
+/// range it covers in that line
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutputMatch {
+    pub line: usize,
+    pub range: std::ops::Range<usize>,
+}
+
+/// Every occurrence of `needle` in `lines`, in order. Smart case: the match
+/// ignores ASCII case unless `needle` has an uppercase letter.
+pub fn find_output_matches(lines: &[String], needle: &str) -> Vec<OutputMatch> {
+    if needle.is_empty() {
+        return Vec::new();
+    }
+    let ignore_case = !needle.chars().any(char::is_uppercase);
+    let fold = |text: &str| {
+        if ignore_case {
+            text.to_ascii_lowercase()
+        } else {
+            text.to_string()
+        }
+    };
+    let needle = fold(needle);
+    let mut matches = Vec::new();
+    for (line, text) in lines.iter().enumerate() {
+        let haystack = fold(text);
+        let mut from = 0;
+        while let Some(pos) = haystack[from..].find(&needle) {
+            let start = from + pos;
+            matches.push(OutputMatch {
+                line,
+                range: start..start + needle.len(),
+            });
+            from = start + needle.len();
+        }
+    }
+    matches
 }
 
 pub struct App {
     // input
     pub input: String,
     pub cursor: usize,
+    undo_stack: Vec<(String, usize)>, // (input, cursor) before each edit, oldest first
+    redo_stack: Vec<(String, usize)>,
+    typing_at: Option<usize>, // cursor after the last typed char, while typing continues there
 
     // suggestions
-    pub suggestions: Vec<String>,
+    pub suggestions: Vec<Suggestion>,
     pub selected: usize,
     pub max_suggestions: usize,
+    pub suggestions_scroll: usize, // first suggestion drawn; the list scrolls to follow `selected`
 
     // history
     pub history: Vec<HistoryEntry>,
+    pub history_list_scroll: usize, // first row drawn in the History tab list
 
     // output streaming
-    pub output_lines: Vec<String>,
+    pub output_lines: VecDeque<String>, // newest `max_output_lines` lines of the running command
+    pub output_dropped: usize,          // older lines dropped to stay within the cap
+    pub max_output_lines: usize,
     pub is_running: bool,
     pub last_run_cmd: Option<String>,
 
@@ -98,14 +738,38 @@ pub struct App {
     pub current_tab: Tab,
     pub selected_history_index: usize,
     pub pinned_output: bool, // middle-left shows output instead of suggestions
+    pub show_preview: bool,  // bottom pane with the full selected suggestion
     pub recent_runs_area: Option<Rect>, // clickable area cache
     pub main_tab_area: Option<Rect>,
     pub history_tab_area: Option<Rect>,
+    pub content_area: Option<Rect>,        // main tab suggestions/output pane
+    pub suggestions_area: Option<Rect>,    // suggestion list, when it is showing
+    pub last_suggestion_click: Option<(usize, Instant)>, // for double-click detection
+    pub history_output_area: Option<Rect>, // history tab output pane
     pub output_scroll: u16,        // scroll offset for main tab output
     pub history_scroll: u16,       // scroll offset for history tab output
-
-    // corpus (legacy fuzzy matching)
-    pub corpus: Vec<String>,
+    pub status_message: Option<(String, Instant)>, // transient note shown in the input title
+    pub pending_history_delete: Option<String>, // command armed for deletion, awaiting confirmation
+    pub history_filter: String,    // fuzzy filter for the History tab list
+    pub history_filter_active: bool, // typing edits `history_filter` instead of running keys
+    pub history_search: String,    // text searched for in the History output pane
+    pub history_search_active: bool, // typing edits `history_search` instead of running keys
+    pub history_search_match: usize, // index of the current hit in `history_output_matches`
+    pub vim: bool,                 // Esc enters normal mode instead of quitting
+    pub edit_mode: EditMode,
+    pub placeholders: Placeholders, // where Tab leaves the cursor in an accepted suggestion
+    pub dangerous: DangerousCommands, // commands that need confirming before they run
+    pub pending_run: Option<String>,  // dangerous command awaiting confirmation
+    pub show_models: bool,            // model on/off overlay is open
+    pub models_selected: usize,       // highlighted row in the model overlay
+    pub config_path: Option<PathBuf>, // where model toggles are saved; None keeps them per session
+
+    // corpus (legacy fuzzy matching), shared with the history model
+    pub corpus: Arc<[String]>,
+
+    // history store, used to delete entries from the History tab
+    pub db: Option<SqlitePool>,
+    pub secrets: SecretFilter, // applied to everything the app writes to `db`
 
     // ensemble for multi-model suggestions
     pub ensemble: Ensemble,
@@ -113,23 +777,80 @@ pub struct App {
     // debounce state for suggestion refresh
     pub last_input_time: Option<Instant>,
     pub pending_refresh: bool,
+    pub debounce: Duration,
 
     // async heavy model state
-    heavy_model_rx: Option<mpsc::UnboundedReceiver<Vec<Suggestion>>>,
-    heavy_model_tx: Option<mpsc::UnboundedSender<Vec<Suggestion>>>,
+    heavy_model_rx: Option<mpsc::UnboundedReceiver<HeavyBatch>>,
+    heavy_model_tx: Option<mpsc::UnboundedSender<HeavyBatch>>,
     heavy_model_tasks: Vec<JoinHandle<()>>,
     pending_heavy_model_query: Option<String>,
+    heavy_model_timeout: Duration,
+    pub is_loading_heavy: bool, // heavy model tasks still running for the current query
+    pub spinner_frame: usize,   // advanced on every UI tick
+}
+
+/// Heavy-model suggestions tagged with the input they were predicted for
+type HeavyBatch = (String, Vec<Suggestion>);
+
+/// Default delay between the last keystroke and a suggestion refresh
+pub const DEFAULT_DEBOUNCE_MS: u64 = 300;
+/// Default time budget for a single heavy model prediction
+pub const DEFAULT_HEAVY_TIMEOUT_MS: u64 = 5000;
+/// Default number of output lines kept for a running command
+pub const DEFAULT_MAX_OUTPUT_LINES: usize = 10_000;
+/// How long a status message stays in the input title
+const STATUS_MESSAGE_TTL: Duration = Duration::from_millis(1500);
+/// Input edits kept for undo
+const MAX_UNDO_STEPS: usize = 100;
+
+/// Settings for [`App::new`], built once from the command line and config
+/// file; the defaults suit tests and embedders
+#[derive(Clone, Debug)]
+pub struct AppOptions {
+    /// Max suggestions shown
+    pub top: usize,
+    pub models: ModelOptions,
+    pub debounce: Duration,
+    pub heavy_model_timeout: Duration,
+    pub vim: bool,
+    pub placeholders: Placeholders,
+    pub dangerous: DangerousCommands,
+    /// Applied to whatever the session writes to the history store
+    pub secrets: SecretFilter,
+    pub max_output_lines: usize,
+    /// Where model toggles are saved; `None` keeps them for the session
+    pub config_path: Option<PathBuf>,
+}
+
+impl Default for AppOptions {
+    fn default() -> Self {
+        Self {
+            top: 20,
+            models: ModelOptions::default(),
+            debounce: Duration::from_millis(DEFAULT_DEBOUNCE_MS),
+            heavy_model_timeout: Duration::from_millis(DEFAULT_HEAVY_TIMEOUT_MS),
+            vim: false,
+            placeholders: Placeholders::default(),
+            dangerous: DangerousCommands::default(),
+            secrets: SecretFilter::default(),
+            max_output_lines: DEFAULT_MAX_OUTPUT_LINES,
+            config_path: None,
+        }
+    }
 }
 
 impl App {
-    pub fn new(
+    pub fn new(corpus: Vec<String>, db: Option<SqlitePool>, options: &AppOptions) -> Result<Self> {
+        Self::new_with_models(ModelRegistry::default(), corpus, db, options)
+    }
+
+    /// Like [`App::new`], with `extra_models` ranked alongside the built-in
+    /// models. Heavy ones run in the background under the same timeout.
+    pub fn new_with_models(
+        extra_models: ModelRegistry,
         corpus: Vec<String>,
-        top: usize,
         db: Option<SqlitePool>,
-        enable_embedding: bool,
-        embedding_model: Option<PathBuf>,
-        enable_llm: bool,
-        llm_model: Option<PathBuf>,
+        options: &AppOptions,
     ) -> Result<Self> {
         // Load recent history from database
         let history = if let Some(ref pool) = db {
@@ -138,86 +859,109 @@ impl App {
             Vec::new()
         };
 
-        // Build ensemble with all suggestion models
-        let mut builder = EnsembleBuilder::new().with_light_model(FuzzyHistoryModel::new(corpus.clone()));
-
-        // Add database-backed models if available
-        if enable_embedding {
-            if let Some(ref pool) = db {
-                builder = builder
-                    .with_light_model(PrefixModel::new(pool.clone()))
-                    .with_light_model(FreqModel::new(pool.clone()))
-                    .with_light_model(AliasModel::with_sql_store(pool.clone()));
-
-                match LlamaEmbeddingClient::from_env_or(embedding_model.clone()) {
-                    Ok(client) => {
-                        let store = EmbeddingStore::new(pool.clone());
-                        let embedding_model = EmbeddingModel::new(store, client);
-                        match embedding_model.warm_up() {
-                            Ok(_) => {
-                                if let Err(err) = embedding_model.learn(&corpus) {
-                                    warn!("embedding warmup failed: {err:?}");
-                                }
-                                builder = builder.with_heavy_model(embedding_model);
-                                info!("embedding model enabled via Ollama");
-                            }
-                            Err(err) => {
-                                warn!("skipping embedding model; Ollama embeddings unavailable: {err:?}");
-                            }
-                        }
-                    }
-                    Err(err) => {
-                        warn!("failed to construct embedding client: {err:?}");
-                    }
-                }
-            }
-        }
-
-        // Add LLM model as heavy model if enabled
-        if enable_llm {
-            if let Some(model_path) = llm_model {
-                let llm_config = LlmConfig {
-                    model_path,
-                    ..Default::default()
-                };
-                builder = builder.with_heavy_model(LlmModel::new(llm_config));
-            } else {
-                warn!("--enable-llm specified but --llm-model not provided");
-            }
-        }
-
-        let ensemble = builder.build();
+        let corpus: Arc<[String]> = corpus.into();
+        let ensemble = build_ensemble(
+            corpus.clone(),
+            options.top,
+            db.as_ref(),
+            &options.models,
+            extra_models,
+        );
 
         // Create channel for async heavy model results
         let (tx, rx) = mpsc::unbounded_channel();
 
-        Ok(Self {
+        let mut app = Self {
             input: String::new(),
             cursor: 0,
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            typing_at: None,
             suggestions: Vec::new(),
             selected: 0,
-            max_suggestions: top,
+            max_suggestions: options.top,
+            suggestions_scroll: 0,
             history,
-            output_lines: Vec::new(),
+            history_list_scroll: 0,
+            output_lines: VecDeque::new(),
+            output_dropped: 0,
+            max_output_lines: options.max_output_lines,
             is_running: false,
             last_run_cmd: None,
             current_tab: Tab::Main,
             selected_history_index: 0,
             pinned_output: false,
+            show_preview: false,
             recent_runs_area: None,
             main_tab_area: None,
             history_tab_area: None,
+            content_area: None,
+            suggestions_area: None,
+            last_suggestion_click: None,
+            history_output_area: None,
             output_scroll: 0,
             history_scroll: 0,
+            status_message: None,
+            pending_history_delete: None,
+            history_filter: String::new(),
+            history_filter_active: false,
+            history_search: String::new(),
+            history_search_active: false,
+            history_search_match: 0,
+            vim: options.vim,
+            edit_mode: EditMode::Insert,
+            placeholders: options.placeholders.clone(),
+            dangerous: options.dangerous.clone(),
+            secrets: options.secrets.clone(),
+            pending_run: None,
+            show_models: false,
+            models_selected: 0,
+            config_path: options.config_path.clone(),
             corpus,
+            db,
             ensemble,
             last_input_time: None,
             pending_refresh: false,
+            debounce: options.debounce,
             heavy_model_rx: Some(rx),
             heavy_model_tx: Some(tx),
             heavy_model_tasks: Vec::new(),
             pending_heavy_model_query: None,
-        })
+            heavy_model_timeout: options.heavy_model_timeout,
+            is_loading_heavy: false,
+            spinner_frame: 0,
+        };
+        app.restore_view();
+        Ok(app)
+    }
+
+    /// Reopen the tab and History filter saved by [`App::save_view`]
+    fn restore_view(&mut self) {
+        let Some(ref pool) = self.db else {
+            return;
+        };
+        match load_tui_view(pool) {
+            Ok(Some(view)) => {
+                self.current_tab = view.tab;
+                self.history_filter = view.history_filter;
+                self.snap_history_selection();
+            }
+            Ok(None) => {}
+            Err(err) => warn!("failed to restore tui view: {err:?}"),
+        }
+    }
+
+    /// Remember the tab and History filter for the next launch
+    pub fn save_view(&self) {
+        if let Some(ref pool) = self.db {
+            let view = TuiView {
+                tab: self.current_tab,
+                history_filter: self.history_filter.clone(),
+            };
+            if let Err(err) = save_tui_view(pool, &view) {
+                warn!("failed to save tui view: {err:?}");
+            }
+        }
     }
 
     pub fn refresh_suggestions(&mut self) {
@@ -229,6 +973,7 @@ impl App {
 
         let query_owned = self.input.clone();
         let query = query_owned.as_str();
+        let previous_selection = self.selected_text();
 
         // Phase 1: Get quick suggestions from light models (non-blocking)
         match self.ensemble.predict_light_models(query) {
@@ -236,36 +981,211 @@ impl App {
                 self.suggestions = suggestions
                     .into_iter()
                     .take(self.max_suggestions)
-                    .map(|s| s.text)
                     .collect();
             }
             Err(e) => {
                 // Fallback to simple fuzzy matching if ensemble fails
                 use log::warn;
-                warn!("Light model prediction failed: {}. Falling back to fuzzy matching.", e);
+                warn!("Light model prediction failed: {:#}. Falling back to fuzzy matching.", e);
 
-                let mut scored: Vec<(i64, String)> = Vec::new();
+                let mut scored: Vec<(i64, String, Vec<usize>)> = Vec::new();
                 for line in self.corpus.iter() {
-                    if let Some(score) = MATCHER.fuzzy_match(line, query) {
-                        scored.push((score, line.clone()));
+                    if let Some((score, indices)) = MATCHER.fuzzy_indices(line, query) {
+                        scored.push((score, line.clone(), indices));
                     }
                 }
-                scored.sort_by(|a, b| b.0.cmp(&a.0));
+                scored.sort_by_key(|entry| std::cmp::Reverse(entry.0));
                 self.suggestions = scored
                     .into_iter()
                     .take(self.max_suggestions)
-                    .map(|(_, s)| s)
+                    .map(|(score, s, indices)| {
+                        Suggestion::with_source(s, score as f64, "history").with_match_indices(indices)
+                    })
                     .collect();
+                scale_fuzzy_scores(&mut self.suggestions);
             }
         }
 
-        self.selected = self.selected.min(self.suggestions.len().saturating_sub(1));
+        self.restore_selection(previous_selection);
         self.pending_refresh = false; // Clear pending flag after refresh
 
         // Phase 2: Spawn background tasks for heavy models (non-blocking)
         self.spawn_heavy_model_tasks(query);
     }
 
+    /// Append a line of command output, dropping the oldest past
+    /// `max_output_lines`. The scroll position follows the lines it shows.
+    pub fn push_output_line(&mut self, line: String) {
+        if push_capped(&mut self.output_lines, line, self.max_output_lines) {
+            self.output_dropped += 1;
+            self.output_scroll = self.output_scroll.saturating_sub(1);
+        }
+    }
+
+    pub fn clear_output(&mut self) {
+        self.output_lines.clear();
+        self.output_dropped = 0;
+    }
+
+    /// Show `message` in the input title for `STATUS_MESSAGE_TTL`
+    pub fn set_status(&mut self, message: impl Into<String>) {
+        self.status_message = Some((message.into(), Instant::now()));
+    }
+
+    /// The current status message, if it has not expired yet
+    pub fn status(&self) -> Option<&str> {
+        self.status_message
+            .as_ref()
+            .filter(|(_, shown_at)| shown_at.elapsed() < STATUS_MESSAGE_TTL)
+            .map(|(message, _)| message.as_str())
+    }
+
+    /// Indices into `self.history` that match `history_filter`, most recent first
+    pub fn filtered_history_indices(&self) -> Vec<usize> {
+        self.history
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| {
+                self.history_filter.is_empty()
+                    || MATCHER.fuzzy_match(&entry.cmd, &self.history_filter).is_some()
+            })
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+
+    /// Keep the History-tab selection on a visible entry after the filter changes
+    pub fn snap_history_selection(&mut self) {
+        let visible = self.filtered_history_indices();
+        if !visible.contains(&self.selected_history_index) {
+            self.selected_history_index = visible.first().copied().unwrap_or(0);
+        }
+        self.history_scroll = 0;
+    }
+
+    /// Where `history_search` occurs in the selected History entry's output
+    pub fn history_output_matches(&self) -> Vec<OutputMatch> {
+        self.history
+            .get(self.selected_history_index)
+            .map(|entry| find_output_matches(&entry.output_lines, &self.history_search))
+            .unwrap_or_default()
+    }
+
+    /// Move `step` hits from the current output search match, wrapping
+    /// around, and scroll the History output pane to its line
+    pub fn jump_to_history_match(&mut self, step: isize) {
+        let matches = self.history_output_matches();
+        if matches.is_empty() {
+            self.history_search_match = 0;
+            return;
+        }
+        let current = self.history_search_match.min(matches.len() - 1) as isize;
+        self.history_search_match = (current + step).rem_euclid(matches.len() as isize) as usize;
+        let line = matches[self.history_search_match].line;
+        self.history_scroll = u16::try_from(line).unwrap_or(u16::MAX);
+    }
+
+    /// Delete the selected History-tab command from the database and from
+    /// every matching entry in `self.history`
+    pub fn delete_selected_history(&mut self) -> Result<()> {
+        let Some(command) = self
+            .history
+            .get(self.selected_history_index)
+            .map(|entry| entry.cmd.clone())
+        else {
+            return Ok(());
+        };
+        if let Some(ref pool) = self.db {
+            delete_command_from_history(pool, &command)?;
+        }
+
+        self.history.retain(|entry| entry.cmd != command);
+        self.selected_history_index = self
+            .selected_history_index
+            .min(self.history.len().saturating_sub(1));
+        self.snap_history_selection();
+        Ok(())
+    }
+
+    /// Switch the model highlighted in the overlay on or off, save the
+    /// disabled list to `config_path` and refresh the suggestions
+    pub fn toggle_selected_model(&mut self) {
+        let Some((name, enabled)) = self.ensemble.model_states().into_iter().nth(self.models_selected)
+        else {
+            return;
+        };
+        self.ensemble.set_model_enabled(&name, !enabled);
+        if let Some(ref path) = self.config_path {
+            if let Err(err) = save_disabled_models(path, &self.ensemble.disabled_models()) {
+                warn!("failed to save disabled models: {err:?}");
+                self.set_status("saving config failed");
+            }
+        }
+        self.refresh_suggestions();
+    }
+
+    /// Remember that `command` was picked from the suggestions for the
+    /// current input, so the acceptance model ranks it higher next time.
+    /// Both go through `secrets`, as the typed input can hold the same
+    /// secret as the command.
+    pub fn record_acceptance(&self, command: &str) {
+        let Some(ref pool) = self.db else {
+            return;
+        };
+        let (Some(prefix), Some(command)) =
+            (self.secrets.apply(&self.input), self.secrets.apply(command))
+        else {
+            debug!("not recording a space-prefixed pick or one matching a secret pattern");
+            return;
+        };
+        if let Err(err) = record_acceptance(pool, &prefix, &command) {
+            warn!("failed to record accepted suggestion: {err:?}");
+        }
+    }
+
+    /// Snapshot the input before an edit so it can be undone. A typed char
+    /// (`typing`) right after the previous one joins its undo step, so undo
+    /// takes back a whole run of typing at once.
+    pub fn record_undo(&mut self, typing: bool) {
+        let continues_typing = typing && self.typing_at == Some(self.cursor);
+        if !continues_typing {
+            if self.undo_stack.len() == MAX_UNDO_STEPS {
+                self.undo_stack.remove(0);
+            }
+            self.undo_stack.push((self.input.clone(), self.cursor));
+        }
+        self.redo_stack.clear();
+        self.typing_at = None;
+    }
+
+    /// Call after a typed char has been inserted and the cursor moved past it
+    pub fn continue_typing(&mut self) {
+        self.typing_at = Some(self.cursor);
+    }
+
+    /// Restore the input as it was before the last edit, if there was one
+    pub fn undo(&mut self) {
+        let Some(previous) = self.undo_stack.pop() else {
+            return;
+        };
+        let current = std::mem::replace(&mut self.input, previous.0);
+        self.redo_stack.push((current, self.cursor));
+        self.cursor = previous.1;
+        self.typing_at = None;
+        self.mark_input_changed();
+    }
+
+    /// Reapply the last undone edit, if any
+    pub fn redo(&mut self) {
+        let Some(next) = self.redo_stack.pop() else {
+            return;
+        };
+        let current = std::mem::replace(&mut self.input, next.0);
+        self.undo_stack.push((current, self.cursor));
+        self.cursor = next.1;
+        self.typing_at = None;
+        self.mark_input_changed();
+    }
+
     /// Mark that input has changed, but defer the actual suggestion refresh (debounce)
     pub fn mark_input_changed(&mut self) {
         self.last_input_time = Some(Instant::now());
@@ -273,16 +1193,14 @@ impl App {
     }
 
     /// Check if enough time has passed since last input to refresh suggestions
-    /// Debounce delay: 300ms
+    /// Debounce delay: `self.debounce` (300ms by default)
     pub fn should_refresh_suggestions(&self) -> bool {
-        const DEBOUNCE_MS: u64 = 300;
-
         if !self.pending_refresh {
             return false;
         }
 
         if let Some(last_time) = self.last_input_time {
-            last_time.elapsed() >= Duration::from_millis(DEBOUNCE_MS)
+            last_time.elapsed() >= self.debounce
         } else {
             false
         }
@@ -295,6 +1213,7 @@ impl App {
         for handle in self.heavy_model_tasks.drain(..) {
             handle.abort();
         }
+        self.is_loading_heavy = false;
 
         // Get heavy models from ensemble
         let heavy_models = self.ensemble.get_heavy_models();
@@ -313,28 +1232,59 @@ impl App {
         };
 
         // Spawn a task for each heavy model
-        for model in heavy_models {
+        let timeout = self.heavy_model_timeout;
+        for WeightedModel { model, weight } in heavy_models {
             let query = query.clone();
             let tx = tx.clone();
+            let name = model.name().to_string();
 
             let handle = tokio::spawn(async move {
-                // Run heavy model prediction in blocking task (subprocess calls)
-                let result = tokio::task::spawn_blocking(move || {
-                    model.predict(&query)
-                }).await;
-
-                // Send results through channel
-                if let Ok(Ok(suggestions)) = result {
-                    let _ = tx.send(suggestions);
+                // Run heavy model prediction in blocking task (subprocess calls),
+                // streaming each batch through the channel as it is produced.
+                // Batches are normalized and weighted to match ensemble aggregation.
+                let task_query = query.clone();
+                let timed_out = Arc::new(AtomicBool::new(false));
+                let task_timed_out = timed_out.clone();
+                let result = tokio::time::timeout(
+                    timeout,
+                    tokio::task::spawn_blocking(move || {
+                        model.predict_streaming(&task_query, &mut |mut batch| {
+                            // The blocking thread outlives the timeout; drop late batches
+                            if task_timed_out.load(AtomicOrdering::Relaxed) {
+                                return;
+                            }
+                            normalize_scores(&mut batch);
+                            for suggestion in &mut batch {
+                                suggestion.score *= weight;
+                            }
+                            let _ = tx.send((task_query.clone(), batch));
+                        })
+                    }),
+                )
+                .await;
+
+                match result {
+                    Ok(Ok(Ok(()))) => {}
+                    Ok(Ok(Err(err))) => warn!("{name} model failed for {query:?}: {err:?}"),
+                    Ok(Err(err)) => warn!("{name} model task for {query:?} did not finish: {err}"),
+                    Err(_) => {
+                        timed_out.store(true, AtomicOrdering::Relaxed);
+                        warn!(
+                            "{name} model timed out after {}ms for {query:?}; dropping results",
+                            timeout.as_millis()
+                        );
+                    }
                 }
             });
 
             self.heavy_model_tasks.push(handle);
         }
+        self.is_loading_heavy = true;
     }
 
     /// Poll for heavy model results without blocking
-    /// Merges results into current suggestions if they arrive
+    /// Merges results into current suggestions if they arrive, dropping any
+    /// predicted for an input the user has since changed
     pub fn poll_heavy_model_results(&mut self) {
         let mut pending_results = Vec::new();
         {
@@ -344,55 +1294,134 @@ impl App {
             };
 
             // Non-blocking check for results
-            while let Ok(heavy_suggestions) = rx.try_recv() {
-                pending_results.push(heavy_suggestions);
+            while let Ok((query, heavy_suggestions)) = rx.try_recv() {
+                if query == self.input {
+                    pending_results.push(heavy_suggestions);
+                } else {
+                    debug!("dropping stale heavy model results for {query:?}");
+                }
             }
         }
 
         for heavy_suggestions in pending_results {
             self.merge_heavy_model_suggestions(heavy_suggestions);
         }
+
+        // Once every task has finished nothing more is coming; a batch sent
+        // just before a task finished is still merged on the next poll
+        if self.is_loading_heavy && self.heavy_model_tasks.iter().all(|h| h.is_finished()) {
+            self.is_loading_heavy = false;
+        }
     }
 
     /// Merge heavy model suggestions into current suggestion list
-    /// Uses the same scoring logic as ensemble aggregation
+    /// Uses the same scoring logic as ensemble aggregation: heavy scores arrive
+    /// already multiplied by their model weight and are summed onto the
+    /// weighted light-model scores
     fn merge_heavy_model_suggestions(&mut self, heavy_suggestions: Vec<Suggestion>) {
         use std::collections::HashMap;
 
-        // Build a map of existing suggestions with their positions
-        let mut score_map: HashMap<String, f64> = HashMap::new();
+        let previous_selection = self.selected_text();
 
-        for (idx, text) in self.suggestions.iter().enumerate() {
-            // Higher positt on = lower score in the list
-            let position_score = (self.suggestions.len() - idx) as f64;
-            score_map.insert(text.clone(), position_score);
-        }
+        let dedup = self.ensemble.dedup();
+        let mut ranked = std::mem::take(&mut self.suggestions);
+        let mut index: HashMap<String, usize> = ranked
+            .iter()
+            .enumerate()
+            .map(|(idx, s)| (dedup.key(&s.text).into_owned(), idx))
+            .collect();
 
-        // Add heavy model suggestions with their scores
         for suggestion in heavy_suggestions {
-            let entry = score_map.entry(suggestion.text).or_insert(0.0);
-            *entry += suggestion.score;
+            let key = dedup.key(&suggestion.text).into_owned();
+            match index.get(&key) {
+                Some(&idx) => ranked[idx].score += suggestion.score,
+                None => {
+                    index.insert(key, ranked.len());
+                    ranked.push(suggestion);
+                }
+            }
         }
 
-        // Re-rank all suggestions
-        let mut ranked: Vec<(String, f64)> = score_map.into_iter().collect();
-        ranked.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
-
-        // Update suggestions list
-        self.suggestions = ranked
-            .into_iter()
-            .take(self.max_suggestions)
-            .map(|(text, _)| text)
-            .collect();
+        // Stable sort so equal scores keep their current order
+        ranked.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal));
+        ranked.truncate(self.max_suggestions);
+        self.suggestions = ranked;
 
-        // Adjust selection to stay in bounds
-        self.selected = self.selected.min(self.suggestions.len().saturating_sub(1));
+        self.restore_selection(previous_selection);
     }
 
-}
+    /// Text of the currently highlighted suggestion, if any
+    fn selected_text(&self) -> Option<String> {
+        self.suggestions.get(self.selected).map(|s| s.text.clone())
+    }
 
-pub fn load_history_lines(files: Vec<PathBuf>, unique: bool) -> Result<Vec<String>> {
-    let mut paths = files;
+    /// Re-point `selected` at `previous` if it survived a refresh,
+    /// otherwise just clamp it to stay in bounds
+    fn restore_selection(&mut self, previous: Option<String>) {
+        let found = previous
+            .and_then(|text| self.suggestions.iter().position(|s| s.text == text));
+        self.selected = match found {
+            Some(idx) => idx,
+            None => self.selected.min(self.suggestions.len().saturating_sub(1)),
+        };
+    }
+
+}
+
+/// Drop repeated history lines, keeping the first occurrence as written.
+/// With `ignore_case`, lines that differ only in case or whitespace count as
+/// repeats too.
+pub fn dedup_lines(lines: &mut Vec<String>, ignore_case: bool) {
+    let mode = if ignore_case {
+        DedupMode::IgnoreCase
+    } else {
+        DedupMode::Exact
+    };
+    let mut seen = AHashSet::with_capacity(lines.len());
+    lines.retain(|s| seen.insert(mode.key(s).into_owned()));
+}
+
+/// Read the history files into the in-memory corpus. With `max_corpus`, only
+/// the last that many lines (after dedup) are kept; this bounds the fuzzy
+/// model alone, as the prefix/freq models query SQLite instead.
+pub fn load_history_lines(
+    files: Vec<PathBuf>,
+    unique: bool,
+    ignore_case: bool,
+    max_corpus: Option<usize>,
+    encoding: HistoryEncoding,
+    secrets: &SecretFilter,
+) -> Result<Vec<String>> {
+    let mut lines: Vec<String> = Vec::new();
+    for p in resolve_history_paths(files)? {
+        if p.exists() {
+            let read = read_history_file(&p, encoding).with_context(|| format!("reading {p:?}"))?;
+            // Secrets are kept out of suggestions just as they are kept out of the store
+            lines.extend(read.iter().filter_map(|line| secrets.apply(line)).map(Cow::into_owned));
+        }
+    }
+
+    // Files are oldest first, so the newest lines are at the end
+    if unique && max_corpus.is_some() {
+        // Keep the latest copy of each line, so a command re-run recently is
+        // not evicted as if it were old
+        lines.reverse();
+        dedup_lines(&mut lines, ignore_case);
+        lines.reverse();
+    } else if unique {
+        dedup_lines(&mut lines, ignore_case);
+    }
+    if let Some(max) = max_corpus {
+        let excess = lines.len().saturating_sub(max);
+        lines.drain(..excess);
+    }
+    Ok(lines)
+}
+
+/// `files` expanded by `expand_history_paths`, or the usual shell history
+/// files in `$HOME` when none are given
+fn resolve_history_paths(files: Vec<PathBuf>) -> Result<Vec<PathBuf>> {
+    let mut paths = expand_history_paths(files);
 
     if paths.is_empty() {
         if let Some(ud) = UserDirs::new() {
@@ -407,38 +1436,232 @@ pub fn load_history_lines(files: Vec<PathBuf>, unique: bool) -> Result<Vec<Strin
     if paths.is_empty() {
         bail!("No history files provided and HOME not found");
     }
+    Ok(paths)
+}
 
-    let mut lines: Vec<String> = Vec::new();
-    for p in paths {
-        if p.exists() {
-            lines.extend(read_history_file(&p).with_context(|| format!("reading {p:?}"))?);
+/// Expand a leading `~/`, glob patterns (`~/.zsh_sessions/*.history`) and
+/// directories (every file directly inside, by name) into file paths. Plain
+/// paths pass through untouched, whether or not they exist.
+fn expand_history_paths(files: Vec<PathBuf>) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    for file in files {
+        let file = expand_tilde(&file);
+        let pattern = file.to_string_lossy();
+        if pattern.contains(['*', '?', '[']) {
+            match glob::glob(&pattern) {
+                Ok(matches) => paths.extend(matches.flatten().filter(|p| p.is_file())),
+                Err(err) => warn!("invalid history file pattern {pattern:?}: {err}"),
+            }
+        } else if file.is_dir() {
+            let mut entries: Vec<PathBuf> = std::fs::read_dir(&file)
+                .map(|dir| {
+                    dir.flatten()
+                        .map(|entry| entry.path())
+                        .filter(|p| p.is_file())
+                        .collect()
+                })
+                .unwrap_or_default();
+            entries.sort();
+            paths.extend(entries);
+        } else {
+            paths.push(file);
         }
     }
+    paths
+}
 
-    if unique {
-        let mut seen = AHashSet::with_capacity(lines.len());
-        lines.retain(|s| seen.insert(s.to_owned()));
+/// `~/x` to `$HOME/x`, for patterns quoted so the shell left them alone
+fn expand_tilde(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), UserDirs::new()) {
+        (Ok(rest), Some(dirs)) => dirs.home_dir().join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// How history files are decoded: lossy UTF-8 unless set otherwise. A byte
+/// order mark at the start of a file wins either way, so UTF-8 BOMs are
+/// dropped and UTF-16 files copied from Windows read without a setting.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HistoryEncoding(&'static Encoding);
+
+impl Default for HistoryEncoding {
+    fn default() -> Self {
+        Self(UTF_8)
+    }
+}
+
+impl FromStr for HistoryEncoding {
+    type Err = anyhow::Error;
+
+    /// Accepts WHATWG encoding labels such as `utf-8`, `latin1` (read as
+    /// windows-1252), `utf-16le` and `utf-16be`
+    fn from_str(s: &str) -> Result<Self> {
+        Encoding::for_label(s.trim().as_bytes())
+            .map(Self)
+            .ok_or_else(|| {
+                anyhow!("unknown encoding {s:?} (e.g. utf-8, latin1, utf-16le, utf-16be)")
+            })
+    }
+}
+
+impl HistoryEncoding {
+    /// The encoding of a file starting with `head`, and the length of its BOM
+    fn sniff(self, head: &[u8]) -> (&'static Encoding, usize) {
+        Encoding::for_bom(head).unwrap_or((self.0, 0))
+    }
+
+    /// Decode a whole file, replacing malformed sequences with U+FFFD
+    pub fn decode(self, bytes: &[u8]) -> Cow<'_, str> {
+        let (encoding, bom) = self.sniff(bytes);
+        encoding.decode_without_bom_handling(&bytes[bom..]).0
+    }
+}
+
+/// `\n` as encoded in `encoding`; other encodings history files come in keep it as one byte
+fn encoded_newline(encoding: &'static Encoding) -> &'static [u8] {
+    if encoding == UTF_16LE {
+        b"\n\0"
+    } else if encoding == UTF_16BE {
+        b"\0\n"
+    } else {
+        b"\n"
     }
-    Ok(lines)
 }
 
-pub fn read_history_file(path: &Path) -> Result<Vec<String>> {
+pub fn read_history_file(path: &Path, encoding: HistoryEncoding) -> Result<Vec<String>> {
     let mut file = File::open(path).with_context(|| format!("opening {path:?}"))?;
     let mut buf = Vec::new();
     file.read_to_end(&mut buf)?;
-    Ok(String::from_utf8_lossy(&buf)
-        .lines()
-        .map(|l| l.to_owned())
-        .collect())
+    Ok(parse_history_text(path, &encoding.decode(&buf)))
+}
+
+fn parse_history_text(path: &Path, text: &str) -> Vec<String> {
+    if is_fish_history(path, text) {
+        parse_fish_history(text)
+    } else {
+        parse_history_lines(text)
+    }
+}
+
+/// Fish history is detected by file name or by sniffing the first `- cmd:` entry
+fn is_fish_history(path: &Path, text: &str) -> bool {
+    let named_fish = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| name.ends_with("fish_history"))
+        .unwrap_or(false);
+    named_fish
+        || text
+            .lines()
+            .find(|line| !line.trim().is_empty())
+            .map(|line| line.starts_with("- cmd: "))
+            .unwrap_or(false)
+}
+
+/// Extract commands from fish's YAML-like history (`- cmd: ...` / `  when: ...`).
+/// Metadata lines such as `when:` and `paths:` are skipped.
+fn parse_fish_history(text: &str) -> Vec<String> {
+    text.lines()
+        .filter_map(|line| line.strip_prefix("- cmd: "))
+        .map(unescape_fish_command)
+        .collect()
+}
+
+/// Undo fish's escaping of backslashes and newlines inside a `cmd:` value
+fn unescape_fish_command(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len());
+    let mut chars = raw.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            out.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('\\') => out.push('\\'),
+            Some(other) => {
+                out.push('\\');
+                out.push(other);
+            }
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// Split raw history text into logical entries.
+/// Lines in zsh's extended format (`: <epoch>:<elapsed>;cmd`) have the prefix
+/// stripped, and their backslash-escaped newlines are joined back into one entry.
+/// Lines without the prefix are kept verbatim.
+fn parse_history_lines(text: &str) -> Vec<String> {
+    let mut entries = Vec::new();
+    let mut pending: Option<String> = None;
+
+    for line in text.lines() {
+        if let Some(mut entry) = pending.take() {
+            entry.push('\n');
+            match line.strip_suffix('\\') {
+                Some(head) => {
+                    entry.push_str(head);
+                    pending = Some(entry);
+                }
+                None => {
+                    entry.push_str(line);
+                    entries.push(entry);
+                }
+            }
+            continue;
+        }
+
+        match strip_zsh_extended_prefix(line) {
+            Some(cmd) => match cmd.strip_suffix('\\') {
+                Some(head) => pending = Some(head.to_owned()),
+                None => entries.push(cmd.to_owned()),
+            },
+            None => entries.push(line.to_owned()),
+        }
+    }
+
+    // A dangling continuation at EOF still counts as an entry
+    if let Some(entry) = pending {
+        entries.push(entry);
+    }
+
+    entries
+}
+
+/// Push `line` onto `lines`, dropping the oldest line once it holds `max`.
+/// Returns whether a line was dropped.
+pub fn push_capped(lines: &mut VecDeque<String>, line: String, max: usize) -> bool {
+    let dropped = lines.len() >= max.max(1);
+    if dropped {
+        lines.pop_front();
+    }
+    lines.push_back(line);
+    dropped
+}
+
+/// Return the command part of a zsh extended history line (`: 1700000000:0;git status`)
+fn strip_zsh_extended_prefix(line: &str) -> Option<&str> {
+    let rest = line.strip_prefix(": ")?;
+    let (epoch, rest) = rest.split_once(':')?;
+    let (elapsed, cmd) = rest.split_once(';')?;
+    let is_digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    if is_digits(epoch) && is_digits(elapsed) {
+        Some(cmd)
+    } else {
+        None
+    }
 }
 
 fn load_recent_history(pool: &SqlitePool, limit: usize) -> Result<Vec<HistoryEntry>> {
     pool.query_collect(
-        "SELECT command, output FROM command_executions ORDER BY executed_at DESC LIMIT ?1",
+        "SELECT command, output, exit_code FROM command_executions ORDER BY executed_at DESC LIMIT ?1",
         vec![Value::Integer(limit as i64)],
         |row| {
             let command: String = row.get(0)?;
             let output_str: String = row.get(1).unwrap_or_default();
+            let exit_code: Option<i64> = row.get(2)?;
 
             let output_lines: Vec<String> = output_str
                 .lines()
@@ -448,95 +1671,495 @@ fn load_recent_history(pool: &SqlitePool, limit: usize) -> Result<Vec<HistoryEnt
             Ok(HistoryEntry {
                 cmd: command,
                 output_lines,
+                exit_code: exit_code.map(|code| code as i32),
             })
         },
     )
 }
 
+/// Which suggestion models to build and how to weight them, resolved once
+/// from the command line and config file
+#[derive(Clone, Debug, Default)]
+pub struct ModelOptions {
+    /// Also enables the SQLite-backed light models
+    pub enable_embedding: bool,
+    pub embedding_model: Option<PathBuf>,
+    pub embedding_search: EmbeddingSearch,
+    pub enable_llm: bool,
+    pub llm_model: Option<PathBuf>,
+    pub llm_device: LlmDevice,
+    pub weights: ModelWeights,
+    /// How suggestions from different models are merged
+    pub dedup: DedupMode,
+    /// See [`PrefixModel::with_word_boundary`]
+    pub prefix_word_boundary: bool,
+    /// Models left out of the ensemble, by name
+    pub disabled_models: Vec<String>,
+}
+
+/// Build the suggestion ensemble shared by the TUI and `serve`: fuzzy history
+/// always, SQLite-backed models and embeddings when a database is available,
+/// and the LLM when enabled
+pub fn build_ensemble(
+    corpus: Arc<[String]>,
+    top: usize,
+    db: Option<&SqlitePool>,
+    options: &ModelOptions,
+    extra_models: ModelRegistry,
+) -> Ensemble {
+    let weights = &options.weights;
+    let mut builder = EnsembleBuilder::new()
+        .with_dedup(options.dedup)
+        .with_light_model_weighted(
+            FuzzyHistoryModel::new(corpus.clone()).with_limit(top * FUZZY_CANDIDATES_PER_SUGGESTION),
+            weights.history,
+        );
+
+    // Add database-backed models if available
+    if options.enable_embedding {
+        if let Some(pool) = db {
+            let prefix = prefix_model(pool.clone(), options.prefix_word_boundary);
+            builder = builder
+                .with_light_model_weighted(prefix, weights.prefix)
+                .with_light_model_weighted(FreqModel::new(pool.clone()), weights.freq)
+                .with_light_model_weighted(FrecencyModel::new(pool.clone()), weights.frecency)
+                .with_light_model_weighted(FtsModel::new(pool.clone()), weights.fts)
+                .with_light_model_weighted(AliasModel::with_sql_store(pool.clone()), weights.alias)
+                .with_light_model_weighted(AcceptanceModel::new(pool.clone()), weights.acceptance);
+            if let Some(model) = load_ngram_model(pool) {
+                builder = builder.with_light_model_weighted(model, weights.ngram);
+            }
+
+            if let Some(model) = load_embedding_model(
+                pool,
+                &corpus,
+                options.embedding_model.clone(),
+                options.embedding_search,
+            ) {
+                builder = builder.with_heavy_model_weighted(model, weights.embedding);
+            }
+        }
+    }
+
+    // Add LLM model as heavy model if enabled
+    if options.enable_llm {
+        if let Some(model) = load_llm_model(options.llm_model.clone(), options.llm_device) {
+            builder = builder.with_heavy_model_weighted(model, weights.llm);
+        }
+    }
+
+    let ensemble = builder.with_registry(extra_models).build();
+    for name in &options.disabled_models {
+        ensemble.set_model_enabled(name, false);
+    }
+    ensemble
+}
+
+/// Next-token statistics over the stored history, or `None` (logged) if
+/// they could not be read
+fn load_ngram_model(pool: &SqlitePool) -> Option<NgramModel> {
+    NgramModel::load(pool)
+        .inspect_err(|err| warn!("failed to load ngram model: {err:?}"))
+        .ok()
+}
+
+/// Embedding model over `pool` after learning `corpus`, or `None` (logged)
+/// when llama-embedding or its model is unavailable
+fn load_embedding_model(
+    pool: &SqlitePool,
+    corpus: &[String],
+    embedding_model: Option<PathBuf>,
+    embedding_search: EmbeddingSearch,
+) -> Option<EmbeddingModel> {
+    let client = match LlamaEmbeddingClient::from_env_or(embedding_model) {
+        Ok(client) => client,
+        Err(err) => {
+            warn!("failed to construct embedding client: {err:?}");
+            return None;
+        }
+    };
+    let model = EmbeddingModel::new(EmbeddingStore::new(pool.clone()), client)
+        .with_search(embedding_search);
+    match model.warm_up() {
+        Ok(_) => {
+            if let Err(err) = model.learn(corpus) {
+                warn!("embedding warmup failed: {err:?}");
+            }
+            info!("embedding model enabled via Ollama");
+            Some(model)
+        }
+        Err(err) => {
+            warn!("skipping embedding model; Ollama embeddings unavailable: {err:?}");
+            None
+        }
+    }
+}
+
+/// LLM generator for `llm_model`, or `None` (logged) when no model path is set
+fn load_llm_model(llm_model: Option<PathBuf>, llm_device: LlmDevice) -> Option<LlmModel> {
+    let Some(model_path) = llm_model else {
+        warn!("--enable-llm specified but --llm-model not provided");
+        return None;
+    };
+    let llm_config = LlmConfig {
+        model_path,
+        device: llm_device,
+        ..Default::default()
+    }
+    .with_prompt_from_env();
+    Some(LlmModel::new(llm_config))
+}
+
+/// PrefixModel that favours commands previously run in the current directory
+fn prefix_model(pool: SqlitePool, word_boundary: bool) -> PrefixModel {
+    let model = PrefixModel::new(pool).with_word_boundary(word_boundary);
+    match std::env::current_dir() {
+        Ok(cwd) => model.with_cwd(cwd),
+        Err(_) => model,
+    }
+}
+
 fn hash_command(command: &str) -> String {
     let mut hasher = Sha256::new();
     hasher.update(command.as_bytes());
     encode(hasher.finalize())
 }
 
-pub fn persist_command_to_history(pool: &SqlitePool, command: &str, session_id: &str) -> Result<()> {
+/// Record one run of `command` with its captured `output`. `exit_code` is
+/// `None` when the process was killed by a signal and no status is known.
+/// Commands `secrets` rejects are not recorded.
+pub fn persist_command_to_history(
+    pool: &SqlitePool,
+    command: &str,
+    session_id: &str,
+    output: &str,
+    exit_code: Option<i32>,
+    secrets: &SecretFilter,
+) -> Result<()> {
+    let Some(command) = secrets.apply(command) else {
+        debug!("not recording a space-prefixed command or one matching a secret pattern");
+        return Ok(());
+    };
     let trimmed = command.trim();
     if trimmed.is_empty() {
         return Ok(());
     }
 
     let hash = hash_command(trimmed);
+    let cwd = std::env::current_dir()
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let exit_code = exit_code.map_or(Value::Null, |code| Value::Integer(code.into()));
 
     // Update history table (for frequency counting)
     pool.execute(
         r#"
-        INSERT INTO history (command, hash, count, source, output)
-        VALUES (?1, ?2, 1, 'tui', '')
+        INSERT INTO history (command, hash, count, source, output, cwd, last_used, last_exit_code)
+        VALUES (?1, ?2, 1, 'tui', '', ?3, CURRENT_TIMESTAMP, ?4)
         ON CONFLICT(hash) DO UPDATE SET
             count = count + 1,
             source = 'tui',
-            created_at = CURRENT_TIMESTAMP;
+            cwd = excluded.cwd,
+            created_at = CURRENT_TIMESTAMP,
+            last_used = CURRENT_TIMESTAMP,
+            last_exit_code = excluded.last_exit_code;
     "#,
         vec![
             Value::Text(trimmed.to_string()),
             Value::Text(hash),
+            Value::Text(cwd.clone()),
+            exit_code.clone(),
         ],
     )?;
 
     // Insert into command_executions (for full history with output)
     pool.execute(
         r#"
-        INSERT INTO command_executions (command, output, session_id, executed_at)
-        VALUES (?1, '', ?2, CURRENT_TIMESTAMP);
+        INSERT INTO command_executions (command, output, session_id, cwd, exit_code, executed_at)
+        VALUES (?1, ?2, ?3, ?4, ?5, CURRENT_TIMESTAMP);
     "#,
         vec![
             Value::Text(trimmed.to_string()),
+            Value::Text(output.to_string()),
             Value::Text(session_id.to_string()),
+            Value::Text(cwd),
+            exit_code,
         ],
     )?;
 
     Ok(())
 }
 
-pub fn import_shell_history_to_db(pool: &SqlitePool, files: &[PathBuf]) -> Result<()> {
-    let lines = load_history_lines(files.to_vec(), true)?; // unique=true to avoid duplicates in memory
+/// Remove every execution of `command` and its frequency row from the store
+pub fn delete_command_from_history(pool: &SqlitePool, command: &str) -> Result<()> {
+    let trimmed = command.trim();
+    pool.execute(
+        "DELETE FROM command_executions WHERE command = ?1",
+        vec![Value::Text(trimmed.to_string())],
+    )?;
+    pool.execute(
+        "DELETE FROM history WHERE hash = ?1",
+        vec![Value::Text(hash_command(trimmed))],
+    )?;
+    Ok(())
+}
+
+/// `meta` key holding the TUI input line as it was when the TUI last quit
+const TUI_INPUT_META_KEY: &str = "tui_input";
+/// Saved TUI input older than this is not restored
+pub const TUI_INPUT_MAX_AGE: Duration = Duration::from_secs(60 * 60);
+
+fn unix_seconds() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// Remember the TUI input line (kept in `meta.value`) and when it was saved
+pub fn save_tui_input(pool: &SqlitePool, input: &str) -> Result<()> {
+    pool.execute(
+        r#"
+        INSERT INTO meta (key, path, value, mtime) VALUES (?1, '', ?2, ?3)
+        ON CONFLICT(key) DO UPDATE SET value = excluded.value, mtime = excluded.mtime;
+    "#,
+        vec![
+            Value::Text(TUI_INPUT_META_KEY.to_string()),
+            Value::Text(input.to_string()),
+            Value::Integer(unix_seconds()),
+        ],
+    )
+}
+
+/// The input saved by [`save_tui_input`], unless it is blank or older than `max_age`
+pub fn load_tui_input(pool: &SqlitePool, max_age: Duration) -> Result<Option<String>> {
+    let saved = pool
+        .query_collect(
+            "SELECT value, mtime FROM meta WHERE key = ?1 AND value IS NOT NULL",
+            vec![Value::Text(TUI_INPUT_META_KEY.to_string())],
+            |row| Ok((row.get::<String>(0)?, row.get::<i64>(1)?)),
+        )?
+        .into_iter()
+        .next();
+    Ok(saved.and_then(|(input, saved_at)| {
+        let age = unix_seconds().saturating_sub(saved_at);
+        (age <= max_age.as_secs() as i64 && !input.trim().is_empty()).then_some(input)
+    }))
+}
+
+const TUI_VIEW_META_KEY: &str = "tui_view";
+
+/// What the TUI reopens with, kept as JSON in `meta.value`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TuiView {
+    pub tab: Tab,
+    pub history_filter: String,
+}
+
+pub fn save_tui_view(pool: &SqlitePool, view: &TuiView) -> Result<()> {
+    pool.execute(
+        r#"
+        INSERT INTO meta (key, path, value, mtime) VALUES (?1, '', ?2, ?3)
+        ON CONFLICT(key) DO UPDATE SET value = excluded.value, mtime = excluded.mtime;
+    "#,
+        vec![
+            Value::Text(TUI_VIEW_META_KEY.to_string()),
+            Value::Text(serde_json::to_string(view)?),
+            Value::Integer(unix_seconds()),
+        ],
+    )
+}
 
-    for command in lines {
-        let trimmed = command.trim();
-        if trimmed.is_empty() {
+/// The view saved by [`save_tui_view`]; one that no longer parses is ignored
+pub fn load_tui_view(pool: &SqlitePool) -> Result<Option<TuiView>> {
+    let saved = pool
+        .query_collect(
+            "SELECT value FROM meta WHERE key = ?1 AND value IS NOT NULL",
+            vec![Value::Text(TUI_VIEW_META_KEY.to_string())],
+            |row| Ok(row.get::<String>(0)?),
+        )?
+        .into_iter()
+        .next();
+    Ok(saved.and_then(|json| {
+        serde_json::from_str(&json)
+            .inspect_err(|err| debug!("ignoring saved tui view {json:?}: {err}"))
+            .ok()
+    }))
+}
+
+/// `meta` key prefix for per-file shell history import progress
+const SHELL_IMPORT_META_PREFIX: &str = "shell_history:";
+
+/// Import new shell history into the store. Each file's mtime and the byte
+/// offset read up to are kept in `meta`, so unchanged files are skipped and
+/// appended ones only have their new lines read. Commands `secrets` rejects
+/// are left out.
+pub fn import_shell_history_to_db(
+    pool: &SqlitePool,
+    files: &[PathBuf],
+    secrets: &SecretFilter,
+    encoding: HistoryEncoding,
+) -> Result<()> {
+    for path in resolve_history_paths(files.to_vec())? {
+        if !path.exists() {
             continue;
         }
+        if let Err(err) = import_history_file(pool, &path, secrets, encoding) {
+            warn!("failed to import {}: {err:?}", path.display());
+        }
+    }
+    Ok(())
+}
 
-        let hash = hash_command(trimmed);
+/// Import the part of `path` not seen by a previous run; returns the number
+/// of distinct commands written
+fn import_history_file(
+    pool: &SqlitePool,
+    path: &Path,
+    secrets: &SecretFilter,
+    encoding: HistoryEncoding,
+) -> Result<usize> {
+    let metadata = std::fs::metadata(path).with_context(|| format!("stat {path:?}"))?;
+    let mtime = metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_nanos() as i64)
+        .unwrap_or(0);
+    let key = format!("{SHELL_IMPORT_META_PREFIX}{}", path.display());
+
+    let len = metadata.len() as i64;
+
+    let previous = pool
+        .query_collect(
+            "SELECT mtime, byte_len, byte_offset FROM meta WHERE key = ?1",
+            vec![Value::Text(key.clone())],
+            |row| Ok((row.get::<i64>(0)?, row.get::<i64>(1)?, row.get::<i64>(2)?)),
+        )?
+        .into_iter()
+        .next();
 
-        // Insert into history table with source='shell'
-        // On conflict, just increment count (don't change source from 'tui' to 'shell')
-        pool.execute(
-            r#"
-            INSERT INTO history (command, hash, count, source, output)
-            VALUES (?1, ?2, 1, 'shell', '')
-            ON CONFLICT(hash) DO UPDATE SET
-                count = count + 1,
-                created_at = CURRENT_TIMESTAMP;
-        "#,
-            vec![
-                Value::Text(trimmed.to_string()),
-                Value::Text(hash),
-            ],
-        ).ok(); // Ignore errors for individual commands
+    let mut file = File::open(path).with_context(|| format!("opening {path:?}"))?;
+    // An append within the mtime resolution leaves mtime alone but not the size
+    if let Some((seen_mtime, seen_len, _)) = previous {
+        if (seen_mtime, seen_len) == (mtime, len) {
+            return Ok(0);
+        }
+    }
+    let mut head = Vec::with_capacity(3);
+    (&mut file).take(3).read_to_end(&mut head)?;
+    let (encoding, bom) = encoding.sniff(&head);
+    let newline = encoded_newline(encoding);
+    let start = match previous {
+        Some((_, _, offset))
+            if was_appended_to(&mut file, offset as u64, metadata.len(), newline)? =>
+        {
+            offset as u64
+        }
+        // New, truncated or rewritten file: read it all again
+        _ => 0,
     }
+    .max(bom as u64);
 
-    Ok(())
+    file.seek(SeekFrom::Start(start))?;
+    let mut buf = Vec::new();
+    file.read_to_end(&mut buf)?;
+    // Leave a trailing partial line (the shell may be mid-write) for next time
+    let complete = buf
+        .chunks_exact(newline.len())
+        .rposition(|unit| unit == newline)
+        .map_or(0, |i| (i + 1) * newline.len());
+    let text = encoding.decode_without_bom_handling(&buf[..complete]).0;
+    let mut lines = parse_history_text(path, &text);
+    dedup_lines(&mut lines, false);
+
+    let mut imported = 0usize;
+    for batch in lines.chunks(IMPORT_BATCH_ROWS) {
+        let mut statements = Vec::with_capacity(batch.len());
+        for command in batch {
+            let Some(command) = secrets.apply(command) else {
+                continue;
+            };
+            let trimmed = command.trim();
+            if trimmed.is_empty() {
+                continue;
+            }
+            // On conflict, just increment count (don't change source from 'tui' to
+            // 'shell'); the lines carry no run time, so `last_used` is left alone
+            statements.push((
+                r#"
+                INSERT INTO history (command, hash, count, source, output)
+                VALUES (?1, ?2, 1, 'shell', '')
+                ON CONFLICT(hash) DO UPDATE SET
+                    count = count + 1;
+            "#,
+                vec![
+                    Value::Text(trimmed.to_string()),
+                    Value::Text(hash_command(trimmed)),
+                ],
+            ));
+            imported += 1;
+        }
+        pool.execute_in_transaction(statements)?;
+    }
+
+    pool.execute(
+        r#"
+        INSERT INTO meta (key, path, mtime, byte_len, byte_offset) VALUES (?1, ?2, ?3, ?4, ?5)
+        ON CONFLICT(key) DO UPDATE SET
+            path = excluded.path,
+            mtime = excluded.mtime,
+            byte_len = excluded.byte_len,
+            byte_offset = excluded.byte_offset;
+    "#,
+        vec![
+            Value::Text(key),
+            Value::Text(path.to_string_lossy().to_string()),
+            Value::Integer(mtime),
+            Value::Integer(len),
+            Value::Integer((start + complete as u64) as i64),
+        ],
+    )?;
+
+    Ok(imported)
 }
 
+/// Whether the file still ends a line at `offset`, i.e. it has only grown
+/// since it was last read that far
+fn was_appended_to(file: &mut File, offset: u64, len: u64, newline: &[u8]) -> Result<bool> {
+    let width = newline.len() as u64;
+    if offset < width || offset > len {
+        return Ok(offset == 0);
+    }
+    let mut last = vec![0u8; newline.len()];
+    file.seek(SeekFrom::Start(offset - width))?;
+    file.read_exact(&mut last)?;
+    Ok(last == newline)
+}
+
+/// Fuzzy history matches kept per requested suggestion, leaving the ensemble
+/// room to re-rank against other models
+const FUZZY_CANDIDATES_PER_SUGGESTION: usize = 5;
+
+/// Skim fuzzy matches of the input against the in-memory history lines
 #[derive(Debug)]
-struct FuzzyHistoryModel {
-    corpus: Vec<String>,
+pub struct FuzzyHistoryModel {
+    corpus: Arc<[String]>,
+    /// Keep only the best `limit` matches; `None` returns every match
+    limit: Option<usize>,
 }
 
 impl FuzzyHistoryModel {
-    fn new(corpus: Vec<String>) -> Self {
-        Self { corpus }
+    pub fn new(corpus: impl Into<Arc<[String]>>) -> Self {
+        Self {
+            corpus: corpus.into(),
+            limit: None,
+        }
+    }
+
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
     }
 }
 
@@ -546,22 +2169,966 @@ impl SuggestModel for FuzzyHistoryModel {
             return Ok(Vec::new());
         }
 
-        let mut scored: Vec<(f64, String)> = Vec::new();
-        for line in &self.corpus {
-            if let Some(score) = MATCHER.fuzzy_match(line, input) {
-                scored.push((score as f64, line.clone()));
+        // Min-heap of (score, earliest line first) holding the best matches so
+        // far; lines are referenced by index and only cloned once selected
+        let limit = self.limit.unwrap_or(usize::MAX);
+        let mut best: BinaryHeap<Reverse<(i64, Reverse<usize>)>> = BinaryHeap::new();
+        if limit > 0 {
+            for (idx, line) in self.corpus.iter().enumerate() {
+                let Some(score) = MATCHER.fuzzy_match(line, input) else {
+                    continue;
+                };
+                if best.len() < limit {
+                    best.push(Reverse((score, Reverse(idx))));
+                } else if best.peek().is_some_and(|Reverse(worst)| (score, Reverse(idx)) > *worst) {
+                    best.pop();
+                    best.push(Reverse((score, Reverse(idx))));
+                }
             }
         }
 
-        scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
-
-        Ok(scored
+        // Sorting ascending by `Reverse` yields best score first, ties in corpus order
+        let mut suggestions: Vec<Suggestion> = best
+            .into_sorted_vec()
             .into_iter()
-            .map(|(score, text)| Suggestion::with_source(text, score, "history"))
-            .collect())
+            .map(|Reverse((score, Reverse(idx)))| {
+                let text = &self.corpus[idx];
+                let indices = MATCHER
+                    .fuzzy_indices(text, input)
+                    .map(|(_, indices)| indices)
+                    .unwrap_or_default();
+                Suggestion::with_source(text.clone(), score as f64, "history")
+                    .with_match_indices(indices)
+            })
+            .collect();
+        scale_fuzzy_scores(&mut suggestions);
+        Ok(suggestions)
     }
 
     fn weight(&self) -> f64 {
         1.0
     }
+
+    fn name(&self) -> &str {
+        "history"
+    }
+}
+
+/// Map raw skim scores, which can be negative or run into the hundreds, onto
+/// (0, 1] with the best match at 1.0. Batches containing non-positive scores
+/// are shifted up first, so relative order is unchanged either way.
+fn scale_fuzzy_scores(suggestions: &mut [Suggestion]) {
+    if suggestions.is_empty() {
+        return;
+    }
+    let (min, max) = suggestions
+        .iter()
+        .map(|s| s.score)
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), score| {
+            (lo.min(score), hi.max(score))
+        });
+    let offset = if min <= 0.0 { 1.0 - min } else { 0.0 };
+    for suggestion in suggestions.iter_mut() {
+        suggestion.score = (suggestion.score + offset) / (max + offset);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_app() -> App {
+        App::new(Vec::new(), None, &AppOptions { top: 10, ..AppOptions::default() }).unwrap()
+    }
+
+    #[test]
+    fn default_dangerous_patterns_catch_common_footguns() {
+        let dangerous = DangerousCommands::default();
+        for command in [
+            "rm -rf build",
+            "sudo rm -r /tmp/x",
+            "rm foo -f",
+            "git reset --hard HEAD~1",
+            "git push origin main --force",
+            "git clean -fdx",
+            "kubectl -n prod delete pod web-0",
+            "dd if=/dev/zero of=/dev/sda",
+            "psql -c 'DROP TABLE users'",
+        ] {
+            assert!(dangerous.is_match(command), "{command}");
+        }
+        for command in ["rm notes.txt", "git reset HEAD", "git push", "kubectl get pods", "ls -rf"] {
+            assert!(!dangerous.is_match(command), "{command}");
+        }
+        assert!(!DangerousCommands::new(&[]).unwrap().is_match("rm -rf /"));
+        assert!(DangerousCommands::new(&["(".to_string()]).is_err());
+    }
+
+    #[test]
+    fn greps_history_newest_first() {
+        let lines: Vec<String> = [
+            "docker run -p 8080:80 nginx",
+            "docker ps",
+            "Docker run --rm -p 5432:5432 postgres",
+            "git status",
+            "docker run --rm -p 3000:3000 app",
+        ]
+        .map(String::from)
+        .to_vec();
+        let texts = |pattern: &HistoryPattern, top| -> Vec<String> {
+            grep_history(&lines, pattern, top).into_iter().map(|s| s.text).collect()
+        };
+
+        let regex = HistoryPattern::regex(r"docker run .* -p \d+:", false).unwrap();
+        assert_eq!(texts(&regex, 10), vec!["docker run --rm -p 3000:3000 app"]);
+        let regex = HistoryPattern::regex(r"docker run .*-p \d+:", true).unwrap();
+        assert_eq!(
+            texts(&regex, 2),
+            vec!["docker run --rm -p 3000:3000 app", "Docker run --rm -p 5432:5432 postgres"]
+        );
+
+        let glob = HistoryPattern::glob("docker *", false).unwrap();
+        assert_eq!(
+            texts(&glob, 10),
+            vec!["docker run --rm -p 3000:3000 app", "docker ps", "docker run -p 8080:80 nginx"]
+        );
+        assert!(texts(&HistoryPattern::glob("status", false).unwrap(), 10).is_empty());
+        assert_eq!(grep_history(&lines, &glob, 1)[0].source.as_deref(), Some("glob"));
+
+        assert!(HistoryPattern::regex("(", false).is_err());
+        assert!(HistoryPattern::glob("[", false).is_err());
+    }
+
+    #[test]
+    fn no_db_search_only_uses_the_fuzzy_model() {
+        let lines: Arc<[String]> = ["git status"].map(String::from).into();
+        let pool = search_pool(true);
+        assert!(pool.is_none());
+        let models = ModelOptions {
+            enable_embedding: true,
+            ..ModelOptions::default()
+        };
+        let ensemble = search_models(lines, 5, pool.as_ref(), models).build();
+        assert_eq!(ensemble.model_states(), vec![("history".to_string(), true)]);
+    }
+
+    #[test]
+    fn completion_is_the_top_suggestion_or_nothing() {
+        let lines: Arc<[String]> = ["git status", "cargo build"].map(String::from).into();
+        let ensemble = light_search_models(lines, 1, None, false).build();
+
+        assert_eq!(best_completion(&ensemble, "git").unwrap().as_deref(), Some("git status"));
+        assert_eq!(best_completion(&ensemble, "zzzq").unwrap(), None);
+        assert_eq!(best_completion(&ensemble, "  ").unwrap(), None);
+    }
+
+    #[test]
+    fn output_search_finds_every_occurrence_with_smart_case() {
+        let lines: Vec<String> = ["error: one", "ok", "Error again, error"].map(String::from).to_vec();
+        let at = |matches: Vec<OutputMatch>| -> Vec<(usize, usize)> {
+            matches.into_iter().map(|m| (m.line, m.range.start)).collect()
+        };
+
+        assert_eq!(at(find_output_matches(&lines, "error")), vec![(0, 0), (2, 0), (2, 13)]);
+        assert_eq!(at(find_output_matches(&lines, "Error")), vec![(2, 0)]);
+        assert!(find_output_matches(&lines, "").is_empty());
+
+        let mut app = test_app();
+        app.history = vec![HistoryEntry {
+            cmd: "make".to_string(),
+            output_lines: lines,
+            exit_code: Some(2),
+        }];
+        app.history_search = "error".to_string();
+        app.jump_to_history_match(1);
+        assert_eq!((app.history_search_match, app.history_scroll), (1, 2));
+        app.jump_to_history_match(1);
+        app.jump_to_history_match(1);
+        assert_eq!((app.history_search_match, app.history_scroll), (0, 0), "wraps around");
+        app.jump_to_history_match(-1);
+        assert_eq!(app.history_search_match, 2);
+    }
+
+    #[derive(Debug)]
+    struct InternalRecommender;
+
+    impl SuggestModel for InternalRecommender {
+        fn predict(&self, input: &str) -> Result<Vec<Suggestion>> {
+            Ok(vec![Suggestion::with_source(format!("{input} --internal"), 1.0, "internal")])
+        }
+
+        fn name(&self) -> &str {
+            "internal"
+        }
+    }
+
+    #[test]
+    fn registered_models_join_the_ensemble() {
+        let app = App::new_with_models(
+            ModelRegistry::new().with_light_model(InternalRecommender, Some(2.0)),
+            vec!["deploy staging".to_string()],
+            None,
+            &AppOptions { top: 10, ..AppOptions::default() },
+        )
+        .unwrap();
+
+        let texts: Vec<String> = app
+            .ensemble
+            .predict_light_models("deploy")
+            .unwrap()
+            .into_iter()
+            .map(|s| s.text)
+            .collect();
+        assert_eq!(texts, vec!["deploy --internal", "deploy staging"]);
+    }
+
+    #[test]
+    fn heavy_merge_keeps_selected_suggestion() {
+        let mut app = test_app();
+        app.suggestions = vec![
+            Suggestion::with_source("git status", 3.0, "history"),
+            Suggestion::with_source("git stash", 2.0, "history"),
+        ];
+        app.selected = 1;
+
+        app.merge_heavy_model_suggestions(vec![Suggestion::with_source(
+            "git stage", 10.0, "llm",
+        )]);
+
+        assert_eq!(app.suggestions[0].text, "git stage");
+        assert_eq!(app.suggestions[app.selected].text, "git stash");
+    }
+
+    #[derive(Debug)]
+    struct FixedHeavyModel;
+
+    impl SuggestModel for FixedHeavyModel {
+        fn predict(&self, _input: &str) -> Result<Vec<Suggestion>> {
+            Ok(vec![Suggestion::with_source("git stage", 1.0, "llm")])
+        }
+    }
+
+    #[test]
+    fn loading_flag_clears_once_heavy_models_finish() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let _enter = runtime.enter();
+        let mut app = test_app();
+        app.ensemble = EnsembleBuilder::new().with_heavy_model(FixedHeavyModel).build();
+
+        app.input = "git".to_string();
+        app.spawn_heavy_model_tasks("git");
+        assert!(app.is_loading_heavy);
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while app.is_loading_heavy && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+            app.poll_heavy_model_results();
+        }
+        assert!(!app.is_loading_heavy);
+        assert_eq!(app.suggestions[0].text, "git stage");
+    }
+
+    /// Emits one batch only after sleeping for `delay`
+    #[derive(Debug)]
+    struct SlowHeavyModel {
+        delay: Duration,
+    }
+
+    impl SuggestModel for SlowHeavyModel {
+        fn predict(&self, _input: &str) -> Result<Vec<Suggestion>> {
+            std::thread::sleep(self.delay);
+            Ok(vec![Suggestion::with_source("git stage", 1.0, "llm")])
+        }
+    }
+
+    #[test]
+    fn heavy_batches_after_the_timeout_are_dropped() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let _enter = runtime.enter();
+        let mut app = test_app();
+        app.heavy_model_timeout = Duration::from_millis(50);
+        app.ensemble = EnsembleBuilder::new()
+            .with_heavy_model(SlowHeavyModel { delay: Duration::from_millis(300) })
+            .build();
+
+        app.input = "git".to_string();
+        app.spawn_heavy_model_tasks("git");
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while app.is_loading_heavy && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+            app.poll_heavy_model_results();
+        }
+        assert!(!app.is_loading_heavy, "the timeout ends loading");
+
+        // Let the blocking thread wake up and emit its late batch
+        std::thread::sleep(Duration::from_millis(500));
+        app.poll_heavy_model_results();
+        assert!(app.suggestions.is_empty());
+    }
+
+    #[test]
+    fn heavy_results_for_an_outdated_input_are_dropped() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let _enter = runtime.enter();
+        let mut app = test_app();
+        app.ensemble = EnsembleBuilder::new().with_heavy_model(FixedHeavyModel).build();
+
+        app.input = "git".to_string();
+        app.spawn_heavy_model_tasks("git");
+        app.input = "git push".to_string();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while app.is_loading_heavy && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+            app.poll_heavy_model_results();
+        }
+        app.poll_heavy_model_results();
+        assert!(app.suggestions.is_empty());
+    }
+
+    #[test]
+    fn heavy_merge_sums_onto_ensemble_scores() {
+        let mut app = test_app();
+        app.suggestions = vec![
+            Suggestion::with_source("git status", 3.0, "history"),
+            Suggestion::with_source("git stash", 2.0, "history"),
+            Suggestion::with_source("git show", 1.0, "history"),
+            Suggestion::with_source("git shortlog", 1.0, "history"),
+        ];
+
+        app.merge_heavy_model_suggestions(vec![
+            Suggestion::with_source("git stash", 1.5, "llm"),
+            Suggestion::with_source("git switch", 1.0, "llm"),
+        ]);
+
+        let texts: Vec<&str> = app.suggestions.iter().map(|s| s.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            vec!["git stash", "git status", "git show", "git shortlog", "git switch"]
+        );
+        assert!((app.suggestions[0].score - 3.5).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn recent_history_carries_exit_codes() {
+        let pool = SqlitePool::open_memory_migrated().unwrap();
+        let secrets = SecretFilter::default();
+        persist_command_to_history(&pool, "make test", "s", "ok\n", Some(0), &secrets).unwrap();
+        persist_command_to_history(&pool, "make deploy", "s", "denied\n", Some(2), &secrets).unwrap();
+        persist_command_to_history(&pool, "ls", "s", "", None, &secrets).unwrap();
+
+        let mut codes: Vec<(String, Option<i32>)> = load_recent_history(&pool, 10)
+            .unwrap()
+            .into_iter()
+            .map(|entry| (entry.cmd, entry.exit_code))
+            .collect();
+        codes.sort();
+        assert_eq!(
+            codes,
+            vec![
+                ("ls".to_string(), None),
+                ("make deploy".to_string(), Some(2)),
+                ("make test".to_string(), Some(0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn accepted_suggestions_go_through_the_secret_filter() {
+        let pool = SqlitePool::open_memory_migrated().unwrap();
+        let secrets = SecretFilter::new(&[], true).unwrap();
+        let options = AppOptions { secrets, ..AppOptions::default() };
+        let mut app = App::new(Vec::new(), Some(pool.clone()), &options).unwrap();
+
+        app.input = "export API_TOKEN=abc".to_string();
+        app.record_acceptance("export API_TOKEN=abc123 && deploy");
+        app.input = " ssh".to_string();
+        app.record_acceptance("ssh prod");
+
+        let rows = pool
+            .query_collect("SELECT prefix, command FROM acceptances", vec![], |row| {
+                Ok((row.get::<String>(0)?, row.get::<String>(1)?))
+            })
+            .unwrap();
+        assert_eq!(
+            rows,
+            vec![("export API_TOKEN=****".to_string(), "export API_TOKEN=**** && deploy".to_string())]
+        );
+    }
+
+    #[test]
+    fn prune_removes_old_executions_and_rare_history() {
+        let pool = SqlitePool::open_memory_migrated().unwrap();
+        for (cmd, age) in [("old", "-100 days"), ("recent", "-1 days")] {
+            pool.execute(
+                "INSERT INTO command_executions (command, executed_at) VALUES (?1, datetime('now', ?2));",
+                vec![Value::Text(cmd.to_string()), Value::Text(age.to_string())],
+            )
+            .unwrap();
+        }
+        for (cmd, count) in [("git status", 10), ("ls", 5), ("rare", 1)] {
+            pool.execute(
+                "INSERT INTO history (command, hash, count) VALUES (?1, ?2, ?3);",
+                vec![
+                    Value::Text(cmd.to_string()),
+                    Value::Text(hash_command(cmd)),
+                    Value::Integer(count),
+                ],
+            )
+            .unwrap();
+        }
+
+        let report = prune_history_db(&pool, 30, Some(2)).unwrap();
+        assert_eq!(
+            report,
+            PruneReport {
+                executions_removed: 1,
+                history_removed: 1,
+            }
+        );
+
+        let remaining = pool
+            .query_collect(
+                "SELECT command FROM history ORDER BY count DESC",
+                std::iter::empty::<Value>(),
+                |row| Ok(row.get::<String>(0)?),
+            )
+            .unwrap();
+        assert_eq!(remaining, vec!["git status", "ls"]);
+    }
+
+    #[test]
+    fn deleting_a_command_clears_both_tables() {
+        let pool = SqlitePool::open_memory_migrated().unwrap();
+        let secrets = SecretFilter::default();
+        for cmd in ["make deploy", "ls", "make deploy"] {
+            persist_command_to_history(&pool, cmd, "test", "", Some(0), &secrets).unwrap();
+        }
+
+        delete_command_from_history(&pool, "make deploy").unwrap();
+
+        for table in ["history", "command_executions"] {
+            let commands = pool
+                .query_collect(
+                    &format!("SELECT command FROM {table}"),
+                    std::iter::empty::<Value>(),
+                    |row| Ok(row.get::<String>(0)?),
+                )
+                .unwrap();
+            assert_eq!(commands, vec!["ls"], "{table}");
+        }
+    }
+
+    #[test]
+    fn fuzzy_scores_are_bounded_and_keep_order() {
+        let mut suggestions = vec![
+            Suggestion::with_source("a", 240.0, "history"),
+            Suggestion::with_source("b", 60.0, "history"),
+        ];
+        scale_fuzzy_scores(&mut suggestions);
+        assert_eq!(suggestions[0].score, 1.0);
+        assert_eq!(suggestions[1].score, 0.25);
+
+        let mut mixed = vec![
+            Suggestion::with_source("a", 10.0, "history"),
+            Suggestion::with_source("b", 0.0, "history"),
+            Suggestion::with_source("c", -5.0, "history"),
+        ];
+        scale_fuzzy_scores(&mut mixed);
+        let scores: Vec<f64> = mixed.iter().map(|s| s.score).collect();
+        assert_eq!(scores[0], 1.0);
+        assert!(scores[0] > scores[1] && scores[1] > scores[2] && scores[2] > 0.0);
+    }
+
+    #[test]
+    fn fuzzy_limit_keeps_the_best_matches_in_order() {
+        let corpus: Vec<String> = ["git stash", "gst", "git status", "ls", "git st", "g s t"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let all = FuzzyHistoryModel::new(corpus.clone()).predict("gst").unwrap();
+        let limited = FuzzyHistoryModel::new(corpus)
+            .with_limit(2)
+            .predict("gst")
+            .unwrap();
+
+        let texts = |s: &[Suggestion]| s.iter().map(|s| s.text.clone()).collect::<Vec<_>>();
+        assert!(all.len() > 2);
+        assert_eq!(texts(&limited), texts(&all[..2]));
+        assert!(!limited[0].match_indices.is_empty());
+    }
+
+    #[test]
+    fn fuzzy_history_reports_matched_char_indices() {
+        let model = FuzzyHistoryModel::new(vec!["git status".to_string()]);
+        let suggestions = model.predict("gst").unwrap();
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].match_indices, vec![0, 4, 5]);
+    }
+
+    #[test]
+    fn strips_zsh_extended_prefix() {
+        let text = ": 1700000000:0;git status\n: 1700000001:12;cargo build\n";
+        assert_eq!(parse_history_lines(text), vec!["git status", "cargo build"]);
+    }
+
+    #[test]
+    fn zsh_extended_entries_keep_their_leading_space() {
+        let text = ": 1700000000:0; export VAULT_ADDR=x\n: 1700000001:0;ls\n";
+        let entries = parse_history_lines(text);
+        assert_eq!(entries, vec![" export VAULT_ADDR=x", "ls"]);
+        let secrets = SecretFilter::default();
+        let kept: Vec<_> = entries.iter().filter_map(|e| secrets.apply(e)).collect();
+        assert_eq!(kept, vec!["ls"]);
+    }
+
+    #[test]
+    fn joins_zsh_multiline_entries() {
+        let text = ": 1700000000:0;for i in 1 2; do\\\necho $i\\\ndone\n: 1700000001:0;ls\n";
+        assert_eq!(
+            parse_history_lines(text),
+            vec!["for i in 1 2; do\necho $i\ndone", "ls"]
+        );
+    }
+
+    #[test]
+    fn keeps_plain_and_mixed_lines() {
+        let text = "git status\n: 1700000000:0;ls -al\n: not-a-timestamp;echo\nmake \\\n";
+        assert_eq!(
+            parse_history_lines(text),
+            vec!["git status", "ls -al", ": not-a-timestamp;echo", "make \\"]
+        );
+    }
+
+    #[test]
+    fn parses_fish_history_entries() {
+        let text = "- cmd: git status\n  when: 1700000000\n- cmd: echo a\\nb\\\\\n  when: 1700000001\n  paths:\n    - b\n";
+        assert!(is_fish_history(Path::new("history.txt"), text));
+        assert_eq!(parse_fish_history(text), vec!["git status", "echo a\nb\\"]);
+    }
+
+    #[test]
+    fn detects_fish_history_by_file_name() {
+        assert!(is_fish_history(Path::new("/home/u/.local/share/fish/fish_history"), ""));
+        assert!(!is_fish_history(Path::new(".zsh_history"), "git status\n"));
+    }
+
+    #[test]
+    fn imports_atuin_history_incrementally() {
+        let atuin = SqlitePool::open_memory().unwrap();
+        atuin
+            .execute(
+                "CREATE TABLE history (id TEXT PRIMARY KEY, timestamp INTEGER NOT NULL, duration INTEGER NOT NULL, exit INTEGER NOT NULL, command TEXT NOT NULL, cwd TEXT NOT NULL, session TEXT NOT NULL, hostname TEXT NOT NULL, deleted_at INTEGER)",
+                vec![],
+            )
+            .unwrap();
+        let rows = [
+            ("a", 1_700_000_000_000_000_000i64, 0i64, "git status", None),
+            ("b", 1_700_000_001_000_000_000, 1, "cargo test", None),
+            ("c", 1_700_000_002_000_000_000, 0, "git status", None),
+            ("d", 1_700_000_003_000_000_000, 0, "rm -rf secrets", Some(1i64)),
+            ("e", 1_700_000_004_000_000_000, 0, "export API_TOKEN=abc123", None),
+        ];
+        for (id, ts, exit, command, deleted_at) in rows {
+            atuin
+                .execute(
+                    "INSERT INTO history VALUES (?1, ?2, 0, ?3, ?4, '/repo', 's', 'h', ?5)",
+                    vec![
+                        Value::Text(id.into()),
+                        Value::Integer(ts),
+                        Value::Integer(exit),
+                        Value::Text(command.into()),
+                        deleted_at.map(Value::Integer).unwrap_or(Value::Null),
+                    ],
+                )
+                .unwrap();
+        }
+        let pool = SqlitePool::open_memory_migrated().unwrap();
+        let secrets = SecretFilter::default();
+
+        assert_eq!(import_atuin_history(&pool, &atuin, "atuin.db", &secrets).unwrap(), 3);
+
+        let history = pool
+            .query_collect(
+                "SELECT command, count, hash FROM history ORDER BY command",
+                vec![],
+                |row| Ok((row.get::<String>(0)?, row.get::<i64>(1)?, row.get::<String>(2)?)),
+            )
+            .unwrap();
+        assert_eq!(
+            history,
+            vec![
+                ("cargo test".to_string(), 1, hash_command("cargo test")),
+                ("git status".to_string(), 2, hash_command("git status")),
+            ]
+        );
+        let exit_codes = pool
+            .query_collect(
+                "SELECT exit_code FROM command_executions WHERE command = 'cargo test'",
+                vec![],
+                |row| Ok(row.get::<i64>(0)?),
+            )
+            .unwrap();
+        assert_eq!(exit_codes, vec![1]);
+
+        assert_eq!(import_atuin_history(&pool, &atuin, "atuin.db", &secrets).unwrap(), 0);
+
+        // Another database does not reset the progress recorded for this one
+        assert_eq!(import_atuin_history(&pool, &atuin, "other.db", &secrets).unwrap(), 3);
+        assert_eq!(import_atuin_history(&pool, &atuin, "atuin.db", &secrets).unwrap(), 0);
+        assert_eq!(import_atuin_history(&pool, &atuin, "other.db", &secrets).unwrap(), 0);
+        let counts = pool
+            .query_collect("SELECT SUM(count) FROM history", vec![], |row| Ok(row.get::<i64>(0)?))
+            .unwrap();
+        assert_eq!(counts, vec![6]);
+    }
+
+    #[test]
+    fn dedup_lines_can_ignore_case_and_whitespace() {
+        let lines = || vec!["ls -la".to_string(), "LS -la".to_string(), "ls  -la ".to_string()];
+
+        let mut exact = lines();
+        dedup_lines(&mut exact, false);
+        assert_eq!(exact.len(), 3);
+
+        let mut folded = lines();
+        dedup_lines(&mut folded, true);
+        assert_eq!(folded, vec!["ls -la"]);
+    }
+
+    #[test]
+    fn max_corpus_keeps_the_newest_unique_lines() {
+        let path = std::env::temp_dir().join(format!("ghosttype-corpus-{}", std::process::id()));
+        std::fs::write(&path, "ls\ngit pull\nls\nmake\ncargo test\n").unwrap();
+
+        let lines = load_history_lines(
+            vec![path.clone()],
+            true,
+            false,
+            Some(2),
+            HistoryEncoding::default(),
+            &SecretFilter::default(),
+        )
+        .unwrap();
+        assert_eq!(lines, vec!["make", "cargo test"]);
+        let recent = load_history_lines(
+            vec![path.clone()],
+            true,
+            false,
+            Some(3),
+            HistoryEncoding::default(),
+            &SecretFilter::default(),
+        )
+        .unwrap();
+        assert_eq!(recent, vec!["ls", "make", "cargo test"], "re-run ls is recent");
+        let all = load_history_lines(
+            vec![path.clone()],
+            true,
+            false,
+            None,
+            HistoryEncoding::default(),
+            &SecretFilter::default(),
+        )
+        .unwrap();
+        assert_eq!(all.len(), 4);
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn corpus_lines_go_through_the_secret_filter() {
+        let path = std::env::temp_dir().join(format!("ghosttype-secret-corpus-{}", std::process::id()));
+        std::fs::write(&path, "ls\nexport API_TOKEN=abc123\n vault login\n").unwrap();
+        let load = |secrets: &SecretFilter| {
+            load_history_lines(vec![path.clone()], true, false, None, HistoryEncoding::default(), secrets)
+                .unwrap()
+        };
+
+        assert_eq!(load(&SecretFilter::default()), vec!["ls"]);
+        let redact = SecretFilter::new(&[], true).unwrap().with_ignore_space(false);
+        assert_eq!(load(&redact), vec!["ls", "export API_TOKEN=****", " vault login"]);
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn history_files_are_decoded_by_bom_or_configured_encoding() {
+        let utf8 = HistoryEncoding::default();
+        assert_eq!(utf8.decode(b"\xEF\xBB\xBFls\n"), "ls\n");
+        assert_eq!(utf8.decode(b"caf\xE9\n"), "caf\u{FFFD}\n");
+
+        let latin1: HistoryEncoding = "latin1".parse().unwrap();
+        assert_eq!(latin1.decode(b"caf\xE9\n"), "caf\u{E9}\n");
+
+        let utf16: Vec<u8> = [0xFF, 0xFE]
+            .into_iter()
+            .chain("cd ~\n".encode_utf16().flat_map(u16::to_le_bytes))
+            .collect();
+        assert_eq!(utf8.decode(&utf16), "cd ~\n", "BOM wins over the setting");
+
+        assert!("klingon".parse::<HistoryEncoding>().is_err());
+    }
+
+    #[test]
+    fn utf16_history_import_resumes_after_whole_lines() {
+        use std::io::Write;
+
+        let dir = std::env::temp_dir().join(format!("ghosttype-utf16-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(".bash_history");
+        let utf16 =
+            |text: &str| -> Vec<u8> { text.encode_utf16().flat_map(u16::to_le_bytes).collect() };
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend(utf16("git status\nls\n"));
+        std::fs::write(&path, bytes).unwrap();
+        let pool = SqlitePool::open_memory_migrated().unwrap();
+        let secrets = SecretFilter::default();
+        let encoding = HistoryEncoding::default();
+
+        assert_eq!(
+            import_history_file(&pool, &path, &secrets, encoding).unwrap(),
+            2
+        );
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(&utf16("make\ncargo b")).unwrap();
+        drop(file);
+        assert_eq!(
+            import_history_file(&pool, &path, &secrets, encoding).unwrap(),
+            1
+        );
+        let stored = pool
+            .query_collect(
+                "SELECT command FROM history ORDER BY command",
+                std::iter::empty::<Value>(),
+                |row| Ok(row.get::<String>(0)?),
+            )
+            .unwrap();
+        assert_eq!(stored, vec!["git status", "ls", "make"]);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn shell_history_import_only_reads_appended_lines() {
+        use std::io::Write;
+
+        let dir = std::env::temp_dir().join(format!("ghosttype-import-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(".bash_history");
+        std::fs::write(&path, "git status\nls\n").unwrap();
+        let pool = SqlitePool::open_memory_migrated().unwrap();
+        let count_of = |cmd: &str| -> i64 {
+            pool.query_collect(
+                "SELECT count FROM history WHERE hash = ?1",
+                vec![Value::Text(hash_command(cmd))],
+                |row| Ok(row.get::<i64>(0)?),
+            )
+            .unwrap()
+            .into_iter()
+            .next()
+            .unwrap_or(0)
+        };
+
+        let secrets = SecretFilter::default();
+        assert_eq!(
+            import_history_file(&pool, &path, &secrets, HistoryEncoding::default()).unwrap(),
+            2
+        );
+        assert_eq!(
+            import_history_file(&pool, &path, &secrets, HistoryEncoding::default()).unwrap(),
+            0,
+            "unchanged file is skipped"
+        );
+
+        let mut file = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"git status\ncargo b").unwrap();
+        drop(file);
+        assert_eq!(
+            import_history_file(&pool, &path, &secrets, HistoryEncoding::default()).unwrap(),
+            1
+        );
+        assert_eq!(count_of("git status"), 2);
+        assert_eq!(count_of("ls"), 1);
+        assert_eq!(count_of("cargo b"), 0, "partial line waits for its newline");
+
+        // Coarse mtimes can miss an append; the size still changes
+        let mtime = std::fs::metadata(&path).unwrap().modified().unwrap();
+        let mut file = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"uild\n").unwrap();
+        file.set_modified(mtime).unwrap();
+        drop(file);
+        assert_eq!(
+            import_history_file(&pool, &path, &secrets, HistoryEncoding::default()).unwrap(),
+            1,
+            "same mtime, different size"
+        );
+        assert_eq!(count_of("cargo build"), 1);
+
+        std::fs::write(&path, "make\n").unwrap();
+        assert_eq!(
+            import_history_file(&pool, &path, &secrets, HistoryEncoding::default()).unwrap(),
+            1,
+            "rewritten file is read again"
+        );
+        assert_eq!(count_of("make"), 1);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn shell_history_import_does_not_make_commands_recent() {
+        let dir = std::env::temp_dir().join(format!("ghosttype-frecency-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(".bash_history");
+        std::fs::write(&path, "git status\ngit stash\n").unwrap();
+        let pool = SqlitePool::open_memory_migrated().unwrap();
+        pool.execute(
+            "INSERT INTO history (command, hash, count, last_used) VALUES ('git status', ?1, 3, datetime('now', '-90 days'));",
+            vec![Value::Text(hash_command("git status"))],
+        )
+        .unwrap();
+        let secrets = SecretFilter::default();
+        import_history_file(&pool, &path, &secrets, HistoryEncoding::default()).unwrap();
+
+        let scores = || -> Vec<(String, f64)> {
+            let mut scores: Vec<(String, f64)> = FrecencyModel::new(pool.clone())
+                .predict("git st")
+                .unwrap()
+                .into_iter()
+                .map(|s| (s.text, s.score))
+                .collect();
+            scores.sort_by(|a, b| a.0.cmp(&b.0));
+            scores
+        };
+        // Imported lines have no run time, so both stay stale
+        assert_eq!(
+            scores(),
+            vec![("git stash".to_string(), 0.25), ("git status".to_string(), 1.0)]
+        );
+
+        persist_command_to_history(&pool, "git stash", "s", "", Some(0), &secrets).unwrap();
+        assert_eq!(
+            scores(),
+            vec![("git stash".to_string(), 8.0), ("git status".to_string(), 1.0)]
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn commands_carrying_secrets_are_not_stored() {
+        let dir = std::env::temp_dir().join(format!("ghosttype-secrets-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(".bash_history");
+        let aws = "export AWS_SECRET_ACCESS_KEY=wJalrXUtnFEMI/K7MDENG/bPxRfiCYEXAMPLEKEY";
+        std::fs::write(&path, format!("{aws}\naws s3 ls\n")).unwrap();
+        let pool = SqlitePool::open_memory_migrated().unwrap();
+        let secrets = SecretFilter::default();
+
+        assert_eq!(
+            import_history_file(&pool, &path, &secrets, HistoryEncoding::default()).unwrap(),
+            1
+        );
+        persist_command_to_history(&pool, aws, "s", "", Some(0), &secrets).unwrap();
+        let stored = |table: &str| {
+            pool.query_collect(
+                &format!("SELECT command FROM {table}"),
+                std::iter::empty::<Value>(),
+                |row| Ok(row.get::<String>(0)?),
+            )
+            .unwrap()
+        };
+        assert_eq!(stored("history"), vec!["aws s3 ls"]);
+        assert!(stored("command_executions").is_empty());
+
+        let redact = SecretFilter::new(&[], true).unwrap();
+        persist_command_to_history(&pool, aws, "s", "", Some(0), &redact).unwrap();
+        assert_eq!(stored("command_executions"), vec!["export AWS_SECRET_ACCESS_KEY=****"]);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn query_can_come_from_stdin() {
+        let stdin = || std::io::Cursor::new("git st\n");
+        assert_eq!(resolve_query(Some("ls"), stdin(), false).unwrap(), "ls");
+        assert_eq!(resolve_query(Some("-"), stdin(), true).unwrap(), "git st");
+        assert_eq!(resolve_query(None, stdin(), false).unwrap(), "git st");
+        assert!(resolve_query(None, stdin(), true).is_err());
+    }
+
+    #[test]
+    fn expands_history_globs_and_directories() {
+        let dir = std::env::temp_dir().join(format!("ghosttype-glob-{}", std::process::id()));
+        let sessions = dir.join("sessions");
+        std::fs::create_dir_all(&sessions).unwrap();
+        for name in ["b.history", "a.history", "notes.txt"] {
+            std::fs::write(sessions.join(name), "ls\n").unwrap();
+        }
+
+        let glob = sessions.join("*.history");
+        let missing = dir.join("missing_history");
+        let expanded = expand_history_paths(vec![glob, sessions.clone(), missing.clone()]);
+        assert_eq!(
+            expanded,
+            vec![
+                sessions.join("a.history"),
+                sessions.join("b.history"),
+                sessions.join("a.history"),
+                sessions.join("b.history"),
+                sessions.join("notes.txt"),
+                missing,
+            ]
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn restores_recent_tui_input_only() {
+        let pool = SqlitePool::open_memory_migrated().unwrap();
+        assert_eq!(load_tui_input(&pool, TUI_INPUT_MAX_AGE).unwrap(), None);
+
+        save_tui_input(&pool, "git rebase -i").unwrap();
+        assert_eq!(
+            load_tui_input(&pool, TUI_INPUT_MAX_AGE).unwrap().as_deref(),
+            Some("git rebase -i")
+        );
+
+        pool.execute(
+            "UPDATE meta SET mtime = mtime - 7200 WHERE key = ?1",
+            vec![Value::Text(TUI_INPUT_META_KEY.to_string())],
+        )
+        .unwrap();
+        assert_eq!(load_tui_input(&pool, TUI_INPUT_MAX_AGE).unwrap(), None);
+
+        save_tui_input(&pool, "  ").unwrap();
+        assert_eq!(load_tui_input(&pool, TUI_INPUT_MAX_AGE).unwrap(), None);
+    }
+
+    #[test]
+    fn saved_view_reopens_on_the_next_launch() {
+        let pool = SqlitePool::open_memory_migrated().unwrap();
+        let mut app = test_app();
+        app.db = Some(pool.clone());
+        app.current_tab = Tab::History;
+        app.history_filter = "docker".to_string();
+        app.save_view();
+
+        let mut relaunched = test_app();
+        relaunched.db = Some(pool.clone());
+        relaunched.restore_view();
+        assert_eq!(relaunched.current_tab, Tab::History);
+        assert_eq!(relaunched.history_filter, "docker");
+
+        pool.execute(
+            "UPDATE meta SET value = '{\"tab\":\"gone\"}' WHERE key = ?1",
+            vec![Value::Text(TUI_VIEW_META_KEY.to_string())],
+        )
+        .unwrap();
+        assert_eq!(load_tui_view(&pool).unwrap(), None);
+    }
 }
@@ -0,0 +1,137 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+
+use crate::model::embedding::EmbeddingClient;
+use crate::model::{
+    check_llama_cli, check_llama_embedding, check_vector_support, LlamaEmbeddingClient, SqlitePool,
+};
+
+/// Outcome of one `ghosttype doctor` check
+#[derive(Debug, PartialEq)]
+enum Status {
+    Pass(String),
+    Fail(String),
+    Skip(String),
+}
+
+impl Status {
+    fn from_result(result: Result<String>) -> Self {
+        match result {
+            Ok(detail) => Self::Pass(detail),
+            Err(err) => Self::Fail(format!("{err:#}")),
+        }
+    }
+}
+
+/// Check each dependency of the suggestion models in turn and print why any
+/// is unavailable; these are the failures the TUI only logs as warnings
+pub fn run_doctor(embedding_model: Option<PathBuf>) -> Result<()> {
+    let mut checks: Vec<(&str, Status)> = Vec::new();
+
+    let pool = SqlitePool::open_default();
+    checks.push((
+        "history database",
+        match &pool {
+            Ok(_) => Status::Pass("opened".to_string()),
+            Err(err) => Status::Fail(format!("{err:#}")),
+        },
+    ));
+    checks.push((
+        "libsql vector functions",
+        match &pool {
+            Ok(pool) => {
+                Status::from_result(check_vector_support(pool).map(|()| "available".to_string()))
+            }
+            Err(_) => Status::Skip("database unavailable".to_string()),
+        },
+    ));
+
+    checks.push((
+        "llama-cli",
+        Status::from_result(check_llama_cli().map(|()| "found".to_string())),
+    ));
+    let binary = LlamaEmbeddingClient::binary_from_env();
+    checks.push((
+        "llama-embedding",
+        Status::from_result(
+            check_llama_embedding(&binary).map(|()| binary.display().to_string()),
+        ),
+    ));
+
+    let client = LlamaEmbeddingClient::from_env_or(embedding_model);
+    checks.push((
+        "embedding model path",
+        match &client {
+            Ok(client) if client.model_path().is_file() => {
+                Status::Pass(client.model_path().display().to_string())
+            }
+            Ok(client) => Status::Fail(format!("{} does not exist", client.model_path().display())),
+            Err(err) => Status::Fail(format!("{err:#}")),
+        },
+    ));
+    checks.push((
+        "embedding health check",
+        match &client {
+            Ok(client) => Status::from_result(
+                client
+                    .health_check()
+                    .map(|()| "embedded a test prompt".to_string()),
+            ),
+            Err(_) => Status::Skip("no embedding model configured".to_string()),
+        },
+    ));
+    checks.push((
+        "candle model download",
+        Status::Skip("this build has no candle backend".to_string()),
+    ));
+
+    print!("{}", format_report(&checks));
+    let failed = checks
+        .iter()
+        .filter(|(_, status)| matches!(status, Status::Fail(_)))
+        .count();
+    if failed > 0 {
+        bail!("{failed} of {} checks failed", checks.len());
+    }
+    Ok(())
+}
+
+fn format_report(checks: &[(&str, Status)]) -> String {
+    let width = checks.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    checks
+        .iter()
+        .map(|(name, status)| {
+            let (tag, detail) = match status {
+                Status::Pass(detail) => ("ok", detail),
+                Status::Fail(detail) => ("FAIL", detail),
+                Status::Skip(detail) => ("skip", detail),
+            };
+            format!("[{tag:>4}] {name:<width$}  {detail}\n")
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_aligns_names_and_shows_errors() {
+        let checks = [
+            ("llama-cli", Status::Fail("running llama-cli --help: not found".to_string())),
+            ("history database", Status::Pass("opened".to_string())),
+            ("candle", Status::Skip("not built".to_string())),
+        ];
+        assert_eq!(
+            format_report(&checks),
+            "[FAIL] llama-cli         running llama-cli --help: not found\n\
+             [  ok] history database  opened\n\
+             [skip] candle            not built\n"
+        );
+        assert_eq!(
+            Status::from_result(Err(anyhow::anyhow!("inner").context("outer"))),
+            Status::Fail("outer: inner".to_string())
+        );
+    }
+}
@@ -0,0 +1,104 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+/// Shells `ghosttype init` can emit integration for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Shell {
+    Zsh,
+    Bash,
+    Fish,
+}
+
+/// Print the integration script for `shell`, calling back into this binary
+pub fn run_init(shell: Shell) -> Result<()> {
+    let exe = std::env::current_exe().context("resolving path of the ghosttype binary")?;
+    print!("{}", init_script(shell, &exe));
+    Ok(())
+}
+
+/// A widget bound to Alt+G that replaces the command line with the top
+/// `ghosttype search` suggestion for it
+fn init_script(shell: Shell, exe: &Path) -> String {
+    let exe = exe.to_string_lossy();
+    match shell {
+        Shell::Zsh => format!(
+            r#"# ghosttype zsh integration: eval "$(ghosttype init zsh)"
+_ghosttype_complete() {{
+  local result
+  result=$({exe} search --file "${{HISTFILE:-$HOME/.zsh_history}}" --query "$BUFFER" --top 1 2>/dev/null)
+  if [[ -n "$result" ]]; then
+    BUFFER="$result"
+    CURSOR=${{#BUFFER}}
+  fi
+  zle reset-prompt
+}}
+zle -N _ghosttype_complete
+bindkey '^[g' _ghosttype_complete
+"#,
+            exe = posix_quote(&exe)
+        ),
+        Shell::Bash => format!(
+            r#"# ghosttype bash integration: eval "$(ghosttype init bash)"
+_ghosttype_complete() {{
+  local result
+  result=$({exe} search --file "${{HISTFILE:-$HOME/.bash_history}}" --query "$READLINE_LINE" --top 1 2>/dev/null)
+  if [[ -n "$result" ]]; then
+    READLINE_LINE="$result"
+    READLINE_POINT=${{#READLINE_LINE}}
+  fi
+}}
+bind -x '"\eg": _ghosttype_complete'
+"#,
+            exe = posix_quote(&exe)
+        ),
+        Shell::Fish => format!(
+            r#"# ghosttype fish integration: ghosttype init fish | source
+function _ghosttype_complete
+    set -l result ({exe} search --file ~/.local/share/fish/fish_history --query (commandline) --top 1 2>/dev/null)
+    if test -n "$result[1]"
+        commandline --replace -- $result[1]
+    end
+    commandline --function repaint
+end
+bind \eg _ghosttype_complete
+"#,
+            exe = fish_quote(&exe)
+        ),
+    }
+}
+
+/// Single-quote for sh-family shells, closing and reopening around `'`
+fn posix_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+/// Single-quote for fish, which allows `\\` and `\'` escapes inside quotes
+fn fish_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\\', r"\\").replace('\'', r"\'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scripts_call_back_into_the_given_binary() {
+        let exe = Path::new("/opt/ghost type/ghosttype");
+        for shell in [Shell::Zsh, Shell::Bash, Shell::Fish] {
+            let script = init_script(shell, exe);
+            assert!(
+                script.contains("'/opt/ghost type/ghosttype' search --file"),
+                "{shell:?}: {script}"
+            );
+            assert!(script.contains("--top 1"), "{shell:?}");
+        }
+        assert!(init_script(Shell::Zsh, exe).contains("${HISTFILE:-$HOME/.zsh_history}"));
+    }
+
+    #[test]
+    fn quotes_single_quotes_per_shell() {
+        assert_eq!(posix_quote("it's"), r"'it'\''s'");
+        assert_eq!(fish_quote(r"it's\x"), r"'it\'s\\x'");
+    }
+}
@@ -0,0 +1,55 @@
+//! Ghosttype's suggestion engine, usable without the bundled TUI.
+//!
+//! The [`model`] layer holds the individual [`SuggestModel`](model::SuggestModel)s,
+//! the SQLite history store and the [`Ensemble`](model::Ensemble) that combines
+//! them; [`core`] loads shell history and wires the built-in models together.
+//! Most front-ends only need the [`prelude`]:
+//!
+//! ```no_run
+//! use ghosttype::prelude::*;
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let secrets = SecretFilter::default();
+//! let history =
+//!     load_history_lines(Vec::new(), true, false, None, HistoryEncoding::default(), &secrets)?;
+//! let ensemble = EnsembleBuilder::new()
+//!     .with_light_model(FuzzyHistoryModel::new(history))
+//!     .build();
+//! for suggestion in ensemble.predict("git ch")? {
+//!     println!("{}", suggestion.text);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod config;
+pub mod core;
+pub mod model;
+pub mod secrets;
+
+// The bundled front-ends, public only so the `ghosttype` binary can reach them
+#[doc(hidden)]
+pub mod clipboard;
+#[doc(hidden)]
+pub mod doctor;
+#[doc(hidden)]
+pub mod init;
+#[doc(hidden)]
+pub mod server;
+#[doc(hidden)]
+pub mod tui;
+
+/// The types and functions needed to build and query an ensemble
+pub mod prelude {
+    pub use crate::config::ModelWeights;
+    pub use crate::core::{
+        build_ensemble, load_history_lines, read_history_file, FuzzyHistoryModel, HistoryEncoding,
+        ModelOptions,
+    };
+    pub use crate::model::{
+        AcceptanceModel, AliasModel, DedupMode, Ensemble, EnsembleBuilder, FrecencyModel,
+        FreqModel, FtsModel, ModelError, ModelRegistry, NgramModel, PrefixModel, SqlitePool,
+        SuggestModel, Suggestion,
+    };
+    pub use crate::secrets::SecretFilter;
+}
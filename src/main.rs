@@ -1,10 +1,10 @@
-mod core;
-mod model;
-mod tui;
 use anyhow::Result;
 use clap::{Parser, Subcommand};
 use env_logger::Env;
+use ghosttype::model::LlmDevice;
+use ghosttype::{config, core, doctor, init, server, tui};
 use std::path::PathBuf;
+use std::time::Duration;
 
 #[derive(Parser, Debug)]
 #[command(
@@ -20,46 +20,235 @@ struct Cli {
 #[derive(Subcommand, Debug)]
 enum Cmd {
     /// Launch interactive TUI
+    ///
+    /// Unset flags fall back to `<config dir>/ghosttype/config.toml`, then
+    /// to the built-in defaults shown below.
     Tui {
         /// History files to load (semicolon separated)
         #[arg(short = 'f', long = "file", num_args = 0.., value_delimiter = ';')]
         files: Vec<PathBuf>,
 
-        /// Max suggestions to show
-        #[arg(short = 'n', long = "top", default_value_t = 20)]
-        top: usize,
+        /// Max suggestions to show [default: 20]
+        #[arg(short = 'n', long = "top")]
+        top: Option<usize>,
 
-        /// Remove duplicate lines
-        #[arg(long, default_value_t = true)]
-        unique: bool,
+        /// Remove duplicate lines [default: true]
+        #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+        unique: Option<bool>,
 
-        /// Enable embedding-based suggestions
-        #[arg(long, default_value_t = true)]
-        enable_embedding: bool,
+        /// Treat lines differing only in case or whitespace as duplicates [default: false]
+        #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+        ignore_case: Option<bool>,
+
+        /// Keep only the most recent N history lines in memory for fuzzy
+        /// matching; the database-backed models are unaffected [default: unlimited]
+        #[arg(long)]
+        max_corpus: Option<usize>,
+
+        /// Decode history files without a BOM as this, e.g. latin1 or
+        /// utf-16le [default: utf-8]
+        #[arg(long, value_name = "ENCODING")]
+        encoding: Option<core::HistoryEncoding>,
+
+        /// Enable embedding-based suggestions [default: true]
+        #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+        enable_embedding: Option<bool>,
 
         /// Path to GGUF model file for llama-embedding
         #[arg(long)]
         embedding_model: Option<PathBuf>,
 
-        /// Enable LLM-based suggestions
-        #[arg(long, default_value_t = false)]
-        enable_llm: bool,
+        /// Enable LLM-based suggestions [default: false]
+        #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+        enable_llm: Option<bool>,
 
         /// Path to GGUF model file for llama-embedding / llama-cli
         #[arg(long)]
         llm_model: Option<PathBuf>,
+
+        /// Compute device for LLM generation: auto, cpu, metal, cuda or cuda:N [default: auto]
+        #[arg(long)]
+        llm_device: Option<LlmDevice>,
+
+        /// Delay after the last keystroke before refreshing suggestions [default: 300]
+        #[arg(long)]
+        debounce_ms: Option<u64>,
+
+        /// Give up on a heavy model (LLM/embedding) prediction after this long [default: 5000]
+        #[arg(long)]
+        heavy_timeout_ms: Option<u64>,
+
+        /// Vim-style keys: Esc enters normal mode (j/k, g/G, i) [default: false]
+        #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+        vim: Option<bool>,
+
+        /// Store commands matching a secret pattern with the secret masked
+        /// instead of leaving them out of history [default: false]
+        #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+        redact: Option<bool>,
+
+        /// Keep commands typed with a leading space out of history, like the
+        /// shells' HIST_IGNORE_SPACE [default: true]
+        #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+        ignore_space: Option<bool>,
+
+        /// Don't open the history database; only fuzzy-match the history files
+        #[arg(long)]
+        no_db: bool,
+
+        /// Draw in this many rows below the prompt instead of taking over the
+        /// screen, keeping the scrollback intact [rows default: 12]
+        #[arg(long, value_name = "ROWS", num_args = 0..=1, default_missing_value = "12")]
+        inline: Option<u16>,
     },
 
     /// Non-TUI fuzzy search (existing behavior)
     Search {
         #[arg(short = 'f', long = "file", num_args = 1.., value_delimiter = ';')]
         files: Vec<PathBuf>,
+        /// Text to complete; `-` or omitting it with piped stdin reads stdin
         #[arg(short, long)]
-        query: String,
+        query: Option<String>,
+        /// Print history lines matching this regex instead of suggestions
+        #[arg(long, value_name = "PATTERN", conflicts_with_all = ["query", "glob"])]
+        regex: Option<String>,
+        /// Print history lines matching this glob (the whole line) instead of suggestions
+        #[arg(long, value_name = "PATTERN", conflicts_with = "query")]
+        glob: Option<String>,
         #[arg(short = 'n', long = "top", default_value_t = 20)]
         top: usize,
         #[arg(long, default_value_t = true)]
         unique: bool,
+        /// Treat lines differing only in case or whitespace as duplicates, and
+        /// match --regex/--glob case-insensitively
+        #[arg(long)]
+        ignore_case: bool,
+        /// Decode history files without a BOM as this, e.g. latin1 or
+        /// utf-16le [default: utf-8]
+        #[arg(long, value_name = "ENCODING")]
+        encoding: Option<core::HistoryEncoding>,
+        /// Don't open the history database; only fuzzy-match the history files
+        #[arg(long)]
+        no_db: bool,
+        /// Also ask the embedding model (needs the history database)
+        #[arg(long)]
+        enable_embedding: bool,
+        /// Path to GGUF model file for llama-embedding
+        #[arg(long)]
+        embedding_model: Option<PathBuf>,
+        /// Also ask the LLM generator
+        #[arg(long)]
+        enable_llm: bool,
+        /// Path to GGUF model file for llama-cli
+        #[arg(long)]
+        llm_model: Option<PathBuf>,
+        /// Compute device for LLM generation: auto, cpu, metal, cuda or cuda:N [default: auto]
+        #[arg(long)]
+        llm_device: Option<LlmDevice>,
+        /// Output format: plain text lines or a JSON array
+        #[arg(long, value_enum, default_value_t = core::OutputFormat::Plain)]
+        format: core::OutputFormat,
+    },
+
+    /// Print the single best suggestion, exiting 1 when there is none
+    ///
+    /// For shell bindings: READLINE_LINE=$(ghosttype complete --query "$READLINE_LINE")
+    Complete {
+        /// History files to load (semicolon separated) [default: ~/.zsh_history, ~/.bash_history]
+        #[arg(short = 'f', long = "file", num_args = 0.., value_delimiter = ';')]
+        files: Vec<PathBuf>,
+        /// Text to complete; `-` or omitting it with piped stdin reads stdin
+        #[arg(short, long)]
+        query: Option<String>,
+        /// Decode history files without a BOM as this, e.g. latin1 or
+        /// utf-16le [default: utf-8]
+        #[arg(long, value_name = "ENCODING")]
+        encoding: Option<core::HistoryEncoding>,
+    },
+
+    /// Answer suggestion queries over a Unix socket, keeping models loaded
+    ///
+    /// Send one line of input per request; each reply is one line holding a
+    /// JSON array of suggestions. Model settings come from config.toml.
+    Serve {
+        /// History files to load (semicolon separated)
+        #[arg(short = 'f', long = "file", num_args = 0.., value_delimiter = ';')]
+        files: Vec<PathBuf>,
+        /// Socket path [default: $XDG_RUNTIME_DIR/ghosttype.sock or the cache dir]
+        #[arg(long)]
+        socket: Option<PathBuf>,
+        /// Max suggestions per reply [default: 20]
+        #[arg(short = 'n', long = "top")]
+        top: Option<usize>,
+        /// Remove duplicate lines [default: true]
+        #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+        unique: Option<bool>,
+    },
+
+    /// Trim old executions and rarely used history, then VACUUM the database
+    Prune {
+        /// Delete command executions older than this many days
+        #[arg(long, default_value_t = 90)]
+        days: u32,
+        /// Keep only the N most frequently used history entries
+        #[arg(long)]
+        keep_top: Option<usize>,
+    },
+
+    /// Print shell integration that binds Alt+G to the top suggestion
+    ///
+    /// zsh/bash: eval "$(ghosttype init zsh)"; fish: ghosttype init fish | source
+    Init {
+        #[arg(value_enum)]
+        shell: init::Shell,
+    },
+
+    /// Embed history commands for semantic suggestions
+    Embeddings {
+        /// History files to embed (semicolon separated)
+        #[arg(short = 'f', long = "file", num_args = 0.., value_delimiter = ';')]
+        files: Vec<PathBuf>,
+        /// Discard stored embeddings and regenerate them
+        #[arg(long)]
+        rebuild: bool,
+        /// Path to GGUF model file for llama-embedding
+        #[arg(long)]
+        embedding_model: Option<PathBuf>,
+        /// Decode history files without a BOM as this, e.g. latin1 or
+        /// utf-16le [default: utf-8]
+        #[arg(long, value_name = "ENCODING")]
+        encoding: Option<core::HistoryEncoding>,
+    },
+
+    /// Check the database, llama.cpp binaries and models, reporting why any is unavailable
+    Doctor {
+        /// Path to GGUF model file for llama-embedding
+        #[arg(long)]
+        embedding_model: Option<PathBuf>,
+    },
+
+    /// Import history from another tool's database
+    Import {
+        #[arg(long = "from", value_enum)]
+        from: core::ImportSource,
+        /// Database to read (default: the tool's standard location)
+        #[arg(long)]
+        path: Option<PathBuf>,
+    },
+
+    /// Manage shell aliases used for suggestions
+    Alias {
+        #[command(subcommand)]
+        cmd: AliasCmd,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum AliasCmd {
+    /// Import `alias` definitions from shell rc files (default: ~/.zshrc, ~/.bashrc)
+    Import {
+        #[arg(short = 'f', long = "file", num_args = 1.., value_delimiter = ';')]
+        files: Vec<PathBuf>,
     },
 }
 
@@ -71,25 +260,187 @@ fn main() -> Result<()> {
             files,
             top,
             unique,
+            ignore_case,
+            max_corpus,
+            encoding,
             enable_embedding,
             embedding_model,
             enable_llm,
             llm_model,
-        }) => tui::run_tui_loop(
+            llm_device,
+            debounce_ms,
+            heavy_timeout_ms,
+            vim,
+            redact,
+            ignore_space,
+            no_db,
+            inline,
+        }) => {
+            let config = config::Config::load()?;
+            let llm_device = config::resolve_llm_device(llm_device, &config)?;
+            let secrets = config.secret_filter(
+                redact.or(config.redact_secrets).unwrap_or(false),
+                ignore_space.or(config.ignore_space).unwrap_or(true),
+            )?;
+            let placeholders = config.placeholders()?;
+            let dangerous = config.dangerous_commands()?;
+            let encoding = encoding.map_or_else(|| config.history_encoding(), Ok)?;
+            let models = core::ModelOptions {
+                enable_embedding: enable_embedding.or(config.enable_embedding).unwrap_or(true),
+                embedding_model: embedding_model.or(config.embedding_model.clone()),
+                embedding_search: config.embedding_search(),
+                enable_llm: enable_llm.or(config.enable_llm).unwrap_or(false),
+                llm_model: llm_model.or(config.llm_model.clone()),
+                llm_device,
+                weights: config.weights.clone(),
+                dedup: config.dedup()?,
+                prefix_word_boundary: config.prefix_word_boundary.unwrap_or(false),
+                disabled_models: config.disabled_models.clone(),
+            };
+            let app = core::AppOptions {
+                top: top.or(config.top).unwrap_or(20),
+                models,
+                debounce: Duration::from_millis(
+                    debounce_ms
+                        .or(config.debounce_ms)
+                        .unwrap_or(core::DEFAULT_DEBOUNCE_MS),
+                ),
+                heavy_model_timeout: Duration::from_millis(
+                    heavy_timeout_ms
+                        .or(config.heavy_timeout_ms)
+                        .unwrap_or(core::DEFAULT_HEAVY_TIMEOUT_MS),
+                ),
+                vim: vim.or(config.vim).unwrap_or(false),
+                placeholders,
+                dangerous,
+                secrets,
+                max_output_lines: config
+                    .max_output_lines
+                    .unwrap_or(core::DEFAULT_MAX_OUTPUT_LINES),
+                config_path: config::default_config_path(),
+            };
+            tui::run_tui_loop(tui::TuiOptions {
+                files,
+                unique: unique.or(config.unique).unwrap_or(true),
+                ignore_case: ignore_case.or(config.ignore_case).unwrap_or(false),
+                max_corpus: max_corpus.or(config.max_corpus),
+                encoding,
+                no_db,
+                inline: inline.or(config.inline_rows),
+                app,
+            })
+        }
+        Some(Cmd::Search {
             files,
+            query,
+            regex,
+            glob,
             top,
             unique,
+            ignore_case,
+            encoding,
+            no_db,
             enable_embedding,
             embedding_model,
             enable_llm,
             llm_model,
-        ),
-        Some(Cmd::Search {
+            llm_device,
+            format,
+        }) => {
+            let config = config::Config::load()?;
+            let encoding = encoding.map_or_else(|| config.history_encoding(), Ok)?;
+            let secrets = config.configured_secret_filter()?;
+            let llm_device = config::resolve_llm_device(llm_device, &config)?;
+            let models = core::ModelOptions {
+                enable_embedding,
+                embedding_model: embedding_model.or(config.embedding_model.clone()),
+                embedding_search: config.embedding_search(),
+                enable_llm,
+                llm_model: llm_model.or(config.llm_model),
+                llm_device,
+                prefix_word_boundary: config.prefix_word_boundary.unwrap_or(false),
+                ..core::ModelOptions::default()
+            };
+            let options = core::SearchOptions {
+                files,
+                top,
+                unique,
+                ignore_case,
+                encoding,
+                secrets,
+                no_db,
+                models,
+                format,
+            };
+            let pattern = match (regex, glob) {
+                (Some(regex), _) => Some(core::HistoryPattern::regex(&regex, ignore_case)?),
+                (None, Some(glob)) => Some(core::HistoryPattern::glob(&glob, ignore_case)?),
+                (None, None) => None,
+            };
+            if let Some(pattern) = pattern {
+                return core::run_history_grep(options, &pattern);
+            }
+            core::run_search(options, query.as_deref())
+        }
+        Some(Cmd::Complete {
             files,
             query,
+            encoding,
+        }) => {
+            let config = config::Config::load()?;
+            let encoding = encoding.map_or_else(|| config.history_encoding(), Ok)?;
+            let secrets = config.configured_secret_filter()?;
+            let word_boundary = config.prefix_word_boundary.unwrap_or(false);
+            if !core::run_complete(files, query.as_deref(), encoding, &secrets, word_boundary)? {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Some(Cmd::Serve {
+            files,
+            socket,
             top,
             unique,
-        }) => core::run_search(files, &query, top, unique),
+        }) => {
+            let config = config::Config::load()?;
+            server::run_serve(
+                files,
+                socket.unwrap_or_else(server::default_socket_path),
+                top.or(config.top).unwrap_or(20),
+                unique.or(config.unique).unwrap_or(true),
+                &config,
+            )
+        }
+        Some(Cmd::Prune { days, keep_top }) => core::run_prune(days, keep_top),
+        Some(Cmd::Init { shell }) => init::run_init(shell),
+        Some(Cmd::Embeddings {
+            files,
+            rebuild,
+            embedding_model,
+            encoding,
+        }) => {
+            let config = config::Config::load()?;
+            let encoding = encoding.map_or_else(|| config.history_encoding(), Ok)?;
+            let secrets = config.configured_secret_filter()?;
+            core::run_embeddings(
+                files,
+                rebuild,
+                embedding_model.or(config.embedding_model),
+                encoding,
+                &secrets,
+            )
+        }
+        Some(Cmd::Doctor { embedding_model }) => {
+            let config = config::Config::load()?;
+            doctor::run_doctor(embedding_model.or(config.embedding_model))
+        }
+        Some(Cmd::Import { from, path }) => {
+            let config = config::Config::load()?;
+            core::run_import(from, path, &config.configured_secret_filter()?)
+        }
+        Some(Cmd::Alias {
+            cmd: AliasCmd::Import { files },
+        }) => core::run_alias_import(files),
         None => {
             eprintln!(
                 "Try: ghosttype tui
@@ -0,0 +1,133 @@
+use anyhow::Result;
+use libsql::Value;
+
+use super::{
+    sqlite::{prefix_match_sql, SqlitePool},
+    ModelError, SuggestModel, Suggestion,
+};
+
+const MAX_RESULTS: usize = 10;
+
+/// Input as it is stored and looked up, so `git  ch` and `git ch ` share picks
+fn normalize_prefix(input: &str) -> String {
+    input.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Remember that `command` was picked while the input read `prefix`; picking
+/// the same pair again bumps its count
+pub fn record_acceptance(pool: &SqlitePool, prefix: &str, command: &str) -> Result<()> {
+    let prefix = normalize_prefix(prefix);
+    let command = command.trim();
+    if prefix.is_empty() || command.is_empty() {
+        return Ok(());
+    }
+    pool.execute(
+        r#"
+        INSERT INTO acceptances (prefix, command) VALUES (?1, ?2)
+        ON CONFLICT(prefix, command) DO UPDATE SET
+            count = count + 1,
+            accepted_at = CURRENT_TIMESTAMP;
+    "#,
+        vec![Value::Text(prefix), Value::Text(command.to_string())],
+    )
+}
+
+/// Suggests what was picked before for the same input. Picks made after
+/// typing more than the current input still count, scaled by how much of
+/// that longer input has been typed so far.
+#[derive(Clone, Debug)]
+pub struct AcceptanceModel {
+    pool: SqlitePool,
+}
+
+impl AcceptanceModel {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+impl SuggestModel for AcceptanceModel {
+    fn predict(&self, input: &str) -> Result<Vec<Suggestion>> {
+        let prefix = normalize_prefix(input);
+        if prefix.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let sql = format!(
+            r#"
+            SELECT command, SUM(count * CAST(length(?1) AS REAL) / length(prefix)) AS score
+            FROM acceptances
+            WHERE {}
+            GROUP BY command
+            ORDER BY score DESC
+            LIMIT ?2
+        "#,
+            prefix_match_sql("prefix", "?1", true)
+        );
+        match self.pool.query_collect(
+            &sql,
+            vec![Value::Text(prefix), Value::Integer(MAX_RESULTS as i64)],
+            |row| {
+                let command: String = row.get(0)?;
+                let score: f64 = row.get(1)?;
+                Ok(Suggestion::with_source(command, score, "accepted"))
+            },
+        ) {
+            Ok(rows) => Ok(rows),
+            Err(err) if ModelError::is_missing_table(&err) => Ok(Vec::new()),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn weight(&self) -> f64 {
+        1.0
+    }
+
+    fn name(&self) -> &str {
+        "acceptance"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeated_picks_rank_first() {
+        let pool = SqlitePool::open_memory_migrated().unwrap();
+        record_acceptance(&pool, "gco", "git checkout main").unwrap();
+        record_acceptance(&pool, "gco ", "git checkout main").unwrap();
+        record_acceptance(&pool, "gco", "git commit").unwrap();
+        record_acceptance(&pool, "gcob", "git checkout -b topic").unwrap();
+        record_acceptance(&pool, "  ", "ls").unwrap();
+
+        let suggestions = AcceptanceModel::new(pool.clone()).predict("gco").unwrap();
+        let ranked: Vec<(&str, f64)> = suggestions
+            .iter()
+            .map(|s| (s.text.as_str(), s.score))
+            .collect();
+        assert_eq!(
+            ranked,
+            vec![
+                ("git checkout main", 2.0),
+                ("git commit", 1.0),
+                ("git checkout -b topic", 0.75),
+            ]
+        );
+        assert_eq!(suggestions[0].source.as_deref(), Some("accepted"));
+        assert!(AcceptanceModel::new(pool.clone()).predict("gcx").unwrap().is_empty());
+
+        let rows = pool
+            .query_collect("SELECT COUNT(*) FROM acceptances", Vec::<Value>::new(), |row| {
+                Ok(row.get::<i64>(0)?)
+            })
+            .unwrap();
+        assert_eq!(rows, vec![3], "same pair is upserted, blank input skipped");
+    }
+
+    #[test]
+    fn missing_table_yields_no_suggestions() {
+        let pool = SqlitePool::open_memory().unwrap();
+        assert!(AcceptanceModel::new(pool).predict("git").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,128 @@
+use super::AliasEntry;
+
+/// Extract `alias name='cmd'` definitions from shell rc file contents.
+/// Handles `alias -g`, single/double quoted and bare values, and values
+/// containing `=`. Lines that are not alias definitions are ignored.
+pub fn parse_alias_definitions(text: &str) -> Vec<AliasEntry> {
+    text.lines().filter_map(parse_alias_line).collect()
+}
+
+fn parse_alias_line(line: &str) -> Option<AliasEntry> {
+    let rest = line.trim().strip_prefix("alias")?;
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+
+    // Skip option flags such as `-g` (zsh global aliases) or `-s`
+    let mut rest = rest.trim_start();
+    while let Some(after_flag) = rest.strip_prefix('-') {
+        let end = after_flag
+            .find(char::is_whitespace)
+            .unwrap_or(after_flag.len());
+        rest = after_flag[end..].trim_start();
+    }
+
+    let (name, value) = rest.split_once('=')?;
+    let name = name.trim();
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return None;
+    }
+
+    let cmd = unquote_shell_value(value);
+    if cmd.is_empty() {
+        return None;
+    }
+
+    Some(AliasEntry {
+        name: name.to_string(),
+        cmd,
+    })
+}
+
+/// Undo shell quoting for one word: `'...'`, `"..."` (with backslash escapes)
+/// and bare text, including concatenations like `'it'\''s'`.
+/// Stops at the first unquoted whitespace or `#` comment.
+fn unquote_shell_value(raw: &str) -> String {
+    let mut out = String::new();
+    let mut chars = raw.trim_start().chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\'' => {
+                for inner in chars.by_ref() {
+                    if inner == '\'' {
+                        break;
+                    }
+                    out.push(inner);
+                }
+            }
+            '"' => {
+                while let Some(inner) = chars.next() {
+                    match inner {
+                        '"' => break,
+                        '\\' => {
+                            if let Some(escaped) = chars.next() {
+                                out.push(escaped);
+                            }
+                        }
+                        other => out.push(other),
+                    }
+                }
+            }
+            '\\' => {
+                if let Some(escaped) = chars.next() {
+                    out.push(escaped);
+                }
+            }
+            c if c.is_whitespace() || c == '#' => break,
+            c => out.push(c),
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, cmd: &str) -> AliasEntry {
+        AliasEntry {
+            name: name.into(),
+            cmd: cmd.into(),
+        }
+    }
+
+    #[test]
+    fn parses_quoted_and_global_aliases() {
+        let rc = r#"
+export PATH="$HOME/bin:$PATH"
+alias gs='git status'
+alias ll="ls -al --color=auto"
+alias -g G='| grep'
+alias k=kubectl # short
+  alias dc='docker compose'
+alias say='echo it'\''s'
+aliases=1
+"#;
+        assert_eq!(
+            parse_alias_definitions(rc),
+            vec![
+                entry("gs", "git status"),
+                entry("ll", "ls -al --color=auto"),
+                entry("G", "| grep"),
+                entry("k", "kubectl"),
+                entry("dc", "docker compose"),
+                entry("say", "echo it's"),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_equals_signs_inside_values() {
+        assert_eq!(
+            parse_alias_definitions("alias env-dev='FOO=bar BAZ=qux make dev'"),
+            vec![entry("env-dev", "FOO=bar BAZ=qux make dev")]
+        );
+    }
+}
@@ -1,9 +1,20 @@
+mod import;
+
 use std::sync::Arc;
 
 use anyhow::Result;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
 use libsql::Value;
 
-use crate::model::{sqlite::SqlitePool, SuggestModel, Suggestion};
+use crate::model::{
+    sqlite::{prefix_match_sql, prefix_match_value, SqlitePool},
+    ModelError, SuggestModel, Suggestion,
+};
+
+pub use import::parse_alias_definitions;
+
+const MAX_ALIASES: usize = 10;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct AliasEntry {
@@ -12,6 +23,7 @@ pub struct AliasEntry {
 }
 
 pub trait AliasStore: Send + Sync {
+    /// Aliases matching `input`, best match first
     fn query_aliases(&self, input: &str) -> Result<Vec<AliasEntry>>;
 }
 
@@ -41,53 +53,135 @@ impl SuggestModel for AliasModel {
         if input.trim().is_empty() {
             return Ok(Vec::new());
         }
+        // Suggest the expanded command so accepting it puts the real
+        // command on the input line, not the alias name already typed
         let entries = self.store.query_aliases(input)?;
         Ok(entries
             .into_iter()
-            .map(|entry| Suggestion::with_source(entry.name, 1.0, "alias"))
+            .enumerate()
+            .map(|(rank, entry)| {
+                Suggestion::with_source(entry.cmd, 1.0 / (rank + 1) as f64, "alias")
+            })
             .collect())
     }
 
     fn weight(&self) -> f64 {
         0.8
     }
+
+    fn name(&self) -> &str {
+        "alias"
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct SqlAliasStore {
     pool: SqlitePool,
+    case_sensitive: bool,
 }
 
 impl SqlAliasStore {
     pub fn new(pool: SqlitePool) -> Self {
-        Self { pool }
+        Self {
+            pool,
+            case_sensitive: false,
+        }
+    }
+
+    /// Match alias names and commands case-sensitively instead of the
+    /// default case-insensitive comparison
+    pub fn with_case_sensitive(mut self, case_sensitive: bool) -> Self {
+        self.case_sensitive = case_sensitive;
+        self
+    }
+
+    /// Insert or replace an alias, refreshing `updated_at` on re-import
+    pub fn upsert(&self, entry: &AliasEntry) -> Result<()> {
+        self.pool.execute(
+            r#"
+            INSERT INTO aliases (name, cmd, updated_at)
+            VALUES (?1, ?2, CURRENT_TIMESTAMP)
+            ON CONFLICT(name) DO UPDATE SET
+                cmd = excluded.cmd,
+                updated_at = CURRENT_TIMESTAMP;
+        "#,
+            vec![
+                Value::Text(entry.name.clone()),
+                Value::Text(entry.cmd.clone()),
+            ],
+        )
+    }
+
+    fn select_aliases(&self, sql: &str, params: Vec<Value>) -> Result<Vec<AliasEntry>> {
+        match self.pool.query_collect(sql, params, |row| {
+            let name: String = row.get(0)?;
+            let cmd: String = row.get(1)?;
+            Ok(AliasEntry { name, cmd })
+        }) {
+            Ok(rows) => Ok(rows),
+            Err(err) if ModelError::is_missing_table(&err) => Ok(Vec::new()),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn matcher(&self) -> SkimMatcherV2 {
+        let matcher = SkimMatcherV2::default();
+        if self.case_sensitive {
+            matcher.respect_case()
+        } else {
+            matcher.ignore_case()
+        }
     }
 }
 
 impl AliasStore for SqlAliasStore {
+    /// Prefix matches on name or command come first, then the remaining
+    /// aliases that fuzzy-match either, by match quality. Alias tables are
+    /// small, so the fuzzy pass scores every alias in memory.
     fn query_aliases(&self, input: &str) -> Result<Vec<AliasEntry>> {
-        let like = format!("{}%", input);
-        let sql = r#"
+        let sql = format!(
+            r#"
             SELECT name, cmd
             FROM aliases
-            WHERE name LIKE ? OR cmd LIKE ?
+            WHERE {} OR {}
             ORDER BY updated_at DESC
-            LIMIT 10
-        "#;
-
-        match self.pool.query_collect(
-            sql,
-            vec![Value::Text(like.clone()), Value::Text(like)],
-            |row| {
-                let name: String = row.get(0)?;
-                let cmd: String = row.get(1)?;
-                Ok(AliasEntry { name, cmd })
-            },
-        ) {
-            Ok(rows) => Ok(rows),
-            Err(err) if err.to_string().contains("no such table") => Ok(Vec::new()),
-            Err(err) => Err(err),
+            LIMIT ?2
+        "#,
+            prefix_match_sql("name", "?1", self.case_sensitive),
+            prefix_match_sql("cmd", "?1", self.case_sensitive),
+        );
+        let mut entries = self.select_aliases(
+            &sql,
+            vec![
+                prefix_match_value(input, self.case_sensitive),
+                Value::Integer(MAX_ALIASES as i64),
+            ],
+        )?;
+        if entries.len() >= MAX_ALIASES {
+            return Ok(entries);
         }
+
+        let matcher = self.matcher();
+        let mut fuzzy: Vec<(i64, AliasEntry)> = self
+            .select_aliases(
+                "SELECT name, cmd FROM aliases ORDER BY updated_at DESC",
+                Vec::new(),
+            )?
+            .into_iter()
+            .filter(|entry| !entries.iter().any(|prefix| prefix.name == entry.name))
+            .filter_map(|entry| {
+                let score = [&entry.name, &entry.cmd]
+                    .into_iter()
+                    .filter_map(|text| matcher.fuzzy_match(text, input))
+                    .max()?;
+                Some((score, entry))
+            })
+            .collect();
+        // Stable sort: equal scores keep the most recently updated first
+        fuzzy.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        let remaining = MAX_ALIASES - entries.len();
+        entries.extend(fuzzy.into_iter().take(remaining).map(|(_, entry)| entry));
+        Ok(entries)
     }
 }
 
@@ -129,10 +223,46 @@ mod tests {
         let model = AliasModel::new(Arc::new(store));
         let suggestions = model.predict("g").unwrap();
         assert_eq!(suggestions.len(), 2);
-        assert_eq!(suggestions[0].text, "gs");
+        assert_eq!(suggestions[0].text, "git status");
         assert_eq!(suggestions[0].source.as_deref(), Some("alias"));
     }
 
+    #[test]
+    fn alias_name_expands_to_full_command() {
+        let pool = SqlitePool::open_memory_migrated().unwrap();
+        let store = SqlAliasStore::new(pool.clone());
+        store
+            .upsert(&AliasEntry {
+                name: "gs".into(),
+                cmd: "git status".into(),
+            })
+            .unwrap();
+
+        let model = AliasModel::with_sql_store(pool);
+        let suggestions = model.predict("gs").unwrap();
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].text, "git status");
+    }
+
+    #[test]
+    fn sql_store_matches_case_insensitively_by_default() {
+        let pool = SqlitePool::open_memory_migrated().unwrap();
+        let store = SqlAliasStore::new(pool.clone());
+        store
+            .upsert(&AliasEntry {
+                name: "GS".into(),
+                cmd: "Git Status".into(),
+            })
+            .unwrap();
+
+        assert_eq!(store.query_aliases("gs").unwrap().len(), 1);
+        assert_eq!(store.query_aliases("git").unwrap().len(), 1);
+
+        let strict = SqlAliasStore::new(pool).with_case_sensitive(true);
+        assert!(strict.query_aliases("git").unwrap().is_empty());
+        assert_eq!(strict.query_aliases("Git").unwrap().len(), 1);
+    }
+
     #[test]
     fn sql_store_queries_aliases() {
         let pool = SqlitePool::open_memory().unwrap();
@@ -160,4 +290,59 @@ mod tests {
             .iter()
             .all(|e| e.name.starts_with('g') || e.cmd.starts_with('g')));
     }
+
+    #[test]
+    fn sql_store_fuzzy_matches_after_prefix_matches() {
+        let pool = SqlitePool::open_memory_migrated().unwrap();
+        let store = SqlAliasStore::new(pool);
+        for (name, cmd) in [
+            ("gits", "git status"),
+            ("gsw", "git switch"),
+            ("dcu", "docker compose up"),
+            ("ll", "ls -al"),
+        ] {
+            store
+                .upsert(&AliasEntry {
+                    name: name.into(),
+                    cmd: cmd.into(),
+                })
+                .unwrap();
+        }
+
+        let names: Vec<String> = store
+            .query_aliases("gs")
+            .unwrap()
+            .into_iter()
+            .map(|e| e.name)
+            .collect();
+        assert_eq!(names[0], "gsw");
+        assert!(names.contains(&"gits".to_string()));
+        assert!(!names.contains(&"ll".to_string()));
+
+        let suggestions = AliasModel::new(Arc::new(store)).predict("dcup").unwrap();
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].text, "docker compose up");
+    }
+
+    #[test]
+    fn upsert_replaces_existing_alias() {
+        let pool = SqlitePool::open_memory_migrated().unwrap();
+        let store = SqlAliasStore::new(pool);
+        store
+            .upsert(&AliasEntry {
+                name: "gs".into(),
+                cmd: "git status".into(),
+            })
+            .unwrap();
+        store
+            .upsert(&AliasEntry {
+                name: "gs".into(),
+                cmd: "git status -sb".into(),
+            })
+            .unwrap();
+
+        let entries = store.query_aliases("gs").unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].cmd, "git status -sb");
+    }
 }
@@ -1,11 +1,14 @@
-use std::path::PathBuf;
+use std::fmt::Debug;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
+use std::sync::Arc;
 
 use anyhow::{bail, Context, Result};
 use libsql::Value;
-use log::debug;
+use log::{debug, info};
+use serde::Deserialize;
 
-use super::{sqlite::SqlitePool, SuggestModel, Suggestion};
+use super::{sqlite::SqlitePool, ModelError, SuggestModel, Suggestion};
 
 const DEFAULT_SOURCE: &str = "history";
 const HEALTHCHECK_PROMPT: &str = "ghosttype-healthcheck";
@@ -14,6 +17,13 @@ const SEARCH_TOP_K: usize = 10;
 const SEARCH_THRESHOLD: f64 = 0.5;
 const LLAMA_EMBED_BIN_ENV: &str = "LLAMA_EMBED_BIN";
 const LLAMA_EMBED_MODEL_ENV: &str = "LLAMA_EMBED_MODEL";
+/// Texts embedded per `llama-embedding` invocation while learning
+const LEARN_BATCH_SIZE: usize = 32;
+/// Separator between prompts passed to one `llama-embedding` run; commands
+/// may contain newlines, so the default `\n` separator cannot be used
+const LLAMA_EMBED_SEPARATOR: &str = "<#ghosttype#>";
+/// `meta` key recording which embedding model produced the stored vectors
+const EMBEDDING_MODEL_META_KEY: &str = "embedding_model";
 
 #[derive(Clone, Debug)]
 pub struct EmbeddingStore {
@@ -40,16 +50,59 @@ impl EmbeddingStore {
         Ok(rows.first().copied().unwrap_or(0) > 0)
     }
 
+    /// Delete every stored embedding for `source`
+    pub fn clear(&self, source: &str) -> Result<()> {
+        self.pool.execute(
+            "DELETE FROM embeddings WHERE source = ?1",
+            vec![Value::Text(source.to_string())],
+        )
+    }
+
+    /// Identity of the model the stored vectors came from, if recorded
+    pub fn model_identity(&self) -> Result<Option<String>> {
+        let rows = self.pool.query_collect(
+            "SELECT path FROM meta WHERE key = ?1",
+            vec![Value::Text(EMBEDDING_MODEL_META_KEY.to_string())],
+            |row| Ok(row.get::<String>(0)?),
+        )?;
+        Ok(rows.into_iter().next())
+    }
+
+    pub fn set_model_identity(&self, identity: &str) -> Result<()> {
+        self.pool.execute(
+            r#"
+            INSERT INTO meta (key, path, mtime) VALUES (?1, ?2, 0)
+            ON CONFLICT(key) DO UPDATE SET path = excluded.path;
+        "#,
+            vec![
+                Value::Text(EMBEDDING_MODEL_META_KEY.to_string()),
+                Value::Text(identity.to_string()),
+            ],
+        )
+    }
+
     pub fn save(&self, source: &str, text: &str, embedding: &[f32]) -> Result<()> {
         let emb_json = serialize_embedding(embedding);
-        self.pool.execute(
+        let result = self.pool.execute(
             "INSERT INTO embeddings (source, text, emb) VALUES (?1, ?2, vector32(?3))",
             vec![
                 Value::Text(source.to_string()),
                 Value::Text(text.to_string()),
                 Value::Text(emb_json),
             ],
-        )
+        );
+        match result {
+            // Without vector32() store the same little-endian f32 layout it produces
+            Err(err) if ModelError::is_vector_unsupported(&err) => self.pool.execute(
+                "INSERT INTO embeddings (source, text, emb) VALUES (?1, ?2, ?3)",
+                vec![
+                    Value::Text(source.to_string()),
+                    Value::Text(text.to_string()),
+                    Value::Blob(encode_f32_blob(embedding)),
+                ],
+            ),
+            other => other,
+        }
     }
 
     pub fn search_similar(
@@ -69,7 +122,7 @@ JOIN embeddings e ON e.rowid = t.id
 WHERE e.source = ?3
 ORDER BY score DESC;
 "#;
-        let rows = self.pool.query_collect(
+        let rows = match self.pool.query_collect(
             sql,
             vec![
                 Value::Text(emb_json),
@@ -81,12 +134,66 @@ ORDER BY score DESC;
                 let score: f64 = row.get(1)?;
                 Ok(Suggestion::with_source(text, score, source))
             },
-        )?;
+        ) {
+            Ok(rows) => rows,
+            Err(err) if ModelError::is_vector_unsupported(&err) => {
+                debug!("vector index unavailable, scanning embeddings in memory: {err:#}");
+                self.search_similar_in_memory(embedding, source, top_k)?
+            }
+            Err(err) => return Err(err),
+        };
         Ok(rows
             .into_iter()
             .filter(|s| s.score >= threshold)
             .collect())
     }
+
+    /// Brute-force cosine search over every stored vector for `source`, for
+    /// SQLite builds without libsql's vector functions or index
+    fn search_similar_in_memory(
+        &self,
+        embedding: &[f32],
+        source: &str,
+        top_k: usize,
+    ) -> Result<Vec<Suggestion>> {
+        let mut rows = self.pool.query_collect(
+            "SELECT text, emb FROM embeddings WHERE source = ?1 AND emb IS NOT NULL",
+            vec![Value::Text(source.to_string())],
+            |row| {
+                let text: String = row.get(0)?;
+                let stored = decode_f32_blob(&row.get::<Vec<u8>>(1)?);
+                let score = cosine_similarity(embedding, &stored);
+                Ok(Suggestion::with_source(text, score, source))
+            },
+        )?;
+        rows.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+        rows.truncate(top_k);
+        Ok(rows)
+    }
+}
+
+/// Turns text into an embedding vector for `EmbeddingModel`.
+///
+/// `LlamaEmbeddingClient` is the only backend today; an in-process one can be
+/// added by implementing this trait.
+pub trait EmbeddingClient: Debug + Send + Sync {
+    fn embed(&self, text: &str) -> Result<Vec<f32>>;
+
+    /// Names the model producing the vectors; when it changes, stored
+    /// embeddings are no longer comparable and get rebuilt
+    fn identity(&self) -> String;
+
+    /// Embed several texts, returning one vector per text in order.
+    /// Backends with real batching should override the one-by-one default.
+    fn embed_batch(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        texts.iter().map(|text| self.embed(text)).collect()
+    }
+
+    /// Fail early when the backend cannot produce embeddings at all
+    fn health_check(&self) -> Result<()> {
+        let _ = self.embed(HEALTHCHECK_PROMPT)?;
+        Ok(())
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -103,10 +210,15 @@ impl LlamaEmbeddingClient {
         }
     }
 
-    pub fn from_env_or(model_path: Option<PathBuf>) -> Result<Self> {
-        let binary = std::env::var(LLAMA_EMBED_BIN_ENV)
+    /// `$LLAMA_EMBED_BIN`, or `llama-embedding` looked up on PATH
+    pub fn binary_from_env() -> PathBuf {
+        std::env::var(LLAMA_EMBED_BIN_ENV)
             .map(PathBuf::from)
-            .unwrap_or_else(|_| PathBuf::from("llama-embedding"));
+            .unwrap_or_else(|_| PathBuf::from("llama-embedding"))
+    }
+
+    pub fn from_env_or(model_path: Option<PathBuf>) -> Result<Self> {
+        let binary = Self::binary_from_env();
 
         let path = if let Ok(env_path) = std::env::var(LLAMA_EMBED_MODEL_ENV) {
             Some(PathBuf::from(env_path))
@@ -122,14 +234,59 @@ impl LlamaEmbeddingClient {
         }
     }
 
-    pub fn embed(&self, text: &str) -> Result<Vec<f32>> {
-        // llama-embedding -m ./model.gguf --log-disable -p "text"
+    pub fn model_path(&self) -> &Path {
+        &self.model_path
+    }
+}
+
+/// Run `<binary> --help`, failing with the reason it could not be run
+pub fn check_llama_embedding(binary: &Path) -> Result<()> {
+    let status = Command::new(binary)
+        .arg("--help")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .with_context(|| format!("running {} --help", binary.display()))?;
+    if !status.success() {
+        bail!("{} --help exited with status {status}", binary.display());
+    }
+    Ok(())
+}
+
+/// Whether this libsql build has the vector functions embeddings rely on;
+/// without them search falls back to a slower in-memory scan
+pub fn check_vector_support(pool: &SqlitePool) -> Result<()> {
+    pool.query_collect("SELECT vector32('[1.0]')", Vec::<Value>::new(), |_| Ok(()))
+        .context("calling vector32")?;
+    Ok(())
+}
+
+impl EmbeddingClient for LlamaEmbeddingClient {
+    fn identity(&self) -> String {
+        self.model_path.to_string_lossy().into_owned()
+    }
+
+    fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        // The JSON output rejects stray tokens instead of skipping them
+        let mut embeddings = self.embed_batch(&[text])?;
+        Ok(embeddings.remove(0))
+    }
+
+    fn embed_batch(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+
         let output = Command::new(&self.binary)
             .arg("-m")
             .arg(&self.model_path)
             .arg("--log-disable")
+            .arg("--embd-output-format")
+            .arg("json")
+            .arg("--embd-separator")
+            .arg(LLAMA_EMBED_SEPARATOR)
             .arg("-p")
-            .arg(text)
+            .arg(texts.join(LLAMA_EMBED_SEPARATOR))
             .stdout(Stdio::piped())
             .stderr(Stdio::null())
             .output()
@@ -139,63 +296,119 @@ impl LlamaEmbeddingClient {
             bail!("llama-embedding exited with status {}", output.status);
         }
 
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        parse_embedding_output(&stdout)
+        let embeddings = parse_batch_embedding_output(&String::from_utf8_lossy(&output.stdout))?;
+        if embeddings.len() != texts.len() {
+            bail!(
+                "llama-embedding returned {} embeddings for {} prompts",
+                embeddings.len(),
+                texts.len()
+            );
+        }
+        Ok(embeddings)
     }
+}
 
-    pub fn health_check(&self) -> Result<()> {
-        let _ = self.embed(HEALTHCHECK_PROMPT)?;
-        Ok(())
+/// How many neighbours `EmbeddingModel` looks up per query, and the cosine
+/// similarity a neighbour needs to be suggested
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EmbeddingSearch {
+    pub top_k: usize,
+    pub threshold: f64,
+}
+
+impl Default for EmbeddingSearch {
+    fn default() -> Self {
+        Self {
+            top_k: SEARCH_TOP_K,
+            threshold: SEARCH_THRESHOLD,
+        }
     }
 }
 
 #[derive(Clone, Debug)]
 pub struct EmbeddingModel {
     store: EmbeddingStore,
-    client: LlamaEmbeddingClient,
+    client: Arc<dyn EmbeddingClient>,
+    search: EmbeddingSearch,
 }
 
 impl EmbeddingModel {
-    pub fn new(store: EmbeddingStore, client: LlamaEmbeddingClient) -> Self {
-        Self { store, client }
+    pub fn new<C>(store: EmbeddingStore, client: C) -> Self
+    where
+        C: EmbeddingClient + 'static,
+    {
+        Self {
+            store,
+            client: Arc::new(client),
+            search: EmbeddingSearch::default(),
+        }
+    }
+
+    pub fn with_search(mut self, search: EmbeddingSearch) -> Self {
+        self.search = search;
+        self
     }
 
     pub fn warm_up(&self) -> Result<()> {
         self.client.health_check()
     }
 
-    pub fn learn(&self, entries: &[String]) -> Result<()> {
-        let mut inserted = 0usize;
+    /// Embed and store up to `MAX_LEARN_INSERTS` entries not stored yet,
+    /// returning how many were saved. Vectors from a different model than
+    /// the recorded one are discarded first.
+    pub fn learn(&self, entries: &[String]) -> Result<usize> {
+        let identity = self.client.identity();
+        match self.store.model_identity()? {
+            Some(previous) if previous != identity => {
+                info!("embedding model changed from {previous} to {identity}; rebuilding embeddings");
+                self.store.clear(DEFAULT_SOURCE)?;
+            }
+            _ => {}
+        }
+        self.store.set_model_identity(&identity)?;
 
+        let mut candidates: Vec<&str> = Vec::new();
         for entry in entries {
-            if inserted >= MAX_LEARN_INSERTS {
+            if candidates.len() >= MAX_LEARN_INSERTS {
                 break;
             }
 
             let candidate = entry.trim();
-            if candidate.is_empty() {
+            if candidate.is_empty() || candidates.contains(&candidate) {
                 continue;
             }
 
             if self.store.exists(DEFAULT_SOURCE, candidate)? {
                 continue;
             }
+            candidates.push(candidate);
+        }
 
-            match self.client.embed(candidate) {
-                Ok(embedding) => {
-                    if let Err(err) = self.store.save(DEFAULT_SOURCE, candidate, &embedding) {
-                        debug!("failed to save embedding: {err:?}");
-                    } else {
-                        inserted += 1;
-                    }
-                }
+        let mut inserted = 0usize;
+        for batch in candidates.chunks(LEARN_BATCH_SIZE) {
+            let embeddings = match self.client.embed_batch(batch) {
+                Ok(embeddings) => embeddings,
                 Err(err) => {
                     debug!("embedding request failed: {err:?}");
+                    continue;
+                }
+            };
+            for (candidate, embedding) in batch.iter().zip(embeddings) {
+                if let Err(err) = self.store.save(DEFAULT_SOURCE, candidate, &embedding) {
+                    debug!("failed to save embedding: {err:?}");
+                } else {
+                    inserted += 1;
                 }
             }
         }
 
-        Ok(())
+        Ok(inserted)
+    }
+
+    /// Drop every stored history embedding and learn `entries` from scratch
+    pub fn relearn(&self, entries: &[String]) -> Result<usize> {
+        self.store.clear(DEFAULT_SOURCE)?;
+        self.learn(entries)
     }
 }
 
@@ -213,9 +426,12 @@ impl SuggestModel for EmbeddingModel {
             }
         };
 
-        let mut suggestions = self
-            .store
-            .search_similar(&embedding, DEFAULT_SOURCE, SEARCH_TOP_K, SEARCH_THRESHOLD)?;
+        let mut suggestions = self.store.search_similar(
+            &embedding,
+            DEFAULT_SOURCE,
+            self.search.top_k,
+            self.search.threshold,
+        )?;
         let weight = self.weight();
         for suggestion in &mut suggestions {
             suggestion.score *= weight;
@@ -226,6 +442,10 @@ impl SuggestModel for EmbeddingModel {
     fn weight(&self) -> f64 {
         0.6
     }
+
+    fn name(&self) -> &str {
+        "embedding"
+    }
 }
 
 fn serialize_embedding(vec: &[f32]) -> String {
@@ -241,25 +461,208 @@ fn serialize_embedding(vec: &[f32]) -> String {
     out
 }
 
-fn parse_embedding_output(raw: &str) -> Result<Vec<f32>> {
-    let mut values = Vec::new();
+/// Raw little-endian f32s, the layout libsql uses for `F32_BLOB` values
+fn encode_f32_blob(vec: &[f32]) -> Vec<u8> {
+    vec.iter().flat_map(|value| value.to_le_bytes()).collect()
+}
+
+fn decode_f32_blob(blob: &[u8]) -> Vec<f32> {
+    blob.chunks_exact(4)
+        .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect()
+}
+
+/// Cosine similarity, or 0.0 when either vector is zero or their lengths differ
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f64 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let (mut dot, mut norm_a, mut norm_b) = (0.0f64, 0.0f64, 0.0f64);
+    for (&x, &y) in a.iter().zip(b) {
+        let (x, y) = (x as f64, y as f64);
+        dot += x * y;
+        norm_a += x * x;
+        norm_b += y * y;
+    }
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a.sqrt() * norm_b.sqrt())
+}
+
+#[derive(Deserialize)]
+struct BatchEmbeddingOutput {
+    data: Vec<BatchEmbedding>,
+}
+
+#[derive(Deserialize)]
+struct BatchEmbedding {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+/// Parse `llama-embedding --embd-output-format json`, ordering vectors by prompt index
+fn parse_batch_embedding_output(raw: &str) -> Result<Vec<Vec<f32>>> {
+    let start = raw
+        .find('{')
+        .context("llama-embedding returned no JSON output")?;
+    let mut output: BatchEmbeddingOutput =
+        serde_json::from_str(raw[start..].trim_end()).context("parsing llama-embedding JSON")?;
+    output.data.sort_by_key(|item| item.index);
+    Ok(output.data.into_iter().map(|item| item.embedding).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_batch_json_in_prompt_order() {
+        let raw = r#"{
+  "object": "list",
+  "data": [
+    {"object": "embedding", "index": 1, "embedding": [0.5, -1.0]},
+    {"object": "embedding", "index": 0, "embedding": [0.25, 2.0]}
+  ]
+}
+"#;
+        assert_eq!(
+            parse_batch_embedding_output(raw).unwrap(),
+            vec![vec![0.25, 2.0], vec![0.5, -1.0]]
+        );
+        assert!(parse_batch_embedding_output("embedding 0: 0.1 0.2").is_err());
+    }
+
+    /// A stand-in `llama-embedding` that prints `stdout` whatever it is asked
+    fn fake_llama_embedding(name: &str, stdout: &str) -> LlamaEmbeddingClient {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir()
+            .join(format!("ghosttype-fake-embed-{name}-{}", std::process::id()));
+        std::fs::write(&path, format!("#!/bin/sh\ncat <<'EOF'\n{stdout}\nEOF\n")).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        LlamaEmbeddingClient::new(path, "model.gguf")
+    }
+
+    #[test]
+    fn single_embeddings_reject_stray_output() {
+        let json = fake_llama_embedding(
+            "json",
+            r#"{"data": [{"index": 0, "embedding": [0.25, -1.0]}]}"#,
+        );
+        assert_eq!(json.embed("git status").unwrap(), vec![0.25, -1.0]);
+
+        let plain = fake_llama_embedding("plain", "embedding 0: 0.25 nan? -1.0");
+        assert!(plain.embed("git status").is_err());
+        let extra = fake_llama_embedding(
+            "extra",
+            r#"{"data": [{"index": 0, "embedding": [0.25]}, {"index": 1, "embedding": [1.0]}]}"#,
+        );
+        assert!(extra.embed("git status").is_err());
+
+        for client in [json, plain, extra] {
+            let _ = std::fs::remove_file(&client.binary);
+        }
+    }
+
+    #[test]
+    fn falls_back_to_in_memory_cosine_without_vector_index() {
+        let pool = SqlitePool::open_memory_migrated().unwrap();
+        let store = EmbeddingStore::new(pool.clone());
+        let axis = |i: usize| -> Vec<f32> { (0..768).map(|j| if j == i { 1.0 } else { 0.0 }).collect() };
+        store.save(DEFAULT_SOURCE, "git status", &axis(0)).unwrap();
+        store.save(DEFAULT_SOURCE, "cargo build", &axis(1)).unwrap();
+
+        pool.execute("DROP INDEX embeddings_idx", std::iter::empty::<Value>()).unwrap();
+        let mut query = axis(0);
+        query[1] = 0.5;
+
+        let results = store.search_similar(&query, DEFAULT_SOURCE, 10, 0.5).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].text, "git status");
+        assert!((results[0].score - 1.0 / 1.25f64.sqrt()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn cosine_similarity_handles_degenerate_vectors() {
+        assert!((cosine_similarity(&[1.0, 0.0], &[1.0, 0.0]) - 1.0).abs() < 1e-12);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+        assert_eq!(cosine_similarity(&[1.0], &[1.0, 0.0]), 0.0);
+        assert_eq!(decode_f32_blob(&encode_f32_blob(&[0.5, -2.0])), vec![0.5, -2.0]);
+    }
 
-    for token in raw.split_whitespace() {
-        let cleaned = token.trim_matches(|c| c == '[' || c == ']' || c == ',');
-        if cleaned.is_empty() {
-            continue;
+    #[derive(Debug)]
+    struct AxisClient {
+        identity: &'static str,
+    }
+
+    impl EmbeddingClient for AxisClient {
+        fn embed(&self, text: &str) -> Result<Vec<f32>> {
+            let mut vec = vec![0.0; 768];
+            vec[text.len() % 768] = 1.0;
+            Ok(vec)
         }
-        match cleaned.parse::<f32>() {
-            Ok(value) => values.push(value),
-            Err(_) => {
-                // ignore tokens that are not numeric
-            }
+
+        fn identity(&self) -> String {
+            self.identity.to_string()
         }
     }
 
-    if values.is_empty() {
-        bail!("llama-embedding returned no embedding values");
+    #[test]
+    fn model_change_discards_old_embeddings() {
+        let pool = SqlitePool::open_memory_migrated().unwrap();
+        let corpus = vec!["git status".to_string(), "ls".to_string()];
+        let stored = |pool: &SqlitePool| -> Vec<String> {
+            pool.query_collect(
+                "SELECT text FROM embeddings ORDER BY text",
+                std::iter::empty::<Value>(),
+                |row| Ok(row.get::<String>(0)?),
+            )
+            .unwrap()
+        };
+
+        let first = EmbeddingModel::new(
+            EmbeddingStore::new(pool.clone()),
+            AxisClient { identity: "a.gguf" },
+        );
+        assert_eq!(first.learn(&corpus).unwrap(), 2);
+        assert_eq!(first.learn(&corpus).unwrap(), 0, "already stored");
+        assert_eq!(first.relearn(&corpus[..1]).unwrap(), 1);
+        assert_eq!(stored(&pool), vec!["git status"]);
+
+        let second = EmbeddingModel::new(
+            EmbeddingStore::new(pool.clone()),
+            AxisClient { identity: "b.gguf" },
+        );
+        assert_eq!(second.learn(&corpus[1..]).unwrap(), 1);
+        assert_eq!(stored(&pool), vec!["ls"]);
+        assert_eq!(
+            EmbeddingStore::new(pool).model_identity().unwrap().as_deref(),
+            Some("b.gguf")
+        );
     }
 
-    Ok(values)
+    #[test]
+    fn lower_threshold_returns_more_neighbours() {
+        let pool = SqlitePool::open_memory_migrated().unwrap();
+        let store = EmbeddingStore::new(pool);
+        // AxisClient embeds the one-char query "q" as the unit vector on axis 1
+        let vector = |along_query: f32, along_other: f32| -> Vec<f32> {
+            let mut vec = vec![0.0; 768];
+            vec[1] = along_query;
+            vec[2] = along_other;
+            vec
+        };
+        store.save(DEFAULT_SOURCE, "git status", &vector(1.0, 0.0)).unwrap();
+        store.save(DEFAULT_SOURCE, "git stash", &vector(1.0, 1.0)).unwrap();
+        store.save(DEFAULT_SOURCE, "cargo build", &vector(0.3, 1.0)).unwrap();
+
+        let model = EmbeddingModel::new(store, AxisClient { identity: "a.gguf" });
+        assert_eq!(model.clone().predict("q").unwrap().len(), 2);
+        let loose = model.with_search(EmbeddingSearch {
+            threshold: 0.2,
+            ..Default::default()
+        });
+        assert_eq!(loose.predict("q").unwrap().len(), 3);
+    }
 }
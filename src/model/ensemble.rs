@@ -1,17 +1,142 @@
+use std::borrow::Cow;
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
+use std::str::FromStr;
 use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, Context, Result};
+use log::{debug, log_enabled, warn, Level};
 
-use super::{SuggestModel, Suggestion};
+use super::{ModelError, SuggestModel, Suggestion};
 
 pub type SharedModel = Arc<dyn SuggestModel>;
 
+/// Default `k` for reciprocal rank fusion, as in the original RRF paper
+pub const DEFAULT_RRF_K: f64 = 60.0;
+
+/// How per-model suggestion lists are combined into one ranking
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum AggregationStrategy {
+    /// Sum of `normalized_score * weight` across models, with each model's
+    /// scores min-max normalized to 0..1 first
+    #[default]
+    WeightedSum,
+    /// Sum of `weight / (k + rank)` across models, ignoring raw score scales
+    ReciprocalRankFusion { k: f64 },
+}
+
+/// How suggestion texts are compared when merging results from several models
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DedupMode {
+    /// Only byte-identical texts are merged
+    #[default]
+    Exact,
+    /// Texts are trimmed and runs of whitespace collapsed before comparing,
+    /// so `git  status` and `git status ` merge into one suggestion
+    NormalizedWhitespace,
+    /// Whitespace is normalized and case ignored
+    IgnoreCase,
+}
+
+impl DedupMode {
+    /// Key under which `text` is merged with other suggestions
+    pub fn key<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        match self {
+            DedupMode::Exact => Cow::Borrowed(text),
+            DedupMode::NormalizedWhitespace => collapse_whitespace(text),
+            DedupMode::IgnoreCase => Cow::Owned(collapse_whitespace(text).to_lowercase()),
+        }
+    }
+}
+
+impl FromStr for DedupMode {
+    type Err = anyhow::Error;
+
+    /// Accepts `exact`, `whitespace` and `ignore-case`
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "exact" => Ok(DedupMode::Exact),
+            "whitespace" => Ok(DedupMode::NormalizedWhitespace),
+            "ignore-case" => Ok(DedupMode::IgnoreCase),
+            _ => bail!("unknown dedup mode {s:?} (expected exact, whitespace or ignore-case)"),
+        }
+    }
+}
+
+/// Trim and replace each run of whitespace with a single space; borrows when
+/// `text` is already normalized
+fn collapse_whitespace(text: &str) -> Cow<'_, str> {
+    let normalized = text.trim() == text
+        && !text.contains("  ")
+        && !text.contains(|c: char| c.is_whitespace() && c != ' ');
+    if normalized {
+        Cow::Borrowed(text)
+    } else {
+        Cow::Owned(text.split_whitespace().collect::<Vec<_>>().join(" "))
+    }
+}
+
+/// A model paired with the weight the ensemble gives it
+#[derive(Clone, Debug)]
+pub struct WeightedModel {
+    pub model: SharedModel,
+    pub weight: f64,
+}
+
+impl WeightedModel {
+    /// `None` falls back to the model's own `weight()`
+    pub fn new(model: SharedModel, weight: Option<f64>) -> Self {
+        let weight = weight.unwrap_or_else(|| model.weight());
+        Self { model, weight }
+    }
+}
+
+impl From<SharedModel> for WeightedModel {
+    fn from(model: SharedModel) -> Self {
+        Self::new(model, None)
+    }
+}
+
+/// Models supplied by an embedder, added after the built-in ones when an
+/// ensemble is assembled for the TUI (see `App::new_with_models`)
+#[derive(Clone, Debug, Default)]
+pub struct ModelRegistry {
+    light_models: Vec<WeightedModel>,
+    heavy_models: Vec<WeightedModel>,
+}
+
+impl ModelRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a fast model queried on every refresh; `None` keeps its own `weight()`
+    pub fn with_light_model<M>(mut self, model: M, weight: Option<f64>) -> Self
+    where
+        M: SuggestModel + 'static,
+    {
+        self.light_models.push(WeightedModel::new(Arc::new(model), weight));
+        self
+    }
+
+    /// Register a slow model run in the background like the embedding and LLM models
+    pub fn with_heavy_model<M>(mut self, model: M, weight: Option<f64>) -> Self
+    where
+        M: SuggestModel + 'static,
+    {
+        self.heavy_models.push(WeightedModel::new(Arc::new(model), weight));
+        self
+    }
+}
+
 #[derive(Default)]
 pub struct EnsembleBuilder {
-    light_models: Vec<SharedModel>,
-    heavy_models: Vec<SharedModel>,
+    light_models: Vec<WeightedModel>,
+    heavy_models: Vec<WeightedModel>,
+    strategy: AggregationStrategy,
+    dedup: DedupMode,
 }
 
 impl EnsembleBuilder {
@@ -23,7 +148,17 @@ impl EnsembleBuilder {
     where
         M: SuggestModel + 'static,
     {
-        self.light_models.push(Arc::new(model));
+        self.light_models.push(WeightedModel::new(Arc::new(model), None));
+        self
+    }
+
+    /// Add a light model with an explicit weight instead of its `weight()`;
+    /// `None` keeps the model's own weight
+    pub fn with_light_model_weighted<M>(mut self, model: M, weight: Option<f64>) -> Self
+    where
+        M: SuggestModel + 'static,
+    {
+        self.light_models.push(WeightedModel::new(Arc::new(model), weight));
         self
     }
 
@@ -31,83 +166,401 @@ impl EnsembleBuilder {
     where
         M: SuggestModel + 'static,
     {
-        self.heavy_models.push(Arc::new(model));
+        self.heavy_models.push(WeightedModel::new(Arc::new(model), None));
+        self
+    }
+
+    /// Add a heavy model with an explicit weight instead of its `weight()`;
+    /// `None` keeps the model's own weight
+    pub fn with_heavy_model_weighted<M>(mut self, model: M, weight: Option<f64>) -> Self
+    where
+        M: SuggestModel + 'static,
+    {
+        self.heavy_models.push(WeightedModel::new(Arc::new(model), weight));
+        self
+    }
+
+    pub fn with_strategy(mut self, strategy: AggregationStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Add every model in `registry`, with the weights it was registered with
+    pub fn with_registry(mut self, registry: ModelRegistry) -> Self {
+        self.light_models.extend(registry.light_models);
+        self.heavy_models.extend(registry.heavy_models);
+        self
+    }
+
+    /// How suggestions from different models are matched up when merging;
+    /// defaults to exact text
+    pub fn with_dedup(mut self, dedup: DedupMode) -> Self {
+        self.dedup = dedup;
         self
     }
 
     pub fn build(self) -> Ensemble {
-        Ensemble::new(self.light_models, self.heavy_models)
+        let mut ensemble =
+            Ensemble::from_weighted(self.light_models, self.heavy_models).with_strategy(self.strategy);
+        ensemble.dedup = self.dedup;
+        ensemble
     }
 }
 
 pub struct Ensemble {
-    light_models: RwLock<Vec<SharedModel>>,
-    heavy_models: RwLock<Vec<SharedModel>>,
+    light_models: RwLock<Vec<WeightedModel>>,
+    heavy_models: RwLock<Vec<WeightedModel>>,
+    /// Names of models switched off with `set_model_enabled`
+    disabled: RwLock<BTreeSet<String>>,
+    strategy: AggregationStrategy,
+    dedup: DedupMode,
 }
 
 impl Ensemble {
+    /// Ensemble weighting each model by its own `weight()`
     pub fn new(light_models: Vec<SharedModel>, heavy_models: Vec<SharedModel>) -> Self {
+        Self::from_weighted(
+            light_models.into_iter().map(WeightedModel::from).collect(),
+            heavy_models.into_iter().map(WeightedModel::from).collect(),
+        )
+    }
+
+    pub fn from_weighted(light_models: Vec<WeightedModel>, heavy_models: Vec<WeightedModel>) -> Self {
         Self {
             light_models: RwLock::new(light_models),
             heavy_models: RwLock::new(heavy_models),
+            disabled: RwLock::new(BTreeSet::new()),
+            strategy: AggregationStrategy::default(),
+            dedup: DedupMode::default(),
         }
     }
 
+    pub fn with_strategy(mut self, strategy: AggregationStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
     /// Legacy method: predicts using all models (both light and heavy)
     /// This blocks on heavy models, so should be avoided in favor of predict_light_models()
     pub fn predict(&self, input: &str) -> Result<Vec<Suggestion>> {
-        let light = self.light_models.read().expect("ensemble lock poisoned");
-        let heavy = self.heavy_models.read().expect("ensemble lock poisoned");
-        let all_models = light.iter().chain(heavy.iter()).cloned();
-        Self::aggregate_predictions(all_models, input)
+        let mut all_models = self.enabled(&self.light_models);
+        all_models.extend(self.enabled(&self.heavy_models));
+        Self::aggregate_predictions(all_models, input, self.strategy, self.dedup)
     }
 
     /// Predict using only light (fast, synchronous) models
     /// Returns immediately without blocking on heavy models
     pub fn predict_light_models(&self, input: &str) -> Result<Vec<Suggestion>> {
-        let models = self.light_models.read().expect("ensemble lock poisoned");
-        Self::aggregate_predictions(models.iter().cloned(), input)
+        let models = self.enabled(&self.light_models);
+        Self::aggregate_predictions(models, input, self.strategy, self.dedup)
     }
 
-    /// Get clones of heavy models for async execution
-    pub fn get_heavy_models(&self) -> Vec<SharedModel> {
-        let models = self.heavy_models.read().expect("ensemble lock poisoned");
-        models.clone()
+    /// Switch every model called `name` on or off; disabled models are left
+    /// out of all predictions. Returns whether any model has that name. The
+    /// setting is kept either way, so it applies to a model added later.
+    pub fn set_model_enabled(&self, name: &str, enabled: bool) -> bool {
+        let mut disabled = self.disabled.write().expect("ensemble lock poisoned");
+        if enabled {
+            disabled.remove(name);
+        } else {
+            disabled.insert(name.to_string());
+        }
+        drop(disabled);
+        self.model_states().iter().any(|(model, _)| model == name)
     }
 
-    fn aggregate_predictions<I>(models: I, input: &str) -> Result<Vec<Suggestion>>
-    where
-        I: IntoIterator<Item = SharedModel>,
-    {
-        let mut score_map: HashMap<String, (f64, Option<String>)> = HashMap::new();
-
-        for model in models {
-            let suggestions = model.predict(input)?;
-            let weight = model.weight();
-
-            for suggestion in suggestions {
-                let entry = score_map
-                    .entry(suggestion.text.clone())
-                    .or_insert((0.0, None));
-                entry.0 += suggestion.score * weight;
-                if entry.1.is_none() {
-                    entry.1 = suggestion.source.clone();
-                }
+    /// Name of each model, light ones first, and whether it is enabled;
+    /// models sharing a name are listed once
+    pub fn model_states(&self) -> Vec<(String, bool)> {
+        let light = self.light_models.read().expect("ensemble lock poisoned");
+        let heavy = self.heavy_models.read().expect("ensemble lock poisoned");
+        let disabled = self.disabled.read().expect("ensemble lock poisoned");
+        let mut states: Vec<(String, bool)> = Vec::new();
+        for entry in light.iter().chain(heavy.iter()) {
+            let name = entry.model.name();
+            if !states.iter().any(|(seen, _)| seen == name) {
+                states.push((name.to_string(), !disabled.contains(name)));
             }
         }
+        states
+    }
 
-        let mut ranked: Vec<Suggestion> = score_map
+    /// Names passed to `set_model_enabled(name, false)`, sorted
+    pub fn disabled_models(&self) -> Vec<String> {
+        let disabled = self.disabled.read().expect("ensemble lock poisoned");
+        disabled.iter().cloned().collect()
+    }
+
+    /// The models in `models` that are not disabled
+    fn enabled(&self, models: &RwLock<Vec<WeightedModel>>) -> Vec<WeightedModel> {
+        let models = models.read().expect("ensemble lock poisoned");
+        let disabled = self.disabled.read().expect("ensemble lock poisoned");
+        models
+            .iter()
+            .filter(|entry| !disabled.contains(entry.model.name()))
+            .cloned()
+            .collect()
+    }
+
+    /// Async counterpart of `predict`: every model runs through its
+    /// `predict_async` as its own tokio task, so no worker thread blocks on
+    /// SQLite or a subprocess. Must be called from within a tokio runtime.
+    pub async fn predict_async(&self, input: &str) -> Result<Vec<Suggestion>> {
+        // Clone the model lists up front; the lock guards cannot be held across an await
+        let mut models = self.enabled(&self.light_models);
+        models.extend(self.enabled(&self.heavy_models));
+
+        let timed = log_enabled!(Level::Debug);
+        let tasks: Vec<_> = models
             .into_iter()
-            .map(|(text, (score, source))| Suggestion {
-                text,
-                score,
-                source,
+            .map(|entry| {
+                let prediction = entry.model.clone().predict_async(input.to_string());
+                let task = tokio::spawn(async move {
+                    let started = timed.then(Instant::now);
+                    let result = prediction.await;
+                    (result, started.map(|t| t.elapsed()))
+                });
+                (entry, task)
             })
             .collect();
 
+        let mut results = Vec::with_capacity(tasks.len());
+        let mut timings = Vec::new();
+        for (entry, task) in tasks {
+            let name = entry.model.name();
+            let (result, elapsed) = task
+                .await
+                .unwrap_or_else(|err| (Err(anyhow!("predict task failed: {err}")), None));
+            if let Some(elapsed) = elapsed {
+                timings.push(ModelTiming::new(name, elapsed));
+            }
+            let result = result.with_context(|| format!("{name} model prediction failed"));
+            results.push((entry.weight, result));
+        }
+        log_timings(input, &timings);
+        Self::combine(results, self.strategy, self.dedup)
+    }
+
+    /// How this ensemble matches up suggestion texts, for callers merging
+    /// further results into its output
+    pub fn dedup(&self) -> DedupMode {
+        self.dedup
+    }
+
+    /// Get clones of the enabled heavy models (with their weights) for async execution
+    pub fn get_heavy_models(&self) -> Vec<WeightedModel> {
+        self.enabled(&self.heavy_models)
+    }
+
+    /// Query `models` concurrently and merge their suggestions into one
+    /// ranking, as `predict` does over its own model lists
+    pub fn aggregate_predictions<I>(
+        models: I,
+        input: &str,
+        strategy: AggregationStrategy,
+        dedup: DedupMode,
+    ) -> Result<Vec<Suggestion>>
+    where
+        I: IntoIterator<Item = WeightedModel>,
+    {
+        Self::combine(Self::predict_concurrently(models, input), strategy, dedup)
+    }
+
+    /// Merge per-model `(weight, result)` pairs, in model order, into one ranking.
+    /// A failing model is logged and skipped so the others still produce
+    /// suggestions; an error is returned only when every model failed.
+    fn combine(
+        results: Vec<(f64, Result<Vec<Suggestion>>)>,
+        strategy: AggregationStrategy,
+        dedup: DedupMode,
+    ) -> Result<Vec<Suggestion>> {
+        let mut index: HashMap<String, usize> = HashMap::new();
+        let mut ranked: Vec<Suggestion> = Vec::new();
+        let mut succeeded = results.is_empty();
+        let mut first_error = None;
+
+        for (weight, suggestions) in results {
+            let mut suggestions = match suggestions {
+                Ok(suggestions) => {
+                    succeeded = true;
+                    suggestions
+                }
+                // A store that was never created has nothing to suggest yet
+                Err(err) if ModelError::is_missing_table(&err) => {
+                    debug!("{err:#}");
+                    succeeded = true;
+                    continue;
+                }
+                Err(err) => {
+                    warn!("{err:#}");
+                    first_error.get_or_insert(err);
+                    continue;
+                }
+            };
+
+            match strategy {
+                AggregationStrategy::WeightedSum => normalize_scores(&mut suggestions),
+                AggregationStrategy::ReciprocalRankFusion { .. } => suggestions
+                    .sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal)),
+            }
+
+            for (rank, suggestion) in suggestions.into_iter().enumerate() {
+                let contribution = match strategy {
+                    AggregationStrategy::WeightedSum => suggestion.score * weight,
+                    AggregationStrategy::ReciprocalRankFusion { k } => {
+                        weight / (k + (rank + 1) as f64)
+                    }
+                };
+                let key = dedup.key(&suggestion.text).into_owned();
+                match index.get(&key) {
+                    Some(&idx) => {
+                        let entry = &mut ranked[idx];
+                        entry.score += contribution;
+                        if entry.source.is_none() {
+                            entry.source = suggestion.source;
+                        }
+                        if entry.match_indices.is_empty() && entry.text == suggestion.text {
+                            entry.match_indices = suggestion.match_indices;
+                        }
+                    }
+                    // The first suggestion seen keeps its text as typed
+                    None => {
+                        index.insert(key, ranked.len());
+                        ranked.push(Suggestion {
+                            score: contribution,
+                            ..suggestion
+                        });
+                    }
+                }
+            }
+        }
+
+        // Stable sort: ties keep first-seen order (model order, then rank)
+        // so identical inputs always produce identical rankings
         ranked.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal));
 
-        Ok(ranked)
+        match first_error {
+            Some(err) if !succeeded => Err(err),
+            _ => Ok(ranked),
+        }
+    }
+
+    /// Run every model's `predict` on its own scoped thread.
+    /// Results come back in model order regardless of completion order.
+    fn predict_concurrently<I>(models: I, input: &str) -> Vec<(f64, Result<Vec<Suggestion>>)>
+    where
+        I: IntoIterator<Item = WeightedModel>,
+    {
+        let models: Vec<WeightedModel> = models.into_iter().collect();
+        let timed = log_enabled!(Level::Debug);
+        let outcomes: Vec<TimedOutcome> = if models.len() <= 1 {
+            models
+                .iter()
+                .map(|entry| {
+                    let (result, timing) = timed_predict(entry.model.as_ref(), input, timed);
+                    (entry.weight, result, timing)
+                })
+                .collect()
+        } else {
+            thread::scope(|scope| {
+                let handles: Vec<_> = models
+                    .iter()
+                    .map(|entry| {
+                        scope.spawn(move || timed_predict(entry.model.as_ref(), input, timed))
+                    })
+                    .collect();
+                models
+                    .iter()
+                    .zip(handles)
+                    .map(|(entry, handle)| {
+                        let (result, timing) = handle.join().unwrap_or_else(|_| {
+                            let err = anyhow!("{} model panicked during predict", entry.model.name());
+                            (Err(err), None)
+                        });
+                        (entry.weight, result, timing)
+                    })
+                    .collect()
+            })
+        };
+
+        let timings: Vec<ModelTiming> = outcomes.iter().filter_map(|(_, _, t)| t.clone()).collect();
+        log_timings(input, &timings);
+        outcomes
+            .into_iter()
+            .map(|(weight, result, _)| (weight, result))
+            .collect()
+    }
+}
+
+/// A model's weight, its prediction, and its latency when timing is enabled
+type TimedOutcome = (f64, Result<Vec<Suggestion>>, Option<ModelTiming>);
+
+/// How long one model took to answer one query
+#[derive(Clone, Debug, PartialEq)]
+struct ModelTiming {
+    model: String,
+    elapsed: Duration,
+}
+
+impl ModelTiming {
+    fn new(model: &str, elapsed: Duration) -> Self {
+        Self {
+            model: model.to_string(),
+            elapsed,
+        }
+    }
+}
+
+/// `predict` with the model's name attached to errors. The clock is only
+/// read when `timed`, so disabled instrumentation costs nothing.
+fn timed_predict(
+    model: &dyn SuggestModel,
+    input: &str,
+    timed: bool,
+) -> (Result<Vec<Suggestion>>, Option<ModelTiming>) {
+    let started = timed.then(Instant::now);
+    let result = model
+        .predict(input)
+        .with_context(|| format!("{} model prediction failed", model.name()));
+    let timing = started.map(|t| ModelTiming::new(model.name(), t.elapsed()));
+    (result, timing)
+}
+
+/// One debug line per query, e.g. `model timings for "git": history=1.2ms embedding=310ms`
+fn log_timings(input: &str, timings: &[ModelTiming]) {
+    if !timings.is_empty() {
+        debug!("model timings for {input:?}: {}", format_timings(timings));
+    }
+}
+
+fn format_timings(timings: &[ModelTiming]) -> String {
+    timings
+        .iter()
+        .map(|t| format!("{}={:.1?}", t.model, t.elapsed))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Min-max normalize scores in place to 0..1 so model weights are comparable.
+/// When every score is equal (including a single suggestion) they all map to 1.0.
+pub fn normalize_scores(suggestions: &mut [Suggestion]) {
+    let (min, max) = suggestions
+        .iter()
+        .map(|s| s.score)
+        .filter(|score| score.is_finite())
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), score| {
+            (lo.min(score), hi.max(score))
+        });
+    let range = max - min;
+
+    for suggestion in suggestions.iter_mut() {
+        suggestion.score = if !suggestion.score.is_finite() {
+            0.0
+        } else if range > f64::EPSILON {
+            (suggestion.score - min) / range
+        } else {
+            1.0
+        };
     }
 }
 
@@ -140,6 +593,19 @@ mod tests {
         }
     }
 
+    #[test]
+    fn explicit_weight_replaces_model_weight() {
+        let model = || StaticModel::new(0.5, vec![Suggestion::with_source("ls", 1.0, "freq")]);
+        let ensemble = EnsembleBuilder::new()
+            .with_light_model_weighted(model(), Some(2.0))
+            .with_light_model_weighted(model(), None)
+            .build();
+
+        let suggestions = ensemble.predict_light_models("l").unwrap();
+        assert_eq!(suggestions.len(), 1);
+        assert!((suggestions[0].score - 2.5).abs() < 1e-9);
+    }
+
     #[test]
     fn aggregates_scores_across_models() {
         let first = Arc::new(StaticModel::new(
@@ -158,7 +624,8 @@ mod tests {
 
         assert_eq!(result.len(), 1);
         assert_eq!(result[0].text, "git status");
-        assert!((result[0].score - 4.0).abs() < f64::EPSILON);
+        // Each single-suggestion list normalizes to 1.0, then 1.0 * 1.0 + 1.0 * 0.5
+        assert!((result[0].score - 1.5).abs() < f64::EPSILON);
     }
 
     #[test]
@@ -178,4 +645,286 @@ mod tests {
 
         assert_eq!(result.len(), 2);
     }
+
+    #[test]
+    fn normalization_keeps_large_counts_from_dominating() {
+        let freq = Arc::new(StaticModel::new(
+            0.5,
+            vec![
+                Suggestion::with_source("git status", 200.0, "freq"),
+                Suggestion::with_source("git log", 150.0, "freq"),
+            ],
+        )) as SharedModel;
+        let embedding = Arc::new(StaticModel::new(
+            0.6,
+            vec![
+                Suggestion::with_source("git stash", 0.9, "embedding"),
+                Suggestion::with_source("git stage", 0.3, "embedding"),
+            ],
+        )) as SharedModel;
+
+        let ensemble = Ensemble::new(vec![freq, embedding], vec![]);
+        let result = ensemble.predict_light_models("git").unwrap();
+
+        assert_eq!(result[0].text, "git stash");
+        assert!((result[0].score - 0.6).abs() < 1e-12);
+        assert_eq!(result[1].text, "git status");
+        assert!((result[1].score - 0.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn normalize_scores_handles_equal_scores() {
+        let mut suggestions = vec![
+            Suggestion::with_source("a", 3.0, "x"),
+            Suggestion::with_source("b", 3.0, "x"),
+        ];
+        normalize_scores(&mut suggestions);
+        assert!(suggestions.iter().all(|s| s.score == 1.0));
+    }
+
+    #[test]
+    fn concurrent_merge_is_deterministic() {
+        let models: Vec<SharedModel> = (0..4)
+            .map(|i| {
+                Arc::new(StaticModel::new(
+                    1.0,
+                    vec![
+                        Suggestion::with_source(format!("cmd {i}"), 1.0, "static"),
+                        Suggestion::with_source("shared", 1.0, "static"),
+                    ],
+                )) as SharedModel
+            })
+            .collect();
+        let ensemble = Ensemble::new(models, vec![]);
+
+        let first = ensemble.predict_light_models("c").unwrap();
+        for _ in 0..20 {
+            assert_eq!(ensemble.predict_light_models("c").unwrap(), first);
+        }
+        assert_eq!(first[0].text, "shared");
+        assert_eq!(first[1].text, "cmd 0");
+        assert_eq!(first[4].text, "cmd 3");
+    }
+
+    #[tokio::test]
+    async fn async_predict_matches_sync_predict() {
+        let light = Arc::new(StaticModel::new(
+            1.0,
+            vec![
+                Suggestion::with_source("git status", 2.0, "freq"),
+                Suggestion::with_source("git stash", 1.0, "freq"),
+            ],
+        )) as SharedModel;
+        let heavy = Arc::new(StaticModel::new(
+            0.5,
+            vec![Suggestion::with_source("git stash", 4.0, "llm")],
+        )) as SharedModel;
+        let ensemble = Ensemble::new(vec![light], vec![heavy]);
+
+        let expected = ensemble.predict("git").unwrap();
+        assert_eq!(ensemble.predict_async("git").await.unwrap(), expected);
+    }
+
+    #[test]
+    fn reciprocal_rank_fusion_ignores_score_scale() {
+        let counts = Arc::new(StaticModel::new(
+            1.0,
+            vec![
+                Suggestion::with_source("git status", 200.0, "freq"),
+                Suggestion::with_source("git commit", 150.0, "freq"),
+            ],
+        )) as SharedModel;
+        let cosine = Arc::new(StaticModel::new(
+            1.0,
+            vec![
+                Suggestion::with_source("git commit", 0.9, "embedding"),
+                Suggestion::with_source("git stash", 0.8, "embedding"),
+            ],
+        )) as SharedModel;
+
+        let ensemble = EnsembleBuilder::new()
+            .with_strategy(AggregationStrategy::ReciprocalRankFusion { k: DEFAULT_RRF_K })
+            .build();
+        ensemble
+            .light_models
+            .write()
+            .unwrap()
+            .extend([counts, cosine].map(WeightedModel::from));
+
+        let result = ensemble.predict_light_models("git").unwrap();
+
+        assert_eq!(result[0].text, "git commit");
+        let expected = 1.0 / 62.0 + 1.0 / 61.0;
+        assert!((result[0].score - expected).abs() < 1e-12);
+        assert_eq!(result.len(), 3);
+    }
+
+    #[test]
+    fn missing_tables_count_as_no_suggestions() {
+        let missing = || -> Result<Vec<Suggestion>> {
+            Err(anyhow::Error::new(ModelError::from(libsql::Error::SqliteFailure(
+                1,
+                "no such table: history".to_string(),
+            )))
+            .context("freq model prediction failed"))
+        };
+        let found = vec![Suggestion::with_source("ls", 1.0, "history")];
+        let strategy = AggregationStrategy::default();
+
+        assert!(Ensemble::combine(vec![(1.0, missing())], strategy, DedupMode::Exact)
+            .unwrap()
+            .is_empty());
+        let result =
+            Ensemble::combine(vec![(1.0, missing()), (1.0, Ok(found))], strategy, DedupMode::Exact)
+                .unwrap();
+        assert_eq!(result[0].text, "ls");
+    }
+
+    #[test]
+    fn registry_models_keep_their_tier_and_weight() {
+        let registry = ModelRegistry::new()
+            .with_light_model(StaticModel::new(1.0, Vec::new()), Some(3.0))
+            .with_heavy_model(StaticModel::new(0.5, Vec::new()), None);
+        let ensemble = EnsembleBuilder::new()
+            .with_light_model(StaticModel::new(1.0, Vec::new()))
+            .with_registry(registry)
+            .build();
+
+        let light = ensemble.light_models.read().unwrap();
+        assert_eq!(light.iter().map(|m| m.weight).collect::<Vec<_>>(), vec![1.0, 3.0]);
+        let heavy = ensemble.get_heavy_models();
+        assert_eq!(heavy.len(), 1);
+        assert_eq!(heavy[0].weight, 0.5);
+    }
+
+    #[test]
+    fn dedup_key_normalizes_whitespace_and_case() {
+        assert_eq!(DedupMode::Exact.key(" git  status"), " git  status");
+        assert_eq!(DedupMode::NormalizedWhitespace.key(" git \t status "), "git status");
+        assert!(matches!(DedupMode::NormalizedWhitespace.key("git status"), Cow::Borrowed(_)));
+        assert_eq!(DedupMode::IgnoreCase.key("LS  -la"), "ls -la");
+        assert_eq!("whitespace".parse::<DedupMode>().unwrap(), DedupMode::NormalizedWhitespace);
+        assert!("fuzzy".parse::<DedupMode>().is_err());
+    }
+
+    #[test]
+    fn normalized_dedup_merges_whitespace_variants() {
+        let spaced = || StaticModel::new(1.0, vec![Suggestion::with_source("git  status ", 1.0, "history")]);
+        let plain = || StaticModel::new(1.0, vec![Suggestion::with_source("git status", 1.0, "freq")]);
+
+        let exact = EnsembleBuilder::new()
+            .with_light_model(spaced())
+            .with_light_model(plain())
+            .build();
+        assert_eq!(exact.predict_light_models("git").unwrap().len(), 2);
+
+        let normalized = EnsembleBuilder::new()
+            .with_dedup(DedupMode::NormalizedWhitespace)
+            .with_light_model(spaced())
+            .with_light_model(plain())
+            .build();
+        let result = normalized.predict_light_models("git").unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].text, "git  status ");
+        assert!((result[0].score - 2.0).abs() < 1e-12);
+    }
+
+    #[derive(Debug)]
+    struct FailingModel;
+
+    impl SuggestModel for FailingModel {
+        fn predict(&self, _input: &str) -> Result<Vec<Suggestion>> {
+            Err(anyhow!("database is locked"))
+        }
+
+        fn name(&self) -> &str {
+            "failing"
+        }
+    }
+
+    #[test]
+    fn prediction_errors_name_the_model() {
+        let ensemble = EnsembleBuilder::new()
+            .with_light_model(FailingModel)
+            .with_light_model(FailingModel)
+            .build();
+        let err = ensemble.predict_light_models("git").unwrap_err();
+        assert_eq!(
+            format!("{err:#}"),
+            "failing model prediction failed: database is locked"
+        );
+    }
+
+    #[test]
+    fn failing_model_does_not_hide_the_others() {
+        let ensemble = EnsembleBuilder::new()
+            .with_light_model(FailingModel)
+            .with_light_model(StaticModel::new(
+                1.0,
+                vec![Suggestion::with_source("git status", 1.0, "freq")],
+            ))
+            .build();
+        let texts: Vec<String> = ensemble
+            .predict_light_models("git")
+            .unwrap()
+            .into_iter()
+            .map(|s| s.text)
+            .collect();
+        assert_eq!(texts, vec!["git status"]);
+    }
+
+    #[derive(Debug)]
+    struct NamedModel(&'static str);
+
+    impl SuggestModel for NamedModel {
+        fn predict(&self, _input: &str) -> Result<Vec<Suggestion>> {
+            Ok(vec![Suggestion::with_source(format!("{} suggestion", self.0), 1.0, self.0)])
+        }
+
+        fn name(&self) -> &str {
+            self.0
+        }
+    }
+
+    #[test]
+    fn disabled_models_are_left_out_until_enabled() {
+        let ensemble = EnsembleBuilder::new()
+            .with_light_model(NamedModel("alias"))
+            .with_light_model(NamedModel("prefix"))
+            .with_heavy_model(NamedModel("llm"))
+            .build();
+        let texts = |suggestions: Vec<Suggestion>| -> Vec<String> {
+            suggestions.into_iter().map(|s| s.text).collect()
+        };
+
+        assert!(ensemble.set_model_enabled("alias", false));
+        assert!(ensemble.set_model_enabled("llm", false));
+        assert!(!ensemble.set_model_enabled("embedding", false), "unknown names are kept");
+        assert_eq!(texts(ensemble.predict("g").unwrap()), vec!["prefix suggestion"]);
+        assert!(ensemble.get_heavy_models().is_empty());
+        assert_eq!(
+            ensemble.model_states(),
+            vec![
+                ("alias".to_string(), false),
+                ("prefix".to_string(), true),
+                ("llm".to_string(), false)
+            ]
+        );
+        assert_eq!(ensemble.disabled_models(), vec!["alias", "embedding", "llm"]);
+
+        ensemble.set_model_enabled("alias", true);
+        assert_eq!(
+            texts(ensemble.predict_light_models("g").unwrap()),
+            vec!["alias suggestion", "prefix suggestion"]
+        );
+    }
+
+    #[test]
+    fn formats_timings_per_model() {
+        let timings = [
+            ModelTiming::new("history", Duration::from_micros(1200)),
+            ModelTiming::new("embedding", Duration::from_millis(310)),
+        ];
+        assert_eq!(format_timings(&timings), "history=1.2ms embedding=310.0ms");
+    }
 }
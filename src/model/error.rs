@@ -0,0 +1,69 @@
+use std::fmt;
+
+/// A failed libsql call, sorted into the cases the models handle differently:
+/// a store that has no tables yet yields no suggestions, a build without
+/// libsql's vector search falls back to scanning, and anything else is a
+/// real failure.
+///
+/// `SqlitePool` attaches this to its errors, so callers holding an
+/// `anyhow::Error` get it back with [`ModelError::of`].
+#[derive(Debug)]
+pub enum ModelError {
+    /// The statement names a table that was never created, e.g. a store
+    /// opened without running migrations
+    MissingTable(libsql::Error),
+    /// This SQLite build lacks libsql's vector functions or index
+    VectorUnsupported(libsql::Error),
+    /// `ALTER TABLE ... ADD COLUMN` on a column the table already has
+    DuplicateColumn(libsql::Error),
+    Query(libsql::Error),
+}
+
+impl ModelError {
+    /// The `ModelError` behind `err`, looking through any context added on the way up
+    pub fn of(err: &anyhow::Error) -> Option<&Self> {
+        err.downcast_ref()
+    }
+
+    pub fn is_missing_table(err: &anyhow::Error) -> bool {
+        matches!(Self::of(err), Some(Self::MissingTable(_)))
+    }
+
+    pub fn is_vector_unsupported(err: &anyhow::Error) -> bool {
+        matches!(Self::of(err), Some(Self::VectorUnsupported(_)))
+    }
+}
+
+impl From<libsql::Error> for ModelError {
+    /// SQLite reports all of these as the generic `SQLITE_ERROR` code, so the
+    /// message is the only thing to go on; keep the matching in this one place
+    fn from(err: libsql::Error) -> Self {
+        let message = err.to_string();
+        if message.contains("no such function: vector")
+            || message.contains("no such function: libsql_vector")
+            || message.contains("no such table: vector_top_k")
+            || message.contains("vector index")
+        {
+            Self::VectorUnsupported(err)
+        } else if message.contains("no such table") {
+            Self::MissingTable(err)
+        } else if message.contains("duplicate column name") {
+            Self::DuplicateColumn(err)
+        } else {
+            Self::Query(err)
+        }
+    }
+}
+
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingTable(err)
+            | Self::VectorUnsupported(err)
+            | Self::DuplicateColumn(err)
+            | Self::Query(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for ModelError {}
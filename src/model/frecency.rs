@@ -0,0 +1,151 @@
+use std::cmp::Ordering;
+
+use anyhow::Result;
+use libsql::Value;
+
+use super::sqlite::{fts_prefix_query, SqlitePool};
+use super::{ModelError, SuggestModel, Suggestion};
+
+/// Candidates fetched by count before re-ranking with the recency weight
+const CANDIDATES: i64 = 100;
+const MAX_RESULTS: usize = 20;
+
+/// Recency multipliers by time since the command last ran, newest first;
+/// anything older than the last bucket gets [`STALE_WEIGHT`]
+const RECENCY_BUCKETS: &[(i64, f64)] = &[
+    (60 * 60, 4.0),
+    (24 * 60 * 60, 2.0),
+    (7 * 24 * 60 * 60, 1.0),
+];
+const STALE_WEIGHT: f64 = 0.25;
+
+/// Ranks history by `count * recency_weight(last_used)` like zoxide or
+/// Firefox's frecency, so a command run a few times this hour beats one run
+/// often months ago. The last-used time is `history.last_used`, set only by
+/// actual runs; commands only seen in shell history files count as stale.
+#[derive(Clone, Debug)]
+pub struct FrecencyModel {
+    pool: SqlitePool,
+}
+
+impl FrecencyModel {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+fn recency_weight(age_seconds: i64) -> f64 {
+    RECENCY_BUCKETS
+        .iter()
+        .find(|(max_age, _)| age_seconds < *max_age)
+        .map_or(STALE_WEIGHT, |(_, weight)| *weight)
+}
+
+impl SuggestModel for FrecencyModel {
+    fn predict(&self, input: &str) -> Result<Vec<Suggestion>> {
+        if input.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let sql = r#"
+            SELECT h.command, h.count,
+                   CAST(strftime('%s', 'now') AS INTEGER) - CAST(strftime('%s', h.last_used) AS INTEGER)
+            FROM history_fts f
+            JOIN history h ON f.rowid = h.id
+            WHERE f.command MATCH ?1
+            ORDER BY h.count DESC
+            LIMIT ?2
+        "#;
+
+        let rows = self.pool.query_collect(
+            sql,
+            vec![
+                Value::Text(fts_prefix_query(input)),
+                Value::Integer(CANDIDATES),
+            ],
+            |row| {
+                let command: String = row.get(0)?;
+                let count: i64 = row.get(1)?;
+                // Rows without a timestamp count as stale
+                let age: i64 = row.get(2).unwrap_or(i64::MAX);
+                let score = count as f64 * recency_weight(age);
+                Ok(Suggestion::with_source(command, score, "frecency"))
+            },
+        );
+        match rows {
+            Ok(mut rows) => {
+                rows.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal));
+                rows.truncate(MAX_RESULTS);
+                Ok(rows)
+            }
+            Err(err) if ModelError::is_missing_table(&err) => Ok(Vec::new()),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn weight(&self) -> f64 {
+        0.5
+    }
+
+    fn name(&self) -> &str {
+        "frecency"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recency_buckets_scale_the_count() {
+        assert_eq!(recency_weight(0), 4.0);
+        assert_eq!(recency_weight(3 * 60 * 60), 2.0);
+        assert_eq!(recency_weight(3 * 24 * 60 * 60), 1.0);
+        assert_eq!(recency_weight(30 * 24 * 60 * 60), STALE_WEIGHT);
+    }
+
+    #[test]
+    fn recent_runs_outrank_old_frequent_ones() {
+        let pool = SqlitePool::open_memory_migrated().unwrap();
+        let entries = [
+            ("git status", 40_i64, "-90 days"),
+            ("git stash pop", 6, "-10 minutes"),
+            ("git switch main", 10, "-5 hours"),
+            ("git show", 3, "-3 days"),
+        ];
+        for (command, count, age) in entries {
+            pool.execute(
+                "INSERT INTO history (command, hash, count, last_used)
+                 VALUES (?1, ?1, ?2, datetime('now', ?3));",
+                vec![
+                    Value::Text(command.to_string()),
+                    Value::Integer(count),
+                    Value::Text(age.to_string()),
+                ],
+            )
+            .unwrap();
+        }
+
+        let suggestions = FrecencyModel::new(pool).predict("git s").unwrap();
+        let ranked: Vec<(&str, f64)> = suggestions
+            .iter()
+            .map(|s| (s.text.as_str(), s.score))
+            .collect();
+        assert_eq!(
+            ranked,
+            vec![
+                ("git stash pop", 24.0),
+                ("git switch main", 20.0),
+                ("git status", 10.0),
+                ("git show", 3.0),
+            ]
+        );
+        assert_eq!(suggestions[0].source.as_deref(), Some("frecency"));
+    }
+
+    #[test]
+    fn missing_history_yields_no_suggestions() {
+        let pool = SqlitePool::open_memory().unwrap();
+        assert!(FrecencyModel::new(pool).predict("git").unwrap().is_empty());
+    }
+}
@@ -1,43 +1,136 @@
+use std::cmp::Ordering;
+use std::time::{SystemTime, UNIX_EPOCH};
+
 use anyhow::Result;
 use libsql::Value;
 
-use super::{sqlite::SqlitePool, SuggestModel, Suggestion};
+use super::sqlite::{fts_prefix_query, SqlitePool};
+use super::{ModelError, SuggestModel, Suggestion};
+
+const SECONDS_PER_DAY: f64 = 86_400.0;
+/// Candidates fetched before re-ranking by decayed score
+const DECAY_CANDIDATES: usize = 100;
+const MAX_RESULTS: usize = 20;
+/// Score multiplier for commands whose most recent run exited non-zero
+const FAILED_EXIT_PENALTY: f64 = 0.25;
+/// Commands with no recorded run (only seen in imported shell history) decay
+/// as if last run this many half-lives ago
+const UNKNOWN_AGE_HALF_LIVES: f64 = 2.0;
 
 #[derive(Clone, Debug)]
 pub struct FreqModel {
     pool: SqlitePool,
+    /// Exponential decay rate per day; `None` keeps pure count ordering
+    decay_lambda: Option<f64>,
 }
 
 impl FreqModel {
     pub fn new(pool: SqlitePool) -> Self {
-        Self { pool }
+        Self {
+            pool,
+            decay_lambda: None,
+        }
+    }
+
+    /// Weight each count by `exp(-lambda * age_days)` where a command's score
+    /// halves every `days` days since it was last run. The last run is
+    /// `history.last_used`, not the row's insert time, so importing old shell
+    /// history does not make it look fresh.
+    pub fn with_half_life(mut self, days: f64) -> Self {
+        self.decay_lambda = (days > 0.0).then(|| std::f64::consts::LN_2 / days);
+        self
+    }
+
+    fn predict_decayed(&self, input: &str, lambda: f64) -> Result<Vec<Suggestion>> {
+        let sql = r#"
+            SELECT h.command, h.count, CAST(strftime('%s', h.last_used) AS INTEGER), h.last_exit_code
+            FROM history_fts f
+            JOIN history h ON f.rowid = h.id
+            WHERE f.command MATCH ?
+            ORDER BY h.count DESC
+            LIMIT ?
+        "#;
+        let unknown_age_days = UNKNOWN_AGE_HALF_LIVES * std::f64::consts::LN_2 / lambda;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs_f64())
+            .unwrap_or(0.0);
+
+        let mut rows = self.pool.query_collect(
+            sql,
+            vec![
+                Value::Text(fts_prefix_query(input)),
+                Value::Integer(DECAY_CANDIDATES as i64),
+            ],
+            |row| {
+                let command: String = row.get(0)?;
+                let count: i64 = row.get(1)?;
+                let last_used: Option<i64> = row.get(2).ok();
+                let age_days = last_used
+                    .map(|ts| ((now - ts as f64) / SECONDS_PER_DAY).max(0.0))
+                    .unwrap_or(unknown_age_days);
+                let last_exit_code: Option<i64> = row.get(3)?;
+                let score =
+                    decayed_score(count as f64, age_days, lambda) * exit_code_factor(last_exit_code);
+                Ok(Suggestion::with_source(command, score, "freq"))
+            },
+        )?;
+
+        rows.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal));
+        rows.truncate(MAX_RESULTS);
+        Ok(rows)
+    }
+}
+
+/// Demote commands that failed the last time they ran; unknown status counts as success
+fn exit_code_factor(last_exit_code: Option<i64>) -> f64 {
+    match last_exit_code {
+        Some(code) if code != 0 => FAILED_EXIT_PENALTY,
+        _ => 1.0,
     }
 }
 
+/// `count * exp(-lambda * age_days)`
+fn decayed_score(count: f64, age_days: f64, lambda: f64) -> f64 {
+    count * (-lambda * age_days).exp()
+}
+
 impl SuggestModel for FreqModel {
     fn predict(&self, input: &str) -> Result<Vec<Suggestion>> {
         if input.trim().is_empty() {
             return Ok(Vec::new());
         }
 
+        if let Some(lambda) = self.decay_lambda {
+            return match self.predict_decayed(input, lambda) {
+                Err(err) if ModelError::is_missing_table(&err) => Ok(Vec::new()),
+                other => other,
+            };
+        }
+
         let sql = r#"
-            SELECT h.command, h.count
+            SELECT h.command, h.count, h.last_exit_code
             FROM history_fts f
             JOIN history h ON f.rowid = h.id
-            WHERE f.command MATCH ? || '*'
+            WHERE f.command MATCH ?
             ORDER BY h.count DESC
             LIMIT 20
         "#;
 
         match self
             .pool
-            .query_collect(sql, vec![Value::Text(input.to_string())], |row| {
+            .query_collect(sql, vec![Value::Text(fts_prefix_query(input))], |row| {
                 let command: String = row.get(0)?;
                 let count: i64 = row.get(1)?;
-                Ok(Suggestion::with_source(command, count as f64, "freq"))
+                let last_exit_code: Option<i64> = row.get(2)?;
+                let score = count as f64 * exit_code_factor(last_exit_code);
+                Ok(Suggestion::with_source(command, score, "freq"))
             }) {
-            Ok(rows) => Ok(rows),
-            Err(err) if err.to_string().contains("no such table") => Ok(Vec::new()),
+            Ok(mut rows) => {
+                rows.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal));
+                Ok(rows)
+            }
+            Err(err) if ModelError::is_missing_table(&err) => Ok(Vec::new()),
             Err(err) => Err(err),
         }
     }
@@ -45,6 +138,10 @@ impl SuggestModel for FreqModel {
     fn weight(&self) -> f64 {
         0.5
     }
+
+    fn name(&self) -> &str {
+        "freq"
+    }
 }
 
 #[cfg(test)]
@@ -55,7 +152,7 @@ mod tests {
     fn returns_ranked_matches_from_fts() {
         let pool = SqlitePool::open_memory().unwrap();
         pool.execute(
-            "CREATE TABLE history (id INTEGER PRIMARY KEY, command TEXT, count INTEGER);",
+            "CREATE TABLE history (id INTEGER PRIMARY KEY, command TEXT, count INTEGER, last_exit_code INTEGER);",
             std::iter::empty::<Value>(),
         )
         .unwrap();
@@ -95,4 +192,126 @@ mod tests {
         assert_eq!(suggestions[1].text, "git commit");
         assert_eq!(suggestions[1].score, 5.0);
     }
+
+    #[test]
+    fn fts_operators_in_input_are_matched_literally() {
+        let pool = SqlitePool::open_memory_migrated().unwrap();
+        for command in ["git commit -m \"fix\"", "git status"] {
+            pool.execute(
+                "INSERT INTO history (command, hash, count) VALUES (?1, ?1, 1);",
+                vec![Value::Text(command.to_string())],
+            )
+            .unwrap();
+        }
+
+        for input in ["git \"commit", "git commit -m", "git: st*", "\"", "-"] {
+            let model = FreqModel::new(pool.clone());
+            assert!(model.predict(input).is_ok(), "{input}");
+            assert!(model.with_half_life(7.0).predict(input).is_ok(), "{input}");
+        }
+        let suggestions = FreqModel::new(pool).predict("git \"commit").unwrap();
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].text, "git commit -m \"fix\"");
+    }
+
+    #[test]
+    fn decayed_score_halves_after_half_life() {
+        let lambda = std::f64::consts::LN_2 / 7.0;
+        assert!((decayed_score(10.0, 0.0, lambda) - 10.0).abs() < 1e-9);
+        assert!((decayed_score(10.0, 7.0, lambda) - 5.0).abs() < 1e-9);
+        assert!((decayed_score(200.0, 365.0, lambda)) < decayed_score(10.0, 0.0, lambda));
+    }
+
+    #[test]
+    fn half_life_ranks_recent_commands_first() {
+        let pool = SqlitePool::open_memory().unwrap();
+        pool.execute(
+            "CREATE TABLE history (id INTEGER PRIMARY KEY, command TEXT, count INTEGER, created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP, last_used TIMESTAMP, last_exit_code INTEGER);",
+            std::iter::empty::<Value>(),
+        )
+        .unwrap();
+        pool.execute(
+            "CREATE VIRTUAL TABLE history_fts USING fts5(command);",
+            std::iter::empty::<Value>(),
+        )
+        .unwrap();
+
+        // `git push` was only imported from shell history just now: its row is
+        // new, but it has no recorded run
+        let entries = [
+            (1_i64, "git status", 200_i64, "datetime('now', '-365 days')"),
+            (2, "git commit", 10, "datetime('now')"),
+            (3, "git push", 30, "NULL"),
+        ];
+        for (id, cmd, count, last_used) in entries {
+            pool.execute(
+                &format!(
+                    "INSERT INTO history (id, command, count, last_used) VALUES (?, ?, ?, {last_used});"
+                ),
+                vec![
+                    Value::Integer(id),
+                    Value::Text(cmd.to_string()),
+                    Value::Integer(count),
+                ],
+            )
+            .unwrap();
+            pool.execute(
+                "INSERT INTO history_fts (rowid, command) VALUES (?, ?);",
+                vec![Value::Integer(id), Value::Text(cmd.to_string())],
+            )
+            .unwrap();
+        }
+
+        let model = FreqModel::new(pool).with_half_life(30.0);
+        let suggestions = model.predict("git").unwrap();
+        let texts: Vec<&str> = suggestions.iter().map(|s| s.text.as_str()).collect();
+        assert_eq!(texts, vec!["git commit", "git push", "git status"]);
+        assert!((suggestions[1].score - 7.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn demotes_commands_whose_last_run_failed() {
+        let pool = SqlitePool::open_memory().unwrap();
+        pool.execute(
+            "CREATE TABLE history (id INTEGER PRIMARY KEY, command TEXT, count INTEGER, last_exit_code INTEGER);",
+            std::iter::empty::<Value>(),
+        )
+        .unwrap();
+        pool.execute(
+            "CREATE VIRTUAL TABLE history_fts USING fts5(command);",
+            std::iter::empty::<Value>(),
+        )
+        .unwrap();
+
+        let entries = [
+            (1_i64, "make deploy", 8_i64, Value::Integer(2)),
+            (2, "make test", 4, Value::Integer(0)),
+            (3, "make lint", 3, Value::Null),
+        ];
+        for (id, cmd, count, exit_code) in entries {
+            pool.execute(
+                "INSERT INTO history (id, command, count, last_exit_code) VALUES (?, ?, ?, ?);",
+                vec![
+                    Value::Integer(id),
+                    Value::Text(cmd.to_string()),
+                    Value::Integer(count),
+                    exit_code,
+                ],
+            )
+            .unwrap();
+            pool.execute(
+                "INSERT INTO history_fts (rowid, command) VALUES (?, ?);",
+                vec![Value::Integer(id), Value::Text(cmd.to_string())],
+            )
+            .unwrap();
+        }
+
+        let texts: Vec<String> = FreqModel::new(pool)
+            .predict("make")
+            .unwrap()
+            .into_iter()
+            .map(|s| s.text)
+            .collect();
+        assert_eq!(texts, vec!["make test", "make lint", "make deploy"]);
+    }
 }
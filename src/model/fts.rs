@@ -0,0 +1,103 @@
+use anyhow::Result;
+use libsql::Value;
+
+use super::sqlite::{fts_prefix_query, SqlitePool};
+use super::{ModelError, SuggestModel, Suggestion};
+
+const MAX_RESULTS: usize = 20;
+
+/// Ranks history by BM25 relevance from the `history_fts` index, treating the
+/// last typed word as a prefix. Unlike `FreqModel` the order reflects how well
+/// a command matches rather than how often it ran.
+#[derive(Clone, Debug)]
+pub struct FtsModel {
+    pool: SqlitePool,
+}
+
+impl FtsModel {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+impl SuggestModel for FtsModel {
+    fn predict(&self, input: &str) -> Result<Vec<Suggestion>> {
+        if input.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // bm25() is lower for better matches, so negate it for the score
+        let sql = r#"
+            SELECT h.command, -bm25(history_fts)
+            FROM history_fts
+            JOIN history h ON history_fts.rowid = h.id
+            WHERE history_fts MATCH ?1
+            ORDER BY bm25(history_fts)
+            LIMIT ?2
+        "#;
+
+        match self.pool.query_collect(
+            sql,
+            vec![
+                Value::Text(fts_prefix_query(input)),
+                Value::Integer(MAX_RESULTS as i64),
+            ],
+            |row| {
+                let command: String = row.get(0)?;
+                let score: f64 = row.get(1)?;
+                Ok(Suggestion::with_source(command, score, "fts"))
+            },
+        ) {
+            Ok(rows) => Ok(rows),
+            Err(err) if ModelError::is_missing_table(&err) => Ok(Vec::new()),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn weight(&self) -> f64 {
+        0.7
+    }
+
+    fn name(&self) -> &str {
+        "fts"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert_history(pool: &SqlitePool, command: &str) {
+        pool.execute(
+            "INSERT INTO history (command, hash) VALUES (?1, ?1);",
+            vec![Value::Text(command.to_string())],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn ranks_matches_by_relevance() {
+        let pool = SqlitePool::open_memory_migrated().unwrap();
+        for command in [
+            "git status",
+            "docker compose logs --follow status",
+            "ls",
+            "git stash pop",
+        ] {
+            insert_history(&pool, command);
+        }
+
+        let suggestions = FtsModel::new(pool).predict("git st").unwrap();
+        let texts: Vec<&str> = suggestions.iter().map(|s| s.text.as_str()).collect();
+        assert_eq!(texts.len(), 2);
+        assert!(texts.contains(&"git status") && texts.contains(&"git stash pop"));
+        assert!(suggestions.iter().all(|s| s.score > 0.0));
+        assert_eq!(suggestions[0].source.as_deref(), Some("fts"));
+    }
+
+    #[test]
+    fn missing_index_yields_no_suggestions() {
+        let pool = SqlitePool::open_memory().unwrap();
+        assert!(FtsModel::new(pool).predict("git").unwrap().is_empty());
+    }
+}
@@ -1,12 +1,83 @@
 use std::collections::HashSet;
-use std::path::PathBuf;
+use std::fs::File;
+use std::io::Read;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
+use std::str::FromStr;
+use std::sync::Mutex;
 
-use anyhow::{bail, Result};
-use log::{info, warn};
+use anyhow::{bail, Context, Result};
+use log::{debug, info, warn};
+use lru::LruCache;
+use once_cell::sync::OnceCell;
 
 use super::{SuggestModel, Suggestion};
 
+/// Number of distinct inputs whose generations are kept in memory
+const GENERATION_CACHE_SIZE: usize = 128;
+/// Placeholder in `LlmConfig::prompt_template` replaced with the user's input
+pub const PROMPT_INPUT_PLACEHOLDER: &str = "{input}";
+/// Environment variable overriding `LlmConfig::prompt_template`
+const LLM_PROMPT_TEMPLATE_ENV: &str = "GHOSTTYPE_LLM_PROMPT";
+/// Few-shot command completion prompt used unless a template is configured
+pub const DEFAULT_PROMPT_TEMPLATE: &str = "git s→status\ndocker p→ps\nnpm i→install\n{input}→";
+/// Magic bytes every GGUF file starts with
+const GGUF_MAGIC: &[u8; 4] = b"GGUF";
+
+/// Compute backend used by llama-cli for generation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LlmDevice {
+    /// Leave offloading to llama-cli's own defaults (no `-ngl`)
+    #[default]
+    Auto,
+    /// Keep every layer on the CPU (`-ngl 0`)
+    Cpu,
+    /// Apple GPU via llama.cpp's Metal backend
+    Metal,
+    /// NVIDIA GPU with the given device index
+    Cuda(usize),
+}
+
+impl LlmDevice {
+    /// Device name as reported by `llama-cli --list-devices`
+    fn backend_name(&self) -> Option<String> {
+        match self {
+            LlmDevice::Auto | LlmDevice::Cpu => None,
+            LlmDevice::Metal => Some("Metal".to_string()),
+            LlmDevice::Cuda(index) => Some(format!("CUDA{index}")),
+        }
+    }
+
+    /// Device and offload flags passed to llama-cli
+    fn llama_cli_args(&self) -> Vec<String> {
+        match (self, self.backend_name()) {
+            (LlmDevice::Auto, _) => Vec::new(),
+            (_, Some(name)) => vec!["--device".into(), name, "-ngl".into(), "99".into()],
+            (_, None) => vec!["-ngl".into(), "0".into()],
+        }
+    }
+}
+
+impl FromStr for LlmDevice {
+    type Err = anyhow::Error;
+
+    /// Accepts `auto`, `cpu`, `metal`, `cuda` and `cuda:<index>`
+    fn from_str(s: &str) -> Result<Self> {
+        let lower = s.trim().to_ascii_lowercase();
+        match lower.as_str() {
+            "auto" => Ok(LlmDevice::Auto),
+            "cpu" => Ok(LlmDevice::Cpu),
+            "metal" => Ok(LlmDevice::Metal),
+            "cuda" => Ok(LlmDevice::Cuda(0)),
+            other => match other.strip_prefix("cuda:").map(str::parse::<usize>) {
+                Some(Ok(index)) => Ok(LlmDevice::Cuda(index)),
+                _ => bail!("unknown LLM device {s:?} (expected auto, cpu, metal, cuda or cuda:N)"),
+            },
+        }
+    }
+}
+
 /// Configuration for LLM model using external llama-cli
 #[derive(Debug, Clone)]
 pub struct LlmConfig {
@@ -14,6 +85,10 @@ pub struct LlmConfig {
     pub temperature: f64,
     pub max_tokens: usize,
     pub seed: u64,
+    pub device: LlmDevice,
+    /// Prompt sent to llama-cli; every `{input}` is substituted with the
+    /// current input line
+    pub prompt_template: String,
 }
 
 impl Default for LlmConfig {
@@ -23,44 +98,101 @@ impl Default for LlmConfig {
             temperature: 0.05,
             max_tokens: 3,
             seed: 299792458,
+            device: LlmDevice::Auto,
+            prompt_template: DEFAULT_PROMPT_TEMPLATE.to_string(),
         }
     }
 }
 
+impl LlmConfig {
+    /// Every field that influences generation, so cache entries from a
+    /// different configuration are never served
+    fn fingerprint(&self) -> String {
+        format!(
+            "{}|{}|{}|{}|{:?}|{}",
+            self.model_path.display(),
+            self.temperature,
+            self.max_tokens,
+            self.seed,
+            self.device,
+            self.prompt_template
+        )
+    }
+
+    /// Replace `prompt_template` with `$GHOSTTYPE_LLM_PROMPT` when it is set
+    pub fn with_prompt_from_env(mut self) -> Self {
+        if let Ok(template) = std::env::var(LLM_PROMPT_TEMPLATE_ENV) {
+            self.prompt_template = template;
+        }
+        self
+    }
+
+    /// Render `prompt_template` for the given input
+    fn render_prompt(&self, input: &str) -> String {
+        self.prompt_template.replace(PROMPT_INPUT_PLACEHOLDER, input)
+    }
+}
+
+type CacheKey = (String, String);
+
 /// LLM-based suggestion model using external llama-cli command
 #[derive(Debug)]
 pub struct LlmModel {
     config: LlmConfig,
-    llama_cli_available: bool,
+    /// Device llama-cli runs on, probed once on first use; `None` when
+    /// llama-cli or the model file is unusable
+    backend: OnceCell<Option<LlmDevice>>,
+    cache: Mutex<LruCache<CacheKey, Vec<Suggestion>>>,
 }
 
 impl LlmModel {
-    /// Create a new LLM model with the given configuration
+    /// Create a new LLM model with the given configuration. llama-cli is not
+    /// probed until the first prediction, keeping startup fast.
     pub fn new(config: LlmConfig) -> Self {
-        let available = check_llama_cli_available();
-
-        if !available {
-            warn!("llama-cli not found. LLM suggestions will be disabled.");
-            warn!("Install llama.cpp: brew install llama.cpp");
-        } else {
-            info!("llama-cli found. LLM suggestions enabled with model: {:?}", config.model_path);
-        }
-
         Self {
             config,
-            llama_cli_available: available,
+            backend: OnceCell::new(),
+            cache: Mutex::new(LruCache::new(
+                NonZeroUsize::new(GENERATION_CACHE_SIZE).expect("cache size is non-zero"),
+            )),
         }
     }
 
-    /// Call llama-cli once to generate a single suggestion
-    fn call_llama_cli(&self, input: &str, seed: u64) -> Result<String> {
-        // Format few-shot prompt with examples
-        let prompt = format!(
-            "git s→status\ndocker p→ps\nnpm i→install\n{}→",
-            input
-        );
+    /// The device to generate on, probing llama-cli and the model the first
+    /// time. Concurrent first callers wait for a single probe; later calls
+    /// (and generations) take no lock.
+    fn backend(&self) -> Option<LlmDevice> {
+        *self.backend.get_or_init(|| {
+            if !check_llama_cli_available() {
+                warn!("llama-cli not found. LLM suggestions will be disabled.");
+                warn!("Install llama.cpp: brew install llama.cpp");
+                return None;
+            }
+            // llama-cli would read anything else as a model and print noise
+            if let Err(err) = check_gguf_model(&self.config.model_path) {
+                warn!("{err:#}. LLM suggestions will be disabled.");
+                return None;
+            }
+            info!(
+                "llama-cli found. LLM suggestions enabled with model: {:?}",
+                self.config.model_path
+            );
+            Some(resolve_device(self.config.device))
+        })
+    }
+
+    fn cache_key(&self, input: &str) -> CacheKey {
+        (self.config.fingerprint(), input.to_string())
+    }
+
+    /// Call llama-cli once on `device` to generate a single suggestion
+    fn call_llama_cli(&self, input: &str, seed: u64, device: LlmDevice) -> Result<String> {
+        let prompt = self.config.render_prompt(input);
 
-        let output = Command::new("llama-cli")
+        let mut command = Command::new("llama-cli");
+        command.args(device.llama_cli_args());
+
+        let output = command
             .arg("-m")
             .arg(&self.config.model_path)
             .arg("-p")
@@ -91,16 +223,52 @@ impl LlmModel {
 
 impl SuggestModel for LlmModel {
     fn predict(&self, input: &str) -> Result<Vec<Suggestion>> {
-        // Skip if llama-cli is not available
-        if !self.llama_cli_available {
-            return Ok(Vec::new());
-        }
+        Ok(self.generate_suggestions(input, &mut |_| {}))
+    }
+
+    fn predict_streaming(
+        &self,
+        input: &str,
+        sink: &mut dyn FnMut(Vec<Suggestion>),
+    ) -> Result<()> {
+        self.generate_suggestions(input, &mut |suggestion| sink(vec![suggestion.clone()]));
+        Ok(())
+    }
 
+    fn weight(&self) -> f64 {
+        0.4
+    }
+
+    fn name(&self) -> &str {
+        "llm"
+    }
+}
+
+impl LlmModel {
+    /// Generate up to 5 unique completions, handing each to `on_suggestion`
+    /// as soon as llama-cli produces it. Cache hits are delivered all at once.
+    fn generate_suggestions(
+        &self,
+        input: &str,
+        on_suggestion: &mut dyn FnMut(&Suggestion),
+    ) -> Vec<Suggestion> {
         // Skip if input is too short
         if input.trim().len() < 2 {
-            return Ok(Vec::new());
+            return Vec::new();
         }
 
+        let key = self.cache_key(input);
+        if let Some(cached) = self.cache.lock().expect("llm cache poisoned").get(&key) {
+            debug!("llm cache hit for {input:?}");
+            cached.iter().for_each(&mut *on_suggestion);
+            return cached.clone();
+        }
+
+        // Skip if llama-cli or the model is not usable
+        let Some(device) = self.backend() else {
+            return Vec::new();
+        };
+
         let mut suggestions = Vec::new();
         let mut seen = HashSet::new();
 
@@ -110,7 +278,7 @@ impl SuggestModel for LlmModel {
                 break;
             }
 
-            match self.call_llama_cli(input, self.config.seed + i) {
+            match self.call_llama_cli(input, self.config.seed + i, device) {
                 Ok(text) => {
                     let trimmed = text.trim().to_string();
 
@@ -121,7 +289,9 @@ impl SuggestModel for LlmModel {
 
                     // Add only unique suggestions
                     if seen.insert(trimmed.clone()) {
-                        suggestions.push(Suggestion::with_source(trimmed, 1.0, "llm"));
+                        let suggestion = Suggestion::with_source(trimmed, 1.0, "llm");
+                        on_suggestion(&suggestion);
+                        suggestions.push(suggestion);
                     }
                 }
                 Err(e) => {
@@ -130,23 +300,75 @@ impl SuggestModel for LlmModel {
             }
         }
 
-        Ok(suggestions)
-    }
+        // Generation is seeded and deterministic per config; skip caching
+        // empty results so a transient failure is retried next time
+        if !suggestions.is_empty() {
+            self.cache
+                .lock()
+                .expect("llm cache poisoned")
+                .put(key, suggestions.clone());
+        }
 
-    fn weight(&self) -> f64 {
-        0.4
+        suggestions
     }
 }
 
 /// Check if llama-cli command is available
 fn check_llama_cli_available() -> bool {
-    Command::new("llama-cli")
+    check_llama_cli().is_ok()
+}
+
+/// Run `llama-cli --help`, failing with the reason it could not be run
+pub fn check_llama_cli() -> Result<()> {
+    let status = Command::new("llama-cli")
         .arg("--help")
         .stdout(Stdio::null())
         .stderr(Stdio::null())
         .status()
-        .map(|s| s.success())
-        .unwrap_or(false)
+        .context("running llama-cli --help")?;
+    if !status.success() {
+        bail!("llama-cli --help exited with status {status}");
+    }
+    Ok(())
+}
+
+/// Fail unless `path` is a GGUF file. llama-cli takes the prompt format's
+/// tokenizer and end-of-text token from the GGUF itself, so any model family
+/// works with the plain-text prompt; other formats (safetensors, GGML) do not.
+pub fn check_gguf_model(path: &Path) -> Result<()> {
+    let mut magic = [0u8; 4];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .with_context(|| format!("reading LLM model {}", path.display()))?;
+    if &magic != GGUF_MAGIC {
+        bail!(
+            "{} is not a GGUF model; llama-cli needs a .gguf file",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Fall back to CPU when llama-cli was not built with the requested backend
+fn resolve_device(device: LlmDevice) -> LlmDevice {
+    let Some(name) = device.backend_name() else {
+        return device;
+    };
+
+    let listed = Command::new("llama-cli")
+        .arg("--list-devices")
+        .stderr(Stdio::null())
+        .output()
+        .map(|out| String::from_utf8_lossy(&out.stdout).contains(&name))
+        .unwrap_or(false);
+
+    if listed {
+        info!("LLM generation will run on {name}");
+        device
+    } else {
+        warn!("llama-cli does not report a {name} device; falling back to CPU");
+        LlmDevice::Cpu
+    }
 }
 
 /// Parse llama-cli output to extract only the generated text
@@ -208,6 +430,83 @@ mod tests {
         assert_eq!(parse_llama_output(empty), "");
     }
 
+    #[test]
+    fn parses_device_names() {
+        assert_eq!("auto".parse::<LlmDevice>().unwrap(), LlmDevice::Auto);
+        assert_eq!("cpu".parse::<LlmDevice>().unwrap(), LlmDevice::Cpu);
+        assert_eq!("Metal".parse::<LlmDevice>().unwrap(), LlmDevice::Metal);
+        assert_eq!("cuda".parse::<LlmDevice>().unwrap(), LlmDevice::Cuda(0));
+        assert_eq!("cuda:2".parse::<LlmDevice>().unwrap(), LlmDevice::Cuda(2));
+        assert!("tpu".parse::<LlmDevice>().is_err());
+        assert_eq!(LlmDevice::Cuda(1).backend_name().as_deref(), Some("CUDA1"));
+    }
+
+    #[test]
+    fn only_an_explicit_cpu_disables_offloading() {
+        assert!(LlmDevice::default().llama_cli_args().is_empty());
+        assert_eq!(LlmDevice::Cpu.llama_cli_args(), ["-ngl", "0"]);
+        assert_eq!(LlmDevice::Metal.llama_cli_args(), ["--device", "Metal", "-ngl", "99"]);
+    }
+
+    #[test]
+    fn default_prompt_matches_few_shot_template() {
+        let config = LlmConfig::default();
+        assert_eq!(
+            config.render_prompt("git st"),
+            "git s→status\ndocker p→ps\nnpm i→install\ngit st→"
+        );
+
+        let custom = LlmConfig {
+            prompt_template: "complete: {input}\n{input}".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(custom.render_prompt("ls"), "complete: ls\nls");
+    }
+
+    #[test]
+    fn cache_key_changes_with_config() {
+        let model = LlmModel::new(LlmConfig::default());
+        let other = LlmModel::new(LlmConfig {
+            seed: 1,
+            ..Default::default()
+        });
+        assert_eq!(model.cache_key("git s"), model.cache_key("git s"));
+        assert_ne!(model.cache_key("git s"), model.cache_key("git st"));
+        assert_ne!(model.cache_key("git s"), other.cache_key("git s"));
+    }
+
+    #[test]
+    fn unusable_model_is_probed_once_on_first_prediction() {
+        let model = LlmModel::new(LlmConfig {
+            model_path: PathBuf::from("/nonexistent/model.gguf"),
+            ..Default::default()
+        });
+        assert!(model.backend.get().is_none(), "construction does not probe");
+
+        assert!(model.predict("x").unwrap().is_empty());
+        assert!(model.backend.get().is_none(), "too-short input does not probe");
+
+        assert!(model.predict("git st").unwrap().is_empty());
+        assert_eq!(model.backend.get(), Some(&None));
+    }
+
+    #[test]
+    fn only_gguf_files_pass_the_model_check() {
+        let dir = std::env::temp_dir().join(format!("ghosttype-gguf-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let gguf = dir.join("model.gguf");
+        std::fs::write(&gguf, b"GGUF\x03\0\0\0").unwrap();
+        let safetensors = dir.join("model.safetensors");
+        std::fs::write(&safetensors, b"\x08\0\0\0{}").unwrap();
+
+        assert!(check_gguf_model(&gguf).is_ok());
+        let err = check_gguf_model(&safetensors).unwrap_err();
+        assert!(format!("{err:#}").contains("is not a GGUF model"));
+        assert!(check_gguf_model(&dir.join("missing.gguf")).is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
     #[test]
     fn test_check_llama_cli() {
         // This will fail in environments without llama-cli, which is OK
@@ -1,17 +1,30 @@
+pub mod acceptance;
 pub mod alias;
 pub mod embedding;
 pub mod ensemble;
+pub mod error;
 pub mod freq;
+pub mod frecency;
+pub mod fts;
 pub mod llm;
+pub mod ngram;
 pub mod prefix;
 pub mod sqlite;
 pub mod suggestion;
 
-pub use alias::AliasModel;
-pub use embedding::{EmbeddingModel, EmbeddingStore, LlamaEmbeddingClient};
-pub use ensemble::EnsembleBuilder;
+pub use acceptance::{record_acceptance, AcceptanceModel};
+pub use alias::{parse_alias_definitions, AliasModel, SqlAliasStore};
+pub use embedding::{
+    check_llama_embedding, check_vector_support, EmbeddingModel, EmbeddingSearch, EmbeddingStore,
+    LlamaEmbeddingClient,
+};
+pub use ensemble::{DedupMode, Ensemble, EnsembleBuilder, ModelRegistry};
+pub use error::ModelError;
 pub use freq::FreqModel;
-pub use llm::{LlmConfig, LlmModel};
+pub use frecency::FrecencyModel;
+pub use fts::FtsModel;
+pub use llm::{check_llama_cli, LlmConfig, LlmDevice, LlmModel};
+pub use ngram::NgramModel;
 pub use prefix::PrefixModel;
 pub use sqlite::SqlitePool;
 pub use suggestion::{SuggestModel, Suggestion};
@@ -0,0 +1,186 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use anyhow::Result;
+use libsql::Value;
+
+use super::sqlite::SqlitePool;
+use super::{ModelError, SuggestModel, Suggestion};
+
+/// Preceding tokens used to predict the next one. Shorter contexts are
+/// tried as a fallback, scaled down by [`BACKOFF`] per token dropped.
+const MAX_CONTEXT: usize = 2;
+const BACKOFF: f64 = 0.5;
+const MAX_RESULTS: usize = 10;
+
+/// Completes the token being typed (or suggests the next one after a space)
+/// from how often each token followed the preceding ones in history, e.g.
+/// `git commit -` → `git commit -m`. Tokens are whitespace-separated, so a
+/// quoted argument with spaces counts as several.
+#[derive(Debug, Default)]
+pub struct NgramModel {
+    /// Context tokens → following token → runs
+    next: HashMap<Vec<String>, HashMap<String, u64>>,
+}
+
+impl NgramModel {
+    /// Build the statistics from `(command, run count)` pairs
+    pub fn from_commands<I, S>(commands: I) -> Self
+    where
+        I: IntoIterator<Item = (S, u64)>,
+        S: AsRef<str>,
+    {
+        let mut next: HashMap<Vec<String>, HashMap<String, u64>> = HashMap::new();
+        for (command, count) in commands {
+            let tokens: Vec<&str> = command.as_ref().split_whitespace().collect();
+            for i in 1..tokens.len() {
+                for n in 1..=MAX_CONTEXT.min(i) {
+                    let context = tokens[i - n..i].iter().map(|t| t.to_string()).collect();
+                    *next
+                        .entry(context)
+                        .or_default()
+                        .entry(tokens[i].to_string())
+                        .or_default() += count;
+                }
+            }
+        }
+        Self { next }
+    }
+
+    /// Build the statistics from every command in the `history` table
+    pub fn load(pool: &SqlitePool) -> Result<Self> {
+        let commands = match pool.query_collect(
+            "SELECT command, count FROM history",
+            Vec::<Value>::new(),
+            |row| Ok((row.get::<String>(0)?, row.get::<i64>(1)?.max(0) as u64)),
+        ) {
+            Ok(commands) => commands,
+            Err(err) if ModelError::is_missing_table(&err) => Vec::new(),
+            Err(err) => return Err(err),
+        };
+        Ok(Self::from_commands(commands))
+    }
+}
+
+/// `input` split into everything up to the token being typed and that
+/// (possibly empty) partial token
+fn split_partial_token(input: &str) -> (&str, &str) {
+    let start = input
+        .char_indices()
+        .rev()
+        .find(|(_, ch)| ch.is_whitespace())
+        .map_or(0, |(idx, ch)| idx + ch.len_utf8());
+    input.split_at(start)
+}
+
+impl SuggestModel for NgramModel {
+    fn predict(&self, input: &str) -> Result<Vec<Suggestion>> {
+        let (head, partial) = split_partial_token(input);
+        let context: Vec<&str> = head.split_whitespace().collect();
+        if context.is_empty() {
+            // Whole-command models already cover the first token
+            return Ok(Vec::new());
+        }
+
+        // Longest context first, so its score wins for tokens seen under several
+        let longest = MAX_CONTEXT.min(context.len());
+        let mut scores: HashMap<&str, f64> = HashMap::new();
+        for n in (1..=longest).rev() {
+            let key: Vec<String> = context[context.len() - n..]
+                .iter()
+                .map(|t| t.to_string())
+                .collect();
+            let Some(followers) = self.next.get(&key) else {
+                continue;
+            };
+            let total: u64 = followers.values().sum();
+            let backoff = BACKOFF.powi((longest - n) as i32);
+            for (token, count) in followers {
+                if token.starts_with(partial) && token != partial {
+                    scores
+                        .entry(token.as_str())
+                        .or_insert(backoff * *count as f64 / total as f64);
+                }
+            }
+        }
+
+        let mut suggestions: Vec<Suggestion> = scores
+            .into_iter()
+            .map(|(token, score)| Suggestion::with_source(format!("{head}{token}"), score, "ngram"))
+            .collect();
+        suggestions.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(Ordering::Equal)
+                .then_with(|| a.text.cmp(&b.text))
+        });
+        suggestions.truncate(MAX_RESULTS);
+        Ok(suggestions)
+    }
+
+    fn weight(&self) -> f64 {
+        0.4
+    }
+
+    fn name(&self) -> &str {
+        "ngram"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model() -> NgramModel {
+        NgramModel::from_commands([
+            ("git commit -m fix", 6),
+            ("git commit --amend", 2),
+            ("git checkout main", 3),
+            ("git checkout -b topic", 1),
+            ("docker run -m 512m alpine", 4),
+        ])
+    }
+
+    fn texts(suggestions: Vec<Suggestion>) -> Vec<String> {
+        suggestions.into_iter().map(|s| s.text).collect()
+    }
+
+    #[test]
+    fn completes_the_token_being_typed() {
+        let suggestions = model().predict("git commit -").unwrap();
+        assert_eq!(texts(suggestions), vec!["git commit -m", "git commit --amend"]);
+    }
+
+    #[test]
+    fn suggests_the_next_token_after_a_space() {
+        let suggestions = model().predict("git ").unwrap();
+        assert_eq!(texts(suggestions), vec!["git commit", "git checkout"]);
+        assert!(model().predict("gi").unwrap().is_empty());
+    }
+
+    #[test]
+    fn falls_back_to_shorter_contexts_at_lower_scores() {
+        // "sudo checkout" never came up, but "-b" did follow "checkout"
+        let suggestions = model().predict("sudo checkout -").unwrap();
+        assert_eq!(texts(suggestions.clone()), vec!["sudo checkout -b"]);
+        assert_eq!(suggestions[0].score, 0.5 * 1.0 / 4.0);
+        assert_eq!(suggestions[0].source.as_deref(), Some("ngram"));
+    }
+
+    #[test]
+    fn loads_counts_from_history() {
+        let pool = SqlitePool::open_memory_migrated().unwrap();
+        for (command, count) in [("cargo test --release", 3_i64), ("cargo test --doc", 1)] {
+            pool.execute(
+                "INSERT INTO history (command, hash, count) VALUES (?1, ?1, ?2);",
+                vec![Value::Text(command.to_string()), Value::Integer(count)],
+            )
+            .unwrap();
+        }
+        let suggestions = NgramModel::load(&pool).unwrap().predict("cargo test --").unwrap();
+        assert_eq!(texts(suggestions), vec!["cargo test --release", "cargo test --doc"]);
+
+        let empty = SqlitePool::open_memory().unwrap();
+        assert!(NgramModel::load(&empty).unwrap().next.is_empty());
+    }
+}
@@ -1,17 +1,83 @@
+use std::path::{Path, PathBuf};
+
 use anyhow::Result;
 use libsql::Value;
 
-use super::{sqlite::SqlitePool, SuggestModel, Suggestion};
+use super::{
+    sqlite::{prefix_match_sql, prefix_match_value, word_match_sql, word_match_value, SqlitePool},
+    ModelError, SuggestModel, Suggestion,
+};
+
+/// Score multiplier for commands last run in the current directory
+const CWD_BOOST: f64 = 2.0;
 
 #[derive(Clone, Debug)]
 pub struct PrefixModel {
     pool: SqlitePool,
+    cwd: Option<PathBuf>,
+    case_sensitive: bool,
+    word_boundary: bool,
 }
 
 impl PrefixModel {
     pub fn new(pool: SqlitePool) -> Self {
-        Self { pool }
+        Self {
+            pool,
+            cwd: None,
+            case_sensitive: false,
+            word_boundary: false,
+        }
+    }
+
+    /// Boost commands whose recorded `cwd` matches `path`.
+    /// Rows without a recorded directory keep their plain count score.
+    pub fn with_cwd(mut self, path: impl AsRef<Path>) -> Self {
+        self.cwd = Some(path.as_ref().to_path_buf());
+        self
     }
+
+    /// Match the prefix case-sensitively instead of the default
+    /// case-insensitive comparison
+    pub fn with_case_sensitive(mut self, case_sensitive: bool) -> Self {
+        self.case_sensitive = case_sensitive;
+        self
+    }
+
+    /// Also match commands with a later word starting with the input, so
+    /// `status` finds `git status`. These rank below every true prefix match.
+    pub fn with_word_boundary(mut self, word_boundary: bool) -> Self {
+        self.word_boundary = word_boundary;
+        self
+    }
+}
+
+/// Scale the scores of word-boundary matches (rows with `is_word_match`,
+/// which come after the prefix matches) to below the weakest prefix match,
+/// keeping their relative order
+fn rank_word_matches_below(rows: Vec<(Suggestion, bool)>) -> Vec<Suggestion> {
+    let weakest_prefix = rows
+        .iter()
+        .filter(|(_, is_word_match)| !is_word_match)
+        .map(|(s, _)| s.score)
+        .fold(f64::INFINITY, f64::min);
+    let strongest_word = rows
+        .iter()
+        .filter(|(_, is_word_match)| *is_word_match)
+        .map(|(s, _)| s.score)
+        .fold(0.0, f64::max);
+    let scale = if weakest_prefix.is_finite() {
+        weakest_prefix / (strongest_word + 1.0)
+    } else {
+        1.0
+    };
+    rows.into_iter()
+        .map(|(mut suggestion, is_word_match)| {
+            if is_word_match {
+                suggestion.score *= scale;
+            }
+            suggestion
+        })
+        .collect()
 }
 
 impl SuggestModel for PrefixModel {
@@ -20,27 +86,51 @@ impl SuggestModel for PrefixModel {
             return Ok(Vec::new());
         }
         log::debug!("PrefixModel::predict invoked with input: {input}");
-        let like = format!("{}%", input);
-        let sql = r#"
-            SELECT command, count
+        let prefix = prefix_match_sql("command", "?1", self.case_sensitive);
+        let mut params = vec![prefix_match_value(input, self.case_sensitive)];
+        let score = match &self.cwd {
+            Some(cwd) => {
+                params.push(Value::Text(cwd.to_string_lossy().to_string()));
+                params.push(Value::Real(CWD_BOOST));
+                "count * CASE WHEN cwd = ?2 THEN ?3 ELSE 1.0 END"
+            }
+            None => "count * 1.0",
+        };
+        let filter = if self.word_boundary {
+            params.push(word_match_value(input, self.case_sensitive));
+            let param = format!("?{}", params.len());
+            format!(
+                "({prefix}) OR ({})",
+                word_match_sql("command", &param, self.case_sensitive)
+            )
+        } else {
+            prefix.clone()
+        };
+        let sql = format!(
+            r#"
+            SELECT command, {score} AS score, NOT ({prefix}) AS word_match
             FROM history
-            WHERE command LIKE ?
-            ORDER BY count DESC
+            WHERE {filter}
+            ORDER BY word_match, score DESC, count DESC
             LIMIT 20
-        "#;
-
-        match self
+        "#
+        );
+        let result = self
             .pool
-            .query_collect(sql, vec![Value::Text(like)], |row| {
+            .query_collect(&sql, params, |row| {
                 let command: String = row.get(0)?;
-                let count: i64 = row.get(1)?;
-                Ok(Suggestion::with_source(command, count as f64, "prefix"))
-            }) {
+                let score: f64 = row.get(1)?;
+                let word_match: bool = row.get::<i64>(2)? != 0;
+                Ok((Suggestion::with_source(command, score, "prefix"), word_match))
+            })
+            .map(rank_word_matches_below);
+
+        match result {
             Ok(rows) => {
                 log::debug!("PrefixModel::predict returning {} suggestions", rows.len());
                 Ok(rows)
             }
-            Err(err) if err.to_string().contains("no such table") => Ok(Vec::new()),
+            Err(err) if ModelError::is_missing_table(&err) => Ok(Vec::new()),
             Err(err) => Err(err),
         }
     }
@@ -48,6 +138,10 @@ impl SuggestModel for PrefixModel {
     fn weight(&self) -> f64 {
         0.8
     }
+
+    fn name(&self) -> &str {
+        "prefix"
+    }
 }
 
 #[cfg(test)]
@@ -76,4 +170,126 @@ mod tests {
         assert_eq!(suggestions[0].text, "git status");
         assert_eq!(suggestions[1].text, "git commit");
     }
+
+    #[test]
+    fn boosts_commands_from_current_directory() {
+        let pool = SqlitePool::open_memory().unwrap();
+        pool.execute(
+            "CREATE TABLE history (id INTEGER PRIMARY KEY, command TEXT, count INTEGER, cwd TEXT DEFAULT '');",
+            std::iter::empty::<Value>(),
+        )
+        .unwrap();
+        for (cmd, count, cwd) in [
+            ("git status", 5, "/elsewhere"),
+            ("git commit", 3, "/project"),
+            ("git push", 4, ""),
+        ] {
+            pool.execute(
+                "INSERT INTO history (command, count, cwd) VALUES (?, ?, ?)",
+                vec![
+                    Value::Text(cmd.to_string()),
+                    Value::Integer(count as i64),
+                    Value::Text(cwd.to_string()),
+                ],
+            )
+            .unwrap();
+        }
+
+        let model = PrefixModel::new(pool).with_cwd("/project");
+        let suggestions = model.predict("git").unwrap();
+        let texts: Vec<&str> = suggestions.iter().map(|s| s.text.as_str()).collect();
+        assert_eq!(texts, vec!["git commit", "git status", "git push"]);
+        assert_eq!(suggestions[0].score, 6.0);
+    }
+
+    #[test]
+    fn word_boundary_matches_rank_below_prefix_matches() {
+        let pool = SqlitePool::open_memory().unwrap();
+        pool.execute(
+            "CREATE TABLE history (id INTEGER PRIMARY KEY, command TEXT, count INTEGER);",
+            std::iter::empty::<Value>(),
+        )
+        .unwrap();
+        for (cmd, count) in [
+            ("status-report", 1),
+            ("git status", 50),
+            ("svn Status -u", 20),
+            ("echo mystatus", 99),
+        ] {
+            pool.execute(
+                "INSERT INTO history (command, count) VALUES (?, ?)",
+                vec![Value::Text(cmd.to_string()), Value::Integer(count as i64)],
+            )
+            .unwrap();
+        }
+        let texts = |model: &PrefixModel| -> Vec<String> {
+            model.predict("status").unwrap().into_iter().map(|s| s.text).collect()
+        };
+
+        let plain = PrefixModel::new(pool.clone());
+        assert_eq!(texts(&plain), vec!["status-report"], "opt-in only");
+
+        let words = PrefixModel::new(pool.clone()).with_word_boundary(true);
+        assert_eq!(texts(&words), vec!["status-report", "git status", "svn Status -u"]);
+        let scores: Vec<f64> = words.predict("status").unwrap().iter().map(|s| s.score).collect();
+        assert!(scores[0] > scores[1] && scores[1] > scores[2]);
+
+        let strict = PrefixModel::new(pool)
+            .with_word_boundary(true)
+            .with_case_sensitive(true);
+        assert_eq!(texts(&strict), vec!["status-report", "git status"]);
+    }
+
+    #[test]
+    fn like_wildcards_in_the_input_match_literally() {
+        let pool = SqlitePool::open_memory().unwrap();
+        pool.execute(
+            "CREATE TABLE history (id INTEGER PRIMARY KEY, command TEXT, count INTEGER);",
+            std::iter::empty::<Value>(),
+        )
+        .unwrap();
+        for cmd in ["my_app start", "myxapp start", "echo 100%", "echo 1000", "cd a\\b"] {
+            pool.execute(
+                "INSERT INTO history (command, count) VALUES (?, 1)",
+                vec![Value::Text(cmd.to_string())],
+            )
+            .unwrap();
+        }
+        let texts = |model: &PrefixModel, input: &str| -> Vec<String> {
+            model.predict(input).unwrap().into_iter().map(|s| s.text).collect()
+        };
+
+        let model = PrefixModel::new(pool.clone());
+        assert_eq!(texts(&model, "my_"), vec!["my_app start"]);
+        assert_eq!(texts(&model, "echo 100%"), vec!["echo 100%"]);
+        assert_eq!(texts(&model, "cd a\\"), vec!["cd a\\b"]);
+        let words = PrefixModel::new(pool).with_word_boundary(true);
+        assert_eq!(texts(&words, "_app"), Vec::<String>::new());
+        assert_eq!(texts(&words, "100%"), vec!["echo 100%"]);
+    }
+
+    #[test]
+    fn matches_prefix_case_insensitively_by_default() {
+        let pool = SqlitePool::open_memory().unwrap();
+        pool.execute(
+            "CREATE TABLE history (id INTEGER PRIMARY KEY, command TEXT, count INTEGER);",
+            std::iter::empty::<Value>(),
+        )
+        .unwrap();
+        pool.execute(
+            "INSERT INTO history (command, count) VALUES ('Git Status', 1)",
+            std::iter::empty::<Value>(),
+        )
+        .unwrap();
+
+        let model = PrefixModel::new(pool.clone());
+        let suggestions = model.predict("git").unwrap();
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].text, "Git Status");
+        assert_eq!(model.predict("GIT").unwrap().len(), 1);
+
+        let strict = PrefixModel::new(pool).with_case_sensitive(true);
+        assert!(strict.predict("git").unwrap().is_empty());
+        assert_eq!(strict.predict("Git").unwrap().len(), 1);
+    }
 }
@@ -1,18 +1,25 @@
 use std::path::Path;
-#[cfg(test)]
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, MutexGuard};
 
 use anyhow::{Context, Result};
 use directories::BaseDirs;
-use libsql::{params::Params, Builder, Connection, Database, Row, Value};
+use log::warn;
+use libsql::{params::Params, Builder, Connection, Database, OpenFlags, Row, Value};
 use tokio::runtime::{Builder as RuntimeBuilder, Runtime};
 
+use super::ModelError;
+
+/// Connections opened per database so concurrent model queries don't queue
+/// behind a single lock
+const POOL_SIZE: usize = 4;
+
 #[derive(Clone)]
 pub struct SqlitePool {
     #[allow(dead_code)]
     db: Arc<Database>,
-    conn: Arc<Mutex<Connection>>,
+    conns: Arc<Vec<Mutex<Connection>>>,
+    next: Arc<AtomicUsize>,
     runtime: Arc<Runtime>,
 }
 
@@ -23,22 +30,47 @@ impl std::fmt::Debug for SqlitePool {
 }
 
 impl SqlitePool {
-    fn new(db: Database, runtime: Arc<Runtime>, apply_migrations: bool) -> Result<Self> {
-        let conn = db
-            .connect()
-            .context("creating initial connection for sqlite pool")?;
+    /// `file_backed` databases get WAL journaling and migrations; the shared
+    /// in-memory test database gets neither
+    fn new(db: Database, runtime: Arc<Runtime>, file_backed: bool) -> Result<Self> {
+        let mut conns = Vec::with_capacity(POOL_SIZE);
+        for idx in 0..POOL_SIZE {
+            let conn = db
+                .connect()
+                .with_context(|| format!("creating connection {idx} for sqlite pool"))?;
 
-        if apply_migrations {
-            run_migrations(runtime.as_ref(), &conn)?;
+            if file_backed {
+                if idx == 0 {
+                    // journal_mode is persistent for the database file
+                    run_pragma(runtime.as_ref(), &conn, "PRAGMA journal_mode=WAL;")?;
+                    run_migrations(runtime.as_ref(), &conn)?;
+                }
+                run_pragma(runtime.as_ref(), &conn, "PRAGMA busy_timeout=5000;")?;
+            }
+            conns.push(Mutex::new(conn));
         }
 
         Ok(Self {
             db: Arc::new(db),
-            conn: Arc::new(Mutex::new(conn)),
+            conns: Arc::new(conns),
+            next: Arc::new(AtomicUsize::new(0)),
             runtime,
         })
     }
 
+    /// Hand out the first idle connection, starting from a round-robin cursor.
+    /// When all are busy, wait on the cursor's connection.
+    fn checkout(&self) -> MutexGuard<'_, Connection> {
+        let start = self.next.fetch_add(1, Ordering::Relaxed) % self.conns.len();
+        for offset in 0..self.conns.len() {
+            let idx = (start + offset) % self.conns.len();
+            if let Ok(conn) = self.conns[idx].try_lock() {
+                return conn;
+            }
+        }
+        self.conns[start].lock().expect("sqlite connection poisoned")
+    }
+
     pub fn open_path(path: impl AsRef<Path>) -> Result<Self> {
         let path = path.as_ref();
         let path_str = path.to_string_lossy().to_string();
@@ -54,6 +86,27 @@ impl SqlitePool {
         Self::new(db, runtime, true)
     }
 
+    /// Open another tool's database without touching it: read-only, and
+    /// no pragmas or migrations
+    pub fn open_readonly(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let path_str = path.to_string_lossy().to_string();
+        let runtime = Arc::new(
+            RuntimeBuilder::new_current_thread()
+                .enable_all()
+                .build()
+                .context("creating runtime for libsql builder")?,
+        );
+        let db = runtime
+            .block_on(
+                Builder::new_local(&path_str)
+                    .flags(OpenFlags::SQLITE_OPEN_READ_ONLY)
+                    .build(),
+            )
+            .with_context(|| format!("opening libsql database at {} read-only", path_str))?;
+        Self::new(db, runtime, false)
+    }
+
     pub fn open_default() -> Result<Self> {
         let cache_dir = BaseDirs::new()
             .context("resolving cache directory for libsql")?
@@ -68,7 +121,7 @@ impl SqlitePool {
         Self::open_path(&db_path)
     }
 
-    #[cfg(test)]
+    /// Fresh in-memory database without any tables, for tests and benchmarks
     pub fn open_memory() -> Result<Self> {
         static MEMORY_DB_COUNTER: AtomicUsize = AtomicUsize::new(0);
         let id = MEMORY_DB_COUNTER.fetch_add(1, Ordering::Relaxed);
@@ -85,21 +138,30 @@ impl SqlitePool {
         Self::new(db, runtime, false)
     }
 
+    /// In-memory database with the full migrated schema applied
+    pub fn open_memory_migrated() -> Result<Self> {
+        let pool = Self::open_memory()?;
+        run_migrations(pool.runtime.as_ref(), &pool.checkout())?;
+        Ok(pool)
+    }
+
     pub fn query_collect<T, I, F>(&self, sql: &str, params: I, mut map: F) -> Result<Vec<T>>
     where
         I: IntoIterator<Item = Value>,
         F: FnMut(Row) -> Result<T>,
     {
-        let conn = self.conn.lock().expect("sqlite connection poisoned");
+        let conn = self.checkout();
         let params = Params::Positional(params.into_iter().collect());
         let mut rows = self
             .runtime
             .block_on(conn.query(sql, params))
+            .map_err(ModelError::from)
             .context("running libsql query")?;
         let mut out = Vec::new();
         while let Some(row) = self
             .runtime
             .block_on(rows.next())
+            .map_err(ModelError::from)
             .context("fetching libsql row")?
         {
             out.push(map(row)?);
@@ -107,21 +169,278 @@ impl SqlitePool {
         Ok(out)
     }
 
-    #[cfg_attr(not(test), allow(dead_code))]
     pub fn execute<I>(&self, sql: &str, params: I) -> Result<()>
     where
         I: IntoIterator<Item = Value>,
     {
-        let conn = self.conn.lock().expect("sqlite connection poisoned");
+        let conn = self.checkout();
         let params = Params::Positional(params.into_iter().collect());
         self.runtime
             .block_on(conn.execute(sql, params))
+            .map_err(ModelError::from)
             .context("executing libsql statement")?;
         Ok(())
     }
+
+    /// Like `execute`, but returns the number of rows changed
+    pub fn execute_changes<I>(&self, sql: &str, params: I) -> Result<u64>
+    where
+        I: IntoIterator<Item = Value>,
+    {
+        let conn = self.checkout();
+        let params = Params::Positional(params.into_iter().collect());
+        self.runtime
+            .block_on(conn.execute(sql, params))
+            .map_err(ModelError::from)
+            .context("executing libsql statement")
+    }
+
+    /// Run `statements` in order on one connection inside a single
+    /// transaction, rolling back if any of them fails
+    pub fn execute_in_transaction(&self, statements: Vec<(&str, Vec<Value>)>) -> Result<()> {
+        let conn = self.checkout();
+        let no_params = || Params::Positional(Vec::<Value>::new());
+        self.runtime
+            .block_on(conn.execute("BEGIN;", no_params()))
+            .context("beginning libsql transaction")?;
+        for (sql, params) in statements {
+            if let Err(err) = self
+                .runtime
+                .block_on(conn.execute(sql, Params::Positional(params)))
+            {
+                self.runtime.block_on(conn.execute("ROLLBACK;", no_params())).ok();
+                return Err(ModelError::from(err))
+                    .context("executing libsql statement in transaction");
+            }
+        }
+        self.runtime
+            .block_on(conn.execute("COMMIT;", no_params()))
+            .context("committing libsql transaction")?;
+        Ok(())
+    }
+
+    /// Rebuild the database file to reclaim space freed by deletes
+    pub fn vacuum(&self) -> Result<()> {
+        let conn = self.checkout();
+        self.runtime
+            .block_on(conn.execute("VACUUM;", Params::Positional(Vec::<Value>::new())))
+            .context("vacuuming libsql database")?;
+        Ok(())
+    }
 }
 
+/// SQL condition matching `column` as a prefix of `param`, paired with
+/// [`prefix_match_value`] for the bound value. Case-insensitive matching
+/// lowercases both sides so it does not depend on `case_sensitive_like`;
+/// case-sensitive matching compares the leading substring exactly.
+pub(crate) fn prefix_match_sql(column: &str, param: &str, case_sensitive: bool) -> String {
+    if case_sensitive {
+        format!("substr({column}, 1, length({param})) = {param}")
+    } else {
+        format!("lower({column}) LIKE lower({param}) ESCAPE '\\'")
+    }
+}
+
+pub(crate) fn prefix_match_value(input: &str, case_sensitive: bool) -> Value {
+    if case_sensitive {
+        Value::Text(input.to_string())
+    } else {
+        Value::Text(format!("{}%", escape_like(input)))
+    }
+}
+
+/// SQL condition matching `column` values with a word (after a space) that
+/// starts with the input, paired with [`word_match_value`]; the counterpart
+/// of [`prefix_match_sql`] for the words after the first
+pub(crate) fn word_match_sql(column: &str, param: &str, case_sensitive: bool) -> String {
+    if case_sensitive {
+        format!("instr({column}, {param}) > 0")
+    } else {
+        format!("lower({column}) LIKE lower({param}) ESCAPE '\\'")
+    }
+}
+
+pub(crate) fn word_match_value(input: &str, case_sensitive: bool) -> Value {
+    if case_sensitive {
+        Value::Text(format!(" {input}"))
+    } else {
+        Value::Text(format!("% {}%", escape_like(input)))
+    }
+}
+
+/// Escape `LIKE` wildcards so `%` and `_` in typed input match literally;
+/// pairs with `ESCAPE '\'` in the SQL
+fn escape_like(input: &str) -> String {
+    let mut escaped = String::with_capacity(input.len());
+    for c in input.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// FTS5 `MATCH` expression for typed input with the last word as a prefix.
+/// Every whitespace-separated word is quoted (doubling embedded `"`) so
+/// characters FTS5 treats as operators, such as `-`, `:` or `*`, are matched
+/// as text instead of producing a syntax error.
+pub(crate) fn fts_prefix_query(input: &str) -> String {
+    let terms: Vec<String> = input
+        .split_whitespace()
+        .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+        .collect();
+    format!("{}*", terms.join(" "))
+}
+
+/// File databases use WAL so readers (e.g. `search`) proceed while the TUI
+/// writes, and a busy timeout so short write locks are waited out instead of
+/// failing immediately
+fn run_pragma(runtime: &Runtime, conn: &Connection, pragma: &str) -> Result<()> {
+    // PRAGMAs report their new value as a row, so run them as queries
+    let mut rows = runtime
+        .block_on(conn.query(pragma, Params::Positional(Vec::<Value>::new())))
+        .with_context(|| format!("running {pragma}"))?;
+    while runtime
+        .block_on(rows.next())
+        .with_context(|| format!("reading result of {pragma}"))?
+        .is_some()
+    {}
+    Ok(())
+}
+
+/// A numbered schema change, applied at most once per database
+struct Migration {
+    version: i64,
+    description: &'static str,
+    apply: fn(&Runtime, &Connection) -> Result<()>,
+}
+
+/// Ordered list of migrations; append new steps with the next version number
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "base schema",
+        apply: migrate_base_schema,
+    },
+    Migration {
+        version: 2,
+        description: "cwd columns",
+        apply: migrate_cwd_columns,
+    },
+    Migration {
+        version: 3,
+        description: "execution exit codes",
+        apply: migrate_exit_code_column,
+    },
+    Migration {
+        version: 4,
+        description: "last exit code per command",
+        apply: migrate_last_exit_code_column,
+    },
+    Migration {
+        version: 5,
+        description: "history import offsets",
+        apply: migrate_meta_offset_column,
+    },
+    Migration {
+        version: 6,
+        description: "accepted suggestions",
+        apply: migrate_acceptances_table,
+    },
+    Migration {
+        version: 7,
+        description: "last run time per command",
+        apply: migrate_last_used_column,
+    },
+    Migration {
+        version: 8,
+        description: "history import file sizes",
+        apply: migrate_meta_byte_len_column,
+    },
+    Migration {
+        version: 9,
+        description: "meta values",
+        apply: migrate_meta_value_column,
+    },
+    Migration {
+        version: 10,
+        description: "atuin import progress per database",
+        apply: migrate_atuin_import_keys,
+    },
+];
+
 fn run_migrations(runtime: &Runtime, conn: &Connection) -> Result<()> {
+    execute_unit(
+        runtime,
+        conn,
+        r#"CREATE TABLE IF NOT EXISTS schema_migrations (
+            version    INTEGER PRIMARY KEY,
+            applied_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        );"#,
+    )
+    .context("creating schema_migrations table")?;
+
+    let applied = applied_versions(runtime, conn)?;
+    for migration in MIGRATIONS {
+        if applied.contains(&migration.version) {
+            continue;
+        }
+
+        execute_unit(runtime, conn, "BEGIN;")?;
+        let result = (migration.apply)(runtime, conn).and_then(|_| {
+            runtime
+                .block_on(conn.execute(
+                    "INSERT INTO schema_migrations (version) VALUES (?1);",
+                    Params::Positional(vec![Value::Integer(migration.version)]),
+                ))
+                .context("recording applied migration")
+        });
+        match result {
+            Ok(_) => execute_unit(runtime, conn, "COMMIT;")?,
+            Err(err) => {
+                let _ = execute_unit(runtime, conn, "ROLLBACK;");
+                return Err(err).with_context(|| {
+                    format!(
+                        "applying migration {} ({})",
+                        migration.version, migration.description
+                    )
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn applied_versions(runtime: &Runtime, conn: &Connection) -> Result<Vec<i64>> {
+    let mut rows = runtime
+        .block_on(conn.query(
+            "SELECT version FROM schema_migrations ORDER BY version",
+            Params::Positional(Vec::<Value>::new()),
+        ))
+        .context("reading applied migrations")?;
+    let mut versions = Vec::new();
+    while let Some(row) = runtime
+        .block_on(rows.next())
+        .context("fetching applied migration")?
+    {
+        versions.push(row.get::<i64>(0)?);
+    }
+    Ok(versions)
+}
+
+fn execute_unit(runtime: &Runtime, conn: &Connection, sql: &str) -> Result<()> {
+    runtime
+        .block_on(conn.execute(sql, Params::Positional(Vec::<Value>::new())))
+        .map_err(ModelError::from)
+        .with_context(|| format!("running statement: {}", sql.lines().next().unwrap_or(sql)))?;
+    Ok(())
+}
+
+/// Migration 1: the original tables, indexes and FTS triggers.
+/// Statements are idempotent so databases created before versioning upgrade cleanly.
+fn migrate_base_schema(runtime: &Runtime, conn: &Connection) -> Result<()> {
     const SCHEMA_STATEMENTS: &[&str] = &[
         r#"CREATE TABLE IF NOT EXISTS history (
             id          INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -173,30 +492,241 @@ fn run_migrations(runtime: &Runtime, conn: &Connection) -> Result<()> {
             emb F32_BLOB(768),
             created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
         );"#,
-        "CREATE INDEX IF NOT EXISTS embeddings_idx ON embeddings(libsql_vector_idx(emb));",
     ];
+    const VECTOR_INDEX_STATEMENT: &str =
+        "CREATE INDEX IF NOT EXISTS embeddings_idx ON embeddings(libsql_vector_idx(emb));";
 
     for sql in SCHEMA_STATEMENTS {
-        runtime
-            .block_on(conn.execute(sql, Params::Positional(Vec::<Value>::new())))
-            .with_context(|| {
-                format!(
-                    "running migration statement: {}",
-                    sql.lines().next().unwrap_or(sql)
-                )
-            })?;
+        execute_unit(runtime, conn, sql)?;
     }
 
-    if let Err(err) = runtime.block_on(conn.execute(
-        "ALTER TABLE history ADD COLUMN output TEXT DEFAULT '';",
-        Params::Positional(Vec::<Value>::new()),
-    )) {
-        let msg = err.to_string();
-        if !msg.contains("duplicate column name") {
-            let result: std::result::Result<(), libsql::Error> = Err(err);
-            result.context("adding output column to history")?;
+    // Plain SQLite has no vector index; embedding search then scans in memory
+    if let Err(err) = execute_unit(runtime, conn, VECTOR_INDEX_STATEMENT) {
+        if !ModelError::is_vector_unsupported(&err) {
+            return Err(err);
         }
+        warn!("libsql vector index unavailable; embedding search will be slower: {err:#}");
     }
 
+    add_column_if_missing(runtime, conn, "history", "output TEXT DEFAULT ''")?;
+
+    Ok(())
+}
+
+/// Migration 2: directory each command was run in
+fn migrate_cwd_columns(runtime: &Runtime, conn: &Connection) -> Result<()> {
+    add_column_if_missing(runtime, conn, "history", "cwd TEXT DEFAULT ''")?;
+    add_column_if_missing(runtime, conn, "command_executions", "cwd TEXT DEFAULT ''")?;
     Ok(())
 }
+
+/// Migration 3: exit status of each execution; NULL when unknown
+fn migrate_exit_code_column(runtime: &Runtime, conn: &Connection) -> Result<()> {
+    add_column_if_missing(runtime, conn, "command_executions", "exit_code INTEGER")
+}
+
+/// Migration 4: exit status of the most recent run, used to demote failing commands
+fn migrate_last_exit_code_column(runtime: &Runtime, conn: &Connection) -> Result<()> {
+    add_column_if_missing(runtime, conn, "history", "last_exit_code INTEGER")
+}
+
+/// Migration 5: bytes of each shell history file already imported
+fn migrate_meta_offset_column(runtime: &Runtime, conn: &Connection) -> Result<()> {
+    add_column_if_missing(runtime, conn, "meta", "byte_offset INTEGER NOT NULL DEFAULT 0")
+}
+
+/// Migration 6: which suggestion was picked for which typed input
+fn migrate_acceptances_table(runtime: &Runtime, conn: &Connection) -> Result<()> {
+    execute_unit(
+        runtime,
+        conn,
+        r#"CREATE TABLE IF NOT EXISTS acceptances (
+            prefix      TEXT NOT NULL,
+            command     TEXT NOT NULL,
+            count       INTEGER NOT NULL DEFAULT 1,
+            accepted_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            PRIMARY KEY (prefix, command)
+        );"#,
+    )
+}
+
+/// Migration 7: when each command last actually ran; shell history imports
+/// carry no timestamps and leave it alone. Backfilled from recorded runs.
+fn migrate_last_used_column(runtime: &Runtime, conn: &Connection) -> Result<()> {
+    add_column_if_missing(runtime, conn, "history", "last_used TIMESTAMP")?;
+    execute_unit(
+        runtime,
+        conn,
+        r#"UPDATE history SET last_used = (
+            SELECT MAX(e.executed_at) FROM command_executions e WHERE e.command = history.command
+        );"#,
+    )
+}
+
+/// Migration 8: size of each shell history file at its last import, so a
+/// change within the mtime resolution is not mistaken for no change
+fn migrate_meta_byte_len_column(runtime: &Runtime, conn: &Connection) -> Result<()> {
+    add_column_if_missing(runtime, conn, "meta", "byte_len INTEGER NOT NULL DEFAULT -1")
+}
+
+/// Migration 9: a value column for `meta` rows that are not about a file,
+/// moving the saved TUI input and view out of `path`
+fn migrate_meta_value_column(runtime: &Runtime, conn: &Connection) -> Result<()> {
+    add_column_if_missing(runtime, conn, "meta", "value TEXT")?;
+    execute_unit(
+        runtime,
+        conn,
+        "UPDATE meta SET value = path, path = '' WHERE key IN ('tui_input', 'tui_view');",
+    )
+}
+
+/// Migration 10: Atuin import progress is kept per database, keyed like the
+/// shell history files are
+fn migrate_atuin_import_keys(runtime: &Runtime, conn: &Connection) -> Result<()> {
+    execute_unit(
+        runtime,
+        conn,
+        "UPDATE meta SET key = 'atuin_import:' || path WHERE key = 'atuin_import';",
+    )
+}
+
+/// `ALTER TABLE ... ADD COLUMN` that tolerates databases which already have the column
+fn add_column_if_missing(
+    runtime: &Runtime,
+    conn: &Connection,
+    table: &str,
+    column_def: &str,
+) -> Result<()> {
+    let sql = format!("ALTER TABLE {table} ADD COLUMN {column_def};");
+    match runtime
+        .block_on(conn.execute(&sql, Params::Positional(Vec::<Value>::new())))
+        .map_err(ModelError::from)
+    {
+        Ok(_) | Err(ModelError::DuplicateColumn(_)) => Ok(()),
+        Err(err) => Err(err).with_context(|| format!("adding column `{column_def}` to {table}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn versions(pool: &SqlitePool) -> Vec<i64> {
+        pool.query_collect(
+            "SELECT version FROM schema_migrations ORDER BY version",
+            std::iter::empty::<Value>(),
+            |row| Ok(row.get::<i64>(0)?),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn fts_prefix_query_quotes_each_word() {
+        assert_eq!(fts_prefix_query("git st"), r#""git" "st"*"#);
+        assert_eq!(fts_prefix_query(r#"git "commit"#), r#""git" """commit"*"#);
+        assert_eq!(fts_prefix_query("  ls  "), r#""ls"*"#);
+    }
+
+    #[test]
+    fn query_errors_say_which_failure_they_are() {
+        let pool = SqlitePool::open_memory().unwrap();
+        let err = pool
+            .query_collect("SELECT command FROM history", Vec::<Value>::new(), |_| Ok(()))
+            .unwrap_err();
+        assert!(ModelError::is_missing_table(&err), "{err:#}");
+
+        let err = pool.execute("SELEC 1", Vec::<Value>::new()).unwrap_err();
+        assert!(matches!(ModelError::of(&err), Some(ModelError::Query(_))), "{err:#}");
+        assert!(!ModelError::is_missing_table(&err));
+
+        pool.execute("CREATE TABLE t (a INTEGER);", Vec::<Value>::new()).unwrap();
+        let err = pool.execute("ALTER TABLE t ADD COLUMN a INTEGER;", Vec::<Value>::new()).unwrap_err();
+        assert!(matches!(ModelError::of(&err), Some(ModelError::DuplicateColumn(_))), "{err:#}");
+        add_column_if_missing(pool.runtime.as_ref(), &pool.checkout(), "t", "a INTEGER").unwrap();
+    }
+
+    #[test]
+    fn records_each_migration_once() {
+        let pool = SqlitePool::open_memory_migrated().unwrap();
+        let expected: Vec<i64> = MIGRATIONS.iter().map(|m| m.version).collect();
+        assert_eq!(versions(&pool), expected);
+
+        run_migrations(pool.runtime.as_ref(), &pool.checkout()).unwrap();
+        assert_eq!(versions(&pool), expected);
+    }
+
+    #[test]
+    fn migrated_schema_has_cwd_columns() {
+        let pool = SqlitePool::open_memory_migrated().unwrap();
+        pool.execute(
+            "INSERT INTO command_executions (command, cwd) VALUES ('ls', '/tmp');",
+            std::iter::empty::<Value>(),
+        )
+        .unwrap();
+        pool.execute(
+            "INSERT INTO history (command, hash, cwd) VALUES ('ls', 'h', '/tmp');",
+            std::iter::empty::<Value>(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn meta_values_move_out_of_path() {
+        let pool = SqlitePool::open_memory_migrated().unwrap();
+        pool.execute(
+            "INSERT INTO meta (key, path, mtime) VALUES ('tui_input', 'git log', 0), ('shell_history:/h', '/h', 0);",
+            std::iter::empty::<Value>(),
+        )
+        .unwrap();
+        migrate_meta_value_column(pool.runtime.as_ref(), &pool.checkout()).unwrap();
+
+        let rows = pool
+            .query_collect(
+                "SELECT key, path, value FROM meta ORDER BY key",
+                std::iter::empty::<Value>(),
+                |row| {
+                    let value: Option<String> = row.get(2)?;
+                    Ok((row.get::<String>(0)?, row.get::<String>(1)?, value))
+                },
+            )
+            .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                ("shell_history:/h".to_string(), "/h".to_string(), None),
+                ("tui_input".to_string(), String::new(), Some("git log".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn checkout_skips_busy_connections() {
+        let pool = SqlitePool::open_memory().unwrap();
+        let held: Vec<_> = (0..POOL_SIZE - 1).map(|_| pool.checkout()).collect();
+        // One connection is still idle, so this must not block
+        let rows = pool
+            .query_collect("SELECT 1", std::iter::empty::<Value>(), |row| {
+                Ok(row.get::<i64>(0)?)
+            })
+            .unwrap();
+        assert_eq!(rows, vec![1]);
+        drop(held);
+    }
+
+    #[test]
+    fn file_databases_use_wal() {
+        let dir = std::env::temp_dir().join(format!("ghosttype-wal-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let pool = SqlitePool::open_path(dir.join("wal.db")).unwrap();
+
+        let modes = pool
+            .query_collect("PRAGMA journal_mode;", std::iter::empty::<Value>(), |row| {
+                Ok(row.get::<String>(0)?)
+            })
+            .unwrap();
+        assert_eq!(modes, vec!["wal".to_string()]);
+
+        drop(pool);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
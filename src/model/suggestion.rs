@@ -1,12 +1,25 @@
 use std::fmt::Debug;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
 
 use anyhow::Result;
+use serde::{Serialize, Serializer};
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize)]
 pub struct Suggestion {
     pub text: String,
     pub source: Option<String>,
+    #[serde(serialize_with = "serialize_finite")]
     pub score: f64,
+    /// Char indices of `text` that matched the query, for highlighting
+    #[serde(skip)]
+    pub match_indices: Vec<usize>,
+}
+
+/// JSON has no NaN/Infinity, so non-finite scores are emitted as 0
+fn serialize_finite<S: Serializer>(score: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(if score.is_finite() { *score } else { 0.0 })
 }
 
 impl Suggestion {
@@ -15,13 +28,98 @@ impl Suggestion {
             text: text.into(),
             source: Some(source.into()),
             score,
+            match_indices: Vec::new(),
         }
     }
+
+    pub fn with_match_indices(mut self, indices: Vec<usize>) -> Self {
+        self.match_indices = indices;
+        self
+    }
 }
 
+/// Boxed future returned by [`SuggestModel::predict_async`]
+pub type PredictFuture = Pin<Box<dyn Future<Output = Result<Vec<Suggestion>>> + Send>>;
+
 pub trait SuggestModel: Send + Sync + Debug {
     fn predict(&self, input: &str) -> Result<Vec<Suggestion>>;
+
+    /// Deliver suggestions to `sink` in batches as they become available.
+    /// The default runs `predict` and delivers its result as a single batch.
+    fn predict_streaming(
+        &self,
+        input: &str,
+        sink: &mut dyn FnMut(Vec<Suggestion>),
+    ) -> Result<()> {
+        let suggestions = self.predict(input)?;
+        if !suggestions.is_empty() {
+            sink(suggestions);
+        }
+        Ok(())
+    }
+
+    /// Predict without blocking an async worker thread. The default runs
+    /// `predict` on tokio's blocking pool, which suits models that wait on
+    /// SQLite or a subprocess; it must be called from within a tokio runtime.
+    fn predict_async(self: Arc<Self>, input: String) -> PredictFuture
+    where
+        Self: 'static,
+    {
+        Box::pin(async move {
+            tokio::task::spawn_blocking(move || self.predict(&input))
+                .await
+                .map_err(|err| anyhow::anyhow!("predict task failed: {err}"))?
+        })
+    }
+
     fn weight(&self) -> f64 {
         1.0
     }
+
+    /// Short label used in logs and error messages
+    fn name(&self) -> &str {
+        "model"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    struct EchoModel;
+
+    impl SuggestModel for EchoModel {
+        fn predict(&self, input: &str) -> Result<Vec<Suggestion>> {
+            Ok(vec![Suggestion::with_source(input, 1.0, "echo")])
+        }
+    }
+
+    #[test]
+    fn default_streaming_delivers_one_batch() {
+        let mut batches = Vec::new();
+        EchoModel
+            .predict_streaming("ls", &mut |batch| batches.push(batch))
+            .unwrap();
+        assert_eq!(batches, vec![vec![Suggestion::with_source("ls", 1.0, "echo")]]);
+    }
+
+    #[tokio::test]
+    async fn default_async_predict_runs_predict() {
+        let model: Arc<dyn SuggestModel> = Arc::new(EchoModel);
+        let suggestions = model.predict_async("ls".to_string()).await.unwrap();
+        assert_eq!(suggestions, vec![Suggestion::with_source("ls", 1.0, "echo")]);
+    }
+
+    #[test]
+    fn serializes_missing_source_and_non_finite_score() {
+        let suggestion = Suggestion {
+            text: "ls".into(),
+            source: None,
+            score: f64::NAN,
+            match_indices: vec![0],
+        };
+        let json = serde_json::to_string(&suggestion).unwrap();
+        assert_eq!(json, r#"{"text":"ls","source":null,"score":0.0}"#);
+    }
 }
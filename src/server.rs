@@ -0,0 +1,231 @@
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+use directories::BaseDirs;
+use log::{info, warn};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::UnixListener;
+
+use crate::config::{self, Config};
+use crate::core;
+use crate::model::ensemble::Ensemble;
+use crate::model::{ModelRegistry, SqlitePool, Suggestion};
+
+/// `$XDG_RUNTIME_DIR/ghosttype.sock` where available, otherwise the cache
+/// directory that also holds the history database
+pub fn default_socket_path() -> PathBuf {
+    let dirs = BaseDirs::new();
+    let base = dirs
+        .as_ref()
+        .and_then(|d| d.runtime_dir().map(Path::to_path_buf))
+        .or_else(|| dirs.as_ref().map(|d| d.cache_dir().join("ghosttype")))
+        .unwrap_or_else(std::env::temp_dir);
+    base.join("ghosttype.sock")
+}
+
+/// Keep the ensemble warm and answer queries on a Unix socket.
+///
+/// Each request is one line holding the current input; each response is one
+/// line holding a JSON array of up to `top` suggestions. Model settings
+/// (embedding, LLM, weights) come from `config`.
+pub fn run_serve(
+    files: Vec<PathBuf>,
+    socket: PathBuf,
+    top: usize,
+    unique: bool,
+    config: &Config,
+) -> Result<()> {
+    let pool = match SqlitePool::open_default() {
+        Ok(p) => Some(p),
+        Err(err) => {
+            warn!("failed to open sqlite history store: {err:?}");
+            None
+        }
+    };
+    let secrets = config.configured_secret_filter()?;
+    let encoding = config.history_encoding()?;
+    if let Some(ref p) = pool {
+        if let Err(e) = core::import_shell_history_to_db(p, &files, &secrets, encoding) {
+            warn!("failed to import shell history: {e:?}");
+        }
+    }
+
+    let llm_device = config::resolve_llm_device(None, config)?;
+    let corpus = core::load_history_lines(
+        files,
+        unique,
+        config.ignore_case.unwrap_or(false),
+        config.max_corpus,
+        encoding,
+        &secrets,
+    )?;
+    let models = core::ModelOptions {
+        enable_embedding: config.enable_embedding.unwrap_or(true),
+        embedding_model: config.embedding_model.clone(),
+        embedding_search: config.embedding_search(),
+        enable_llm: config.enable_llm.unwrap_or(false),
+        llm_model: config.llm_model.clone(),
+        llm_device,
+        weights: config.weights.clone(),
+        dedup: config.dedup()?,
+        prefix_word_boundary: config.prefix_word_boundary.unwrap_or(false),
+        disabled_models: config.disabled_models.clone(),
+    };
+    let ensemble = Arc::new(core::build_ensemble(
+        corpus.into(),
+        top,
+        pool.as_ref(),
+        &models,
+        ModelRegistry::default(),
+    ));
+
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime.block_on(serve(&socket, ensemble, top))
+}
+
+async fn serve(socket: &Path, ensemble: Arc<Ensemble>, top: usize) -> Result<()> {
+    if let Some(parent) = socket.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("creating socket directory {}", parent.display()))?;
+    }
+    remove_stale_socket(socket)?;
+    let listener = UnixListener::bind(socket)
+        .with_context(|| format!("binding unix socket {}", socket.display()))?;
+    info!("listening on {}", socket.display());
+
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, _) = accepted.context("accepting socket connection")?;
+                let ensemble = ensemble.clone();
+                tokio::spawn(async move {
+                    if let Err(err) = handle_connection(stream, &ensemble, top).await {
+                        warn!("socket connection failed: {err:?}");
+                    }
+                });
+            }
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+
+    std::fs::remove_file(socket).ok();
+    Ok(())
+}
+
+/// Remove a socket file left behind by a previous run, which would make bind
+/// fail. Anything else at `socket`, or a socket another server still answers
+/// on, is left alone.
+fn remove_stale_socket(socket: &Path) -> Result<()> {
+    let metadata = match std::fs::symlink_metadata(socket) {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => {
+            return Err(err).with_context(|| format!("checking socket {}", socket.display()))
+        }
+    };
+    if !metadata.file_type().is_socket() {
+        bail!("{} is already in use by a file that is not a socket", socket.display());
+    }
+    if UnixStream::connect(socket).is_ok() {
+        bail!("{} is already in use by another server", socket.display());
+    }
+    std::fs::remove_file(socket)
+        .with_context(|| format!("removing stale socket {}", socket.display()))
+}
+
+/// Answer newline-delimited queries on one connection until it closes
+async fn handle_connection<S>(stream: S, ensemble: &Ensemble, top: usize) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut lines = BufReader::new(reader).lines();
+
+    while let Some(line) = lines.next_line().await? {
+        let query = line.trim();
+        let suggestions: Vec<Suggestion> = if query.is_empty() {
+            Vec::new()
+        } else {
+            match ensemble.predict_async(query).await {
+                Ok(suggestions) => suggestions.into_iter().take(top).collect(),
+                Err(err) => {
+                    warn!("prediction failed for {query:?}: {err:?}");
+                    Vec::new()
+                }
+            }
+        };
+
+        let mut response = serde_json::to_string(&suggestions)?;
+        response.push('\n');
+        writer.write_all(response.as_bytes()).await?;
+        writer.flush().await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::EnsembleBuilder;
+    use crate::model::SuggestModel;
+
+    #[derive(Debug)]
+    struct PrefixEcho;
+
+    impl SuggestModel for PrefixEcho {
+        fn predict(&self, input: &str) -> Result<Vec<Suggestion>> {
+            Ok(vec![
+                Suggestion::with_source(format!("{input} status"), 2.0, "echo"),
+                Suggestion::with_source(format!("{input} stash"), 1.0, "echo"),
+            ])
+        }
+    }
+
+    #[tokio::test]
+    async fn answers_each_line_with_json_suggestions() {
+        let ensemble = EnsembleBuilder::new().with_light_model(PrefixEcho).build();
+        let (client, server) = tokio::io::duplex(4096);
+        let server = tokio::spawn(async move { handle_connection(server, &ensemble, 1).await });
+
+        let (reader, mut writer) = tokio::io::split(client);
+        writer.write_all(b"git\n\n").await.unwrap();
+        writer.shutdown().await.unwrap();
+
+        let mut lines = BufReader::new(reader).lines();
+        let first = lines.next_line().await.unwrap().unwrap();
+        let value: serde_json::Value = serde_json::from_str(&first).unwrap();
+        assert_eq!(value[0]["text"], "git status");
+        assert_eq!(value.as_array().unwrap().len(), 1);
+        assert_eq!(lines.next_line().await.unwrap().unwrap(), "[]");
+
+        server.await.unwrap().unwrap();
+    }
+
+    #[test]
+    fn only_stale_sockets_are_removed() {
+        let dir = std::env::temp_dir().join(format!("ghosttype-socket-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let socket = dir.join("ghosttype.sock");
+
+        remove_stale_socket(&socket).unwrap();
+
+        std::fs::write(&socket, "not a socket").unwrap();
+        assert!(remove_stale_socket(&socket).is_err());
+        assert!(socket.exists());
+        std::fs::remove_file(&socket).unwrap();
+
+        let live = std::os::unix::net::UnixListener::bind(&socket).unwrap();
+        assert!(remove_stale_socket(&socket).is_err());
+        assert!(socket.exists());
+
+        drop(live);
+        remove_stale_socket(&socket).unwrap();
+        assert!(!socket.exists());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
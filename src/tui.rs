@@ -1,9 +1,11 @@
+use crate::clipboard::copy_to_clipboard;
 use crate::core;
-use crate::model::SqlitePool;
+use crate::model::{SqlitePool, Suggestion};
+use crate::secrets::SecretFilter;
 use anyhow::Result;
 use crossterm::event::{
-    self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind, KeyModifiers,
-    MouseEvent, MouseEventKind,
+    self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture,
+    Event, KeyCode, KeyEventKind, KeyModifiers, MouseEvent, MouseEventKind,
 };
 use crossterm::terminal::{
     disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
@@ -12,14 +14,19 @@ use crossterm::ExecutableCommand;
 use log::warn;
 use ratatui::backend::CrosstermBackend;
 use ratatui::layout::{Constraint, Direction, Layout, Rect};
-use ratatui::style::{Modifier, Style};
+use ratatui::style::{Color, Modifier, Style};
 use ratatui::text::{Line, Span};
-use ratatui::widgets::{Block, Borders, Clear, List, ListItem, Paragraph};
-use ratatui::{Frame, Terminal};
+use ratatui::widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{Frame, Terminal, TerminalOptions, Viewport};
 use std::borrow::Cow;
-use std::io::Stdout;
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read, Stdout};
 use std::path::PathBuf;
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc;
 use std::time::{Duration, Instant};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 pub enum KeyResult {
     Continue,
@@ -27,27 +34,43 @@ pub enum KeyResult {
     RunCommand(String),
 }
 
+/// Everything the TUI session takes from its flags and the config file,
+/// resolved once in `main`
+#[derive(Clone, Debug)]
+pub struct TuiOptions {
+    /// History files to load into the fuzzy corpus and import
+    pub files: Vec<PathBuf>,
+    pub unique: bool,
+    pub ignore_case: bool,
+    pub max_corpus: Option<usize>,
+    pub encoding: core::HistoryEncoding,
+    /// Skip the history database entirely
+    pub no_db: bool,
+    /// Rows to draw below the prompt; `None` takes over the screen
+    pub inline: Option<u16>,
+    pub app: core::AppOptions,
+}
+
 pub fn run_tui(
-    files: Vec<PathBuf>,
-    top: usize,
-    unique: bool,
+    options: &TuiOptions,
     pool: Option<SqlitePool>,
-    enable_embedding: bool,
-    embedding_model: Option<PathBuf>,
-    enable_llm: bool,
-    llm_model: Option<PathBuf>,
     initial_input: Option<String>,
-) -> Result<(Option<String>, String)> {
-    let corpus = core::load_history_lines(files, unique)?;
-    let mut app = core::App::new(
-        corpus,
-        top,
-        pool,
-        enable_embedding,
-        embedding_model,
-        enable_llm,
-        llm_model,
+    session_id: &str,
+) -> Result<String> {
+    let corpus = core::load_history_lines(
+        options.files.clone(),
+        options.unique,
+        options.ignore_case,
+        options.max_corpus,
+        options.encoding,
+        &options.app.secrets,
     )?;
+    let recorder = RunRecorder {
+        pool: pool.as_ref(),
+        session_id,
+        secrets: &options.app.secrets,
+    };
+    let mut app = core::App::new(corpus, pool.clone(), &options.app)?;
 
     // Restore any previously retained input
     if let Some(initial_input) = initial_input {
@@ -60,14 +83,20 @@ pub fn run_tui(
 
     enable_raw_mode()?;
     let mut stdout = std::io::stdout();
-    stdout.execute(EnterAlternateScreen)?;
+    // Inline mode draws below the prompt and leaves the scrollback alone
+    let inline = options.inline;
+    if inline.is_none() {
+        stdout.execute(EnterAlternateScreen)?;
+    }
     stdout.execute(EnableMouseCapture)?;
+    stdout.execute(EnableBracketedPaste)?;
     let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+    let viewport = inline.map_or(Viewport::Fullscreen, Viewport::Inline);
+    let mut terminal = Terminal::with_options(backend, TerminalOptions { viewport })?;
 
     let tick_rate = Duration::from_millis(33);
     let mut last_tick = Instant::now();
-    let mut command_to_run: Option<String> = None;
+    let mut running: Option<RunningCommand> = None;
 
     loop {
         terminal.draw(|f| ui(f, &mut app)).ok();
@@ -80,18 +109,32 @@ pub fn run_tui(
                 Event::Key(key) if key.kind == KeyEventKind::Press => {
                     let result = handle_key(key.code, key.modifiers, &mut app)?;
                     match result {
-                        KeyResult::Quit => should_quit = true,
-                        KeyResult::RunCommand(cmd) => {
-                            command_to_run = Some(cmd);
-                            break;
-                        }
+                        // Ctrl+C and Esc stop a running command before they quit
+                        KeyResult::Quit => match running.as_mut() {
+                            Some(run) => {
+                                if let Err(err) = run.stop() {
+                                    warn!("failed to stop {:?}: {err}", run.command);
+                                }
+                            }
+                            None => should_quit = true,
+                        },
+                        KeyResult::RunCommand(cmd) => start_command(&mut app, &mut running, cmd),
                         KeyResult::Continue => {}
                     }
                 }
                 Event::Mouse(mev) => {
-                    handle_mouse(mev, &mut app);
+                    if let KeyResult::RunCommand(cmd) = handle_mouse(mev, &mut app) {
+                        start_command(&mut app, &mut running, cmd);
+                    }
+                }
+                Event::Paste(text) => {
+                    handle_paste(&text, &mut app);
+                }
+                Event::Resize(_, height) => {
+                    handle_resize(inline.map_or(height, |rows| rows.min(height)), &mut app);
+                    // Drop the old buffer so the next draw repaints every cell
+                    terminal.clear()?;
                 }
-                Event::Resize(_, _) => {}
                 _ => {}
             }
         }
@@ -104,20 +147,47 @@ pub fn run_tui(
         // Poll for heavy model results (non-blocking)
         app.poll_heavy_model_results();
 
+        if let Some(run) = running.as_mut() {
+            if let Some(exit_code) = run.poll(&mut app)? {
+                app.is_running = false;
+                recorder.record(&mut app, run, exit_code);
+                running = None;
+            }
+        }
+
         if should_quit {
             break;
         }
         if last_tick.elapsed() >= tick_rate {
             last_tick = Instant::now();
+            app.spinner_frame = app.spinner_frame.wrapping_add(1);
         }
     }
 
+    app.save_view();
+    if inline.is_some() {
+        // Wipe the rows drawn into, leaving the cursor where the TUI started
+        terminal.clear()?;
+        terminal.show_cursor()?;
+    }
     let mut stdout: Stdout = std::io::stdout();
     disable_raw_mode()?;
-    stdout.execute(LeaveAlternateScreen)?;
+    if inline.is_none() {
+        stdout.execute(LeaveAlternateScreen)?;
+    }
     stdout.execute(DisableMouseCapture)?;
-    let final_input = app.input.clone();
-    Ok((command_to_run, final_input))
+    stdout.execute(DisableBracketedPaste)?;
+    Ok(app.input.clone())
+}
+
+/// Run `command`, or hold it for confirmation if it looks destructive
+fn run_or_confirm(app: &mut core::App, command: String) -> KeyResult {
+    if app.dangerous.is_match(&command) {
+        app.pending_run = Some(command);
+        KeyResult::Continue
+    } else {
+        KeyResult::RunCommand(command)
+    }
 }
 
 pub fn handle_key(
@@ -125,8 +195,69 @@ pub fn handle_key(
     mods: KeyModifiers,
     app: &mut core::App,
 ) -> Result<KeyResult> {
+    // Only `y` runs a command held for confirmation; any other key drops it
+    if let Some(command) = app.pending_run.take() {
+        if matches!(code, KeyCode::Char('y' | 'Y')) && !mods.contains(KeyModifiers::CONTROL) {
+            return Ok(KeyResult::RunCommand(command));
+        }
+        app.set_status("not run");
+        return Ok(KeyResult::Continue);
+    }
+
+    if app.show_models && handle_models_key(code, mods, app) {
+        return Ok(KeyResult::Continue);
+    }
+
+    let is_history_delete = app.current_tab == core::Tab::History
+        && !app.history_filter_active
+        && !app.history_search_active
+        && matches!(code, KeyCode::Char('d') | KeyCode::Delete);
+    if !is_history_delete {
+        app.pending_history_delete = None;
+    }
+
+    if app.current_tab == core::Tab::History
+        && app.history_filter_active
+        && handle_history_filter_key(code, mods, app)
+    {
+        return Ok(KeyResult::Continue);
+    }
+
+    if app.current_tab == core::Tab::History
+        && app.history_search_active
+        && handle_history_search_key(code, mods, app)
+    {
+        return Ok(KeyResult::Continue);
+    }
+
+    if app.current_tab == core::Tab::Main
+        && app.edit_mode == core::EditMode::Normal
+        && handle_normal_mode_key(code, mods, app)
+    {
+        return Ok(KeyResult::Continue);
+    }
+
     match (code, mods) {
         (KeyCode::Char('c'), KeyModifiers::CONTROL) => return Ok(KeyResult::Quit),
+        // Escape clears an output search, then an applied history filter, before it quits
+        (KeyCode::Esc, _)
+            if app.current_tab == core::Tab::History && !app.history_search.is_empty() =>
+        {
+            app.history_search.clear();
+        }
+        (KeyCode::Esc, _)
+            if app.current_tab == core::Tab::History && !app.history_filter.is_empty() =>
+        {
+            app.history_filter.clear();
+            app.snap_history_selection();
+        }
+        (KeyCode::Esc, _)
+            if app.vim
+                && app.current_tab == core::Tab::Main
+                && app.edit_mode == core::EditMode::Insert =>
+        {
+            app.edit_mode = core::EditMode::Normal;
+        }
         (KeyCode::Esc, _) => return Ok(KeyResult::Quit),
 
         // Tab switching: Ctrl+Tab to toggle between tabs
@@ -137,14 +268,49 @@ pub fn handle_key(
             };
         }
 
+        (KeyCode::Char('p'), KeyModifiers::CONTROL) if app.current_tab == core::Tab::Main => {
+            app.show_preview = !app.show_preview;
+        }
+
+        (KeyCode::Char('o'), KeyModifiers::CONTROL) => {
+            app.show_models = true;
+            app.models_selected = 0;
+        }
+
+        // Copy the selected suggestion (or the input line) instead of running it
+        (KeyCode::Char('y'), KeyModifiers::CONTROL) => {
+            let text = match app.current_tab {
+                core::Tab::Main => app
+                    .suggestions
+                    .get(app.selected)
+                    .map(|s| s.text.clone())
+                    .unwrap_or_else(|| app.input.clone()),
+                core::Tab::History => app
+                    .history
+                    .get(app.selected_history_index)
+                    .map(|h| h.cmd.clone())
+                    .unwrap_or_default(),
+            };
+            if !text.trim().is_empty() {
+                match copy_to_clipboard(&text) {
+                    Ok(()) => app.set_status("copied"),
+                    Err(err) => {
+                        warn!("clipboard copy failed: {err:?}");
+                        app.set_status("copy failed");
+                    }
+                }
+            }
+        }
+
         (KeyCode::Up, _) => {
             match app.current_tab {
                 core::Tab::Main => {
                     app.selected = app.selected.saturating_sub(1);
                 }
                 core::Tab::History => {
-                    if app.selected_history_index + 1 < app.history.len() {
-                        app.selected_history_index += 1;
+                    let visible = app.filtered_history_indices();
+                    if let Some(&older) = visible.iter().find(|&&i| i > app.selected_history_index) {
+                        app.selected_history_index = older;
                     }
                     app.history_scroll = 0; // Reset scroll when changing selection
                 }
@@ -158,7 +324,10 @@ pub fn handle_key(
                     }
                 }
                 core::Tab::History => {
-                    app.selected_history_index = app.selected_history_index.saturating_sub(1);
+                    let visible = app.filtered_history_indices();
+                    if let Some(&newer) = visible.iter().rev().find(|&&i| i < app.selected_history_index) {
+                        app.selected_history_index = newer;
+                    }
                     app.history_scroll = 0; // Reset scroll when changing selection
                 }
             }
@@ -194,28 +363,114 @@ pub fn handle_key(
             }
         }
 
+        (KeyCode::Char('/'), KeyModifiers::NONE) if app.current_tab == core::Tab::History => {
+            app.history_filter_active = true;
+        }
+
+        // Search the selected command's output; n/N step through the hits
+        (KeyCode::Char('f'), KeyModifiers::CONTROL) if app.current_tab == core::Tab::History => {
+            app.history_search.clear();
+            app.history_search_active = true;
+        }
+        (KeyCode::Char('n'), KeyModifiers::NONE)
+            if app.current_tab == core::Tab::History && !app.history_search.is_empty() =>
+        {
+            app.jump_to_history_match(1);
+        }
+        (KeyCode::Char('N'), KeyModifiers::NONE | KeyModifiers::SHIFT)
+            if app.current_tab == core::Tab::History && !app.history_search.is_empty() =>
+        {
+            app.jump_to_history_match(-1);
+        }
+
+        // Delete the selected history entry; the first press only arms it
+        (KeyCode::Char('d') | KeyCode::Delete, KeyModifiers::NONE)
+            if app.current_tab == core::Tab::History =>
+        {
+            if let Some(cmd) = app.history.get(app.selected_history_index).map(|h| h.cmd.clone()) {
+                if app.pending_history_delete.as_deref() == Some(cmd.as_str()) {
+                    app.pending_history_delete = None;
+                    match app.delete_selected_history() {
+                        Ok(()) => app.set_status("deleted"),
+                        Err(err) => {
+                            warn!("failed to delete history entry: {err:?}");
+                            app.set_status("delete failed");
+                        }
+                    }
+                } else {
+                    app.pending_history_delete = Some(cmd);
+                    app.set_status("press d again to delete");
+                }
+            }
+        }
+
+        // Ctrl+Y already copies, so redo is Ctrl+Shift+Z, or Alt+Z where the
+        // terminal reports Ctrl+Shift+Z as plain Ctrl+Z
+        (KeyCode::Char('z'), KeyModifiers::CONTROL) if app.current_tab == core::Tab::Main => {
+            app.undo();
+        }
+        (KeyCode::Char('z' | 'Z'), m)
+            if app.current_tab == core::Tab::Main
+                && (m == KeyModifiers::CONTROL | KeyModifiers::SHIFT || m == KeyModifiers::ALT) =>
+        {
+            app.redo();
+        }
+
+        (KeyCode::Left, KeyModifiers::CONTROL) if app.current_tab == core::Tab::Main => {
+            app.cursor = prev_word_boundary(&app.input, app.cursor);
+        }
+        (KeyCode::Right, KeyModifiers::CONTROL) if app.current_tab == core::Tab::Main => {
+            app.cursor = next_word_boundary(&app.input, app.cursor);
+        }
+        // Readline-style kills: word before the cursor, to line start, to line end
+        (KeyCode::Char('w'), KeyModifiers::CONTROL) if app.current_tab == core::Tab::Main && app.cursor > 0 => {
+            app.record_undo(false);
+            let start = prev_word_boundary(&app.input, app.cursor);
+            app.input.replace_range(start..app.cursor, "");
+            app.cursor = start;
+            app.clear_output(); // Clear output when typing
+            app.output_scroll = 0; // Reset scroll
+            app.mark_input_changed(); // Debounced refresh
+        }
+        (KeyCode::Char('u'), KeyModifiers::CONTROL) if app.current_tab == core::Tab::Main && app.cursor > 0 => {
+            app.record_undo(false);
+            app.input.replace_range(..app.cursor, "");
+            app.cursor = 0;
+            app.clear_output(); // Clear output when typing
+            app.output_scroll = 0; // Reset scroll
+            app.mark_input_changed(); // Debounced refresh
+        }
+        (KeyCode::Char('k'), KeyModifiers::CONTROL)
+            if app.current_tab == core::Tab::Main && app.cursor < app.input.len() =>
+        {
+            app.record_undo(false);
+            app.input.truncate(app.cursor);
+            app.clear_output(); // Clear output when typing
+            app.output_scroll = 0; // Reset scroll
+            app.mark_input_changed(); // Debounced refresh
+        }
         (KeyCode::Left, _) if app.current_tab == core::Tab::Main => {
-            app.cursor = app.cursor.saturating_sub(1);
+            app.cursor = prev_char_boundary(&app.input, app.cursor);
         }
         (KeyCode::Right, _) if app.current_tab == core::Tab::Main => {
-            app.cursor = (app.cursor + 1).min(app.input.len());
-        }
-        (KeyCode::Backspace, _) if app.current_tab == core::Tab::Main => {
-            if app.cursor > 0 {
-                app.input.remove(app.cursor - 1);
-                app.cursor -= 1;
-                app.output_lines.clear(); // Clear output when typing
-                app.output_scroll = 0; // Reset scroll
-                app.mark_input_changed(); // Debounced refresh
-            }
+            app.cursor = next_char_boundary(&app.input, app.cursor);
         }
-        (KeyCode::Delete, _) if app.current_tab == core::Tab::Main => {
-            if app.cursor < app.input.len() {
-                app.input.remove(app.cursor);
-                app.output_lines.clear(); // Clear output when typing
-                app.output_scroll = 0; // Reset scroll
-                app.mark_input_changed(); // Debounced refresh
-            }
+        (KeyCode::Backspace, _) if app.current_tab == core::Tab::Main && app.cursor > 0 => {
+            app.record_undo(false);
+            app.cursor = prev_char_boundary(&app.input, app.cursor);
+            app.input.remove(app.cursor);
+            app.clear_output(); // Clear output when typing
+            app.output_scroll = 0; // Reset scroll
+            app.mark_input_changed(); // Debounced refresh
+        }
+        (KeyCode::Delete, _)
+            if app.current_tab == core::Tab::Main && app.cursor < app.input.len() =>
+        {
+            app.record_undo(false);
+            app.input.remove(app.cursor);
+            app.clear_output(); // Clear output when typing
+            app.output_scroll = 0; // Reset scroll
+            app.mark_input_changed(); // Debounced refresh
         }
         (KeyCode::Home, _) if app.current_tab == core::Tab::Main => {
             app.cursor = 0;
@@ -230,14 +485,33 @@ pub fn handle_key(
             app.cursor = app.input.len(); // Move cursor to end (simulates select all) - Mac Cmd+A
         }
         (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) if app.current_tab == core::Tab::Main => {
+            app.record_undo(true);
             app.input.insert(app.cursor, c);
-            app.cursor += 1;
-            app.output_lines.clear(); // Clear output when typing
+            app.cursor += c.len_utf8();
+            app.continue_typing();
+            app.clear_output(); // Clear output when typing
             app.output_scroll = 0; // Reset scroll
             app.mark_input_changed(); // Debounced refresh
         }
         (KeyCode::Tab, _) if app.current_tab == core::Tab::Main => {
-            if let Some(sel) = app.suggestions.get(app.selected).cloned() {
+            if let Some(sel) = app.suggestions.get(app.selected).map(|s| s.text.clone()) {
+                app.record_acceptance(&sel);
+                app.record_undo(false);
+                app.input = sel;
+                // Land on the first placeholder to fill in, if there is one
+                app.cursor = app.placeholders.first_in(&app.input).unwrap_or(app.input.len());
+                app.mark_input_changed(); // Debounced refresh
+            }
+        }
+
+        // Shift/Alt+Enter loads the suggestion for editing instead of running it
+        (KeyCode::Enter, m)
+            if app.current_tab == core::Tab::Main
+                && m.intersects(KeyModifiers::SHIFT | KeyModifiers::ALT) =>
+        {
+            if let Some(sel) = app.suggestions.get(app.selected).map(|s| s.text.clone()) {
+                app.record_acceptance(&sel);
+                app.record_undo(false);
                 app.input = sel;
                 app.cursor = app.input.len();
                 app.mark_input_changed(); // Debounced refresh
@@ -245,15 +519,16 @@ pub fn handle_key(
         }
 
         (KeyCode::Enter, _) if app.current_tab == core::Tab::Main => {
-            let to_run = if let Some(sel) = app.suggestions.get(app.selected) {
-                sel.clone()
+            let to_run = if let Some(sel) = app.suggestions.get(app.selected).map(|s| s.text.clone()) {
+                app.record_acceptance(&sel);
+                sel
             } else {
                 app.input.clone()
             };
             if to_run.trim().is_empty() {
                 return Ok(KeyResult::Continue);
             }
-            return Ok(KeyResult::RunCommand(to_run));
+            return Ok(run_or_confirm(app, to_run));
         }
 
         _ => {}
@@ -261,13 +536,222 @@ pub fn handle_key(
     Ok(KeyResult::Continue)
 }
 
-fn handle_mouse(mev: MouseEvent, app: &mut core::App) {
+// Rows taken by the tab bar, the input box, and a block's top/bottom borders,
+// mirroring the layouts in `ui_main_tab` and `ui_history_tab`
+const TAB_BAR_ROWS: u16 = 1;
+const INPUT_ROWS: u16 = 3;
+const BORDER_ROWS: u16 = 2;
+const PREVIEW_ROWS: u16 = 6;
+
+fn preview_rows(app: &core::App) -> u16 {
+    if app.show_preview {
+        PREVIEW_ROWS
+    } else {
+        0
+    }
+}
+
+/// Clamp scroll offsets to what fits in a terminal `height` rows tall, so
+/// nothing renders past the end after a shrink. The selection is kept; the
+/// suggestion list scrolls to show it.
+fn handle_resize(height: u16, app: &mut core::App) {
+    let main_rows = height
+        .saturating_sub(TAB_BAR_ROWS + INPUT_ROWS + BORDER_ROWS + preview_rows(app))
+        as usize;
+    let history_rows = height.saturating_sub(TAB_BAR_ROWS + BORDER_ROWS) as usize;
+
+    let max_output_scroll = app.output_lines.len().saturating_sub(main_rows);
+    app.output_scroll = app.output_scroll.min(clamp_u16(max_output_scroll));
+
+    let history_lines = app
+        .history
+        .get(app.selected_history_index)
+        .map(|entry| entry.output_lines.len())
+        .unwrap_or(0);
+    app.history_scroll = app
+        .history_scroll
+        .min(clamp_u16(history_lines.saturating_sub(history_rows)));
+}
+
+fn clamp_u16(value: usize) -> u16 {
+    value.min(u16::MAX as usize) as u16
+}
+
+/// Insert a bracketed paste at the cursor in one edit, so the whole string
+/// triggers a single debounced refresh instead of one per char
+fn handle_paste(text: &str, app: &mut core::App) {
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    if text.is_empty() {
+        return;
+    }
+    match app.current_tab {
+        core::Tab::Main => {
+            app.record_undo(false);
+            app.input.insert_str(app.cursor, &text);
+            app.cursor += text.len();
+            app.clear_output(); // Clear output when typing
+            app.output_scroll = 0; // Reset scroll
+            app.mark_input_changed(); // Debounced refresh
+        }
+        core::Tab::History if app.history_filter_active => {
+            app.history_filter.push_str(text.trim_end_matches('\n'));
+            app.snap_history_selection();
+        }
+        core::Tab::History if app.history_search_active => {
+            app.history_search.push_str(text.trim_end_matches('\n'));
+            app.history_search_match = 0;
+            app.jump_to_history_match(0);
+        }
+        core::Tab::History => {}
+    }
+}
+
+/// Edit the History-tab filter while it has focus. Returns false for keys the
+/// filter does not consume (navigation, Ctrl shortcuts) so they apply as usual.
+fn handle_history_filter_key(code: KeyCode, mods: KeyModifiers, app: &mut core::App) -> bool {
+    match (code, mods) {
+        (KeyCode::Esc, _) => {
+            app.history_filter.clear();
+            app.history_filter_active = false;
+        }
+        (KeyCode::Enter, _) => {
+            app.history_filter_active = false;
+        }
+        (KeyCode::Backspace, _) => {
+            app.history_filter.pop();
+        }
+        (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+            app.history_filter.push(c);
+        }
+        _ => return false,
+    }
+    app.snap_history_selection();
+    true
+}
+
+/// Edit the History output search while it has focus, jumping to the first
+/// hit as it changes. Returns false for keys the search does not consume.
+fn handle_history_search_key(code: KeyCode, mods: KeyModifiers, app: &mut core::App) -> bool {
+    match (code, mods) {
+        (KeyCode::Esc, _) => {
+            app.history_search.clear();
+            app.history_search_active = false;
+            return true;
+        }
+        (KeyCode::Enter, _) => {
+            app.history_search_active = false;
+            return true;
+        }
+        (KeyCode::Backspace, _) => {
+            app.history_search.pop();
+        }
+        (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+            app.history_search.push(c);
+        }
+        _ => return false,
+    }
+    app.history_search_match = 0;
+    app.jump_to_history_match(0);
+    true
+}
+
+/// Move through and toggle the models in the overlay opened with Ctrl+O.
+/// Every key but Ctrl+C stays in the overlay; returns false for Ctrl+C.
+fn handle_models_key(code: KeyCode, mods: KeyModifiers, app: &mut core::App) -> bool {
+    match (code, mods) {
+        (KeyCode::Char('c'), KeyModifiers::CONTROL) => return false,
+        (KeyCode::Esc, _) | (KeyCode::Char('o'), KeyModifiers::CONTROL) => app.show_models = false,
+        (KeyCode::Up, _) | (KeyCode::Char('k'), KeyModifiers::NONE) => {
+            app.models_selected = app.models_selected.saturating_sub(1);
+        }
+        (KeyCode::Down, _) | (KeyCode::Char('j'), KeyModifiers::NONE)
+            if app.models_selected + 1 < app.ensemble.model_states().len() =>
+        {
+            app.models_selected += 1;
+        }
+        (KeyCode::Char(' ') | KeyCode::Enter, _) => app.toggle_selected_model(),
+        _ => {}
+    }
+    true
+}
+
+/// Vim normal mode on the Main tab: j/k move the selection, g/G jump to the
+/// first/last suggestion and i returns to insert mode. Other plain chars are
+/// swallowed rather than typed; returns false for keys handled as usual.
+fn handle_normal_mode_key(code: KeyCode, mods: KeyModifiers, app: &mut core::App) -> bool {
+    let KeyCode::Char(c) = code else {
+        return false;
+    };
+    if !matches!(mods, KeyModifiers::NONE | KeyModifiers::SHIFT) {
+        return false;
+    }
+    match c {
+        'j' if app.selected + 1 < app.suggestions.len() => app.selected += 1,
+        'k' => app.selected = app.selected.saturating_sub(1),
+        'g' => app.selected = 0,
+        'G' => app.selected = app.suggestions.len().saturating_sub(1),
+        'i' => app.edit_mode = core::EditMode::Insert,
+        _ => {}
+    }
+    true
+}
+
+/// Byte offset of the char boundary before `cursor` (the cursor is a byte index into `text`)
+fn prev_char_boundary(text: &str, cursor: usize) -> usize {
+    text[..cursor.min(text.len())]
+        .char_indices()
+        .next_back()
+        .map(|(idx, _)| idx)
+        .unwrap_or(0)
+}
+
+/// Byte offset of the char boundary after `cursor`, clamped to the end of `text`
+fn next_char_boundary(text: &str, cursor: usize) -> usize {
+    text[cursor.min(text.len())..]
+        .chars()
+        .next()
+        .map(|ch| cursor + ch.len_utf8())
+        .unwrap_or(text.len())
+}
+
+/// Byte offset of the start of the whitespace-delimited word before `cursor`
+fn prev_word_boundary(text: &str, cursor: usize) -> usize {
+    let before = &text[..cursor.min(text.len())];
+    let word_end = before.trim_end_matches(char::is_whitespace).len();
+    before[..word_end]
+        .char_indices()
+        .rev()
+        .find(|(_, ch)| ch.is_whitespace())
+        .map(|(idx, ch)| idx + ch.len_utf8())
+        .unwrap_or(0)
+}
+
+/// Byte offset just past the end of the whitespace-delimited word after `cursor`
+fn next_word_boundary(text: &str, cursor: usize) -> usize {
+    let start = cursor.min(text.len());
+    let after = &text[start..];
+    let word_start = after.len() - after.trim_start_matches(char::is_whitespace).len();
+    after[word_start..]
+        .char_indices()
+        .find(|(_, ch)| ch.is_whitespace())
+        .map(|(idx, _)| start + word_start + idx)
+        .unwrap_or(text.len())
+}
+
+/// Two clicks on the same suggestion within this window run it
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(400);
+
+fn handle_mouse(mev: MouseEvent, app: &mut core::App) -> KeyResult {
+    // The overlays take their answers from the keyboard
+    if app.pending_run.is_some() || app.show_models {
+        return KeyResult::Continue;
+    }
     if let MouseEventKind::Down(_) = mev.kind {
         // Check if clicking on Main tab
         if let Some(area) = app.main_tab_area {
             if point_in_rect(mev.column, mev.row, area) {
                 app.current_tab = core::Tab::Main;
-                return;
+                return KeyResult::Continue;
             }
         }
 
@@ -275,7 +759,26 @@ fn handle_mouse(mev: MouseEvent, app: &mut core::App) {
         if let Some(area) = app.history_tab_area {
             if point_in_rect(mev.column, mev.row, area) {
                 app.current_tab = core::Tab::History;
-                return;
+                return KeyResult::Continue;
+            }
+        }
+
+        // Click selects a suggestion; a second click on it runs it like Enter
+        if app.current_tab == core::Tab::Main {
+            if let Some(index) = suggestion_at(mev.column, mev.row, app) {
+                let now = Instant::now();
+                let double = app.last_suggestion_click.is_some_and(|(last, at)| {
+                    last == index && now.duration_since(at) <= DOUBLE_CLICK_INTERVAL
+                });
+                app.selected = index;
+                if double {
+                    app.last_suggestion_click = None;
+                    let command = app.suggestions[index].text.clone();
+                    app.record_acceptance(&command);
+                    return run_or_confirm(app, command);
+                }
+                app.last_suggestion_click = Some((index, now));
+                return KeyResult::Continue;
             }
         }
 
@@ -286,6 +789,85 @@ fn handle_mouse(mev: MouseEvent, app: &mut core::App) {
             }
         }
     }
+
+    if let MouseEventKind::ScrollUp | MouseEventKind::ScrollDown = mev.kind {
+        handle_scroll(mev, app);
+    }
+    KeyResult::Continue
+}
+
+/// Index of the suggestion drawn at screen position (`x`, `y`), counting the
+/// list's top border and the extra rows of multi-line suggestions
+fn suggestion_at(x: u16, y: u16, app: &core::App) -> Option<usize> {
+    let area = app.suggestions_area?;
+    let inner = Rect {
+        x: area.x + 1,
+        y: area.y + 1,
+        width: area.width.saturating_sub(BORDER_ROWS),
+        height: area.height.saturating_sub(BORDER_ROWS),
+    };
+    if !point_in_rect(x, y, inner) {
+        return None;
+    }
+
+    let mut row = (y - inner.y) as usize;
+    let visible = app.suggestions.iter().enumerate().skip(app.suggestions_scroll);
+    for (index, suggestion) in visible {
+        let height = normalized_command_for_display(&suggestion.text)
+            .split('\n')
+            .count();
+        if row < height {
+            return Some(index);
+        }
+        row -= height;
+    }
+    None
+}
+
+/// Lines moved per wheel notch when scrolling output
+const MOUSE_SCROLL_LINES: u16 = 3;
+
+/// Scroll whichever output pane is showing, or move the selection while the
+/// pointer is over the suggestion list, without going past the content
+fn handle_scroll(mev: MouseEvent, app: &mut core::App) {
+    let down = mev.kind == MouseEventKind::ScrollDown;
+    let scroll = |offset: u16, lines: usize, area: Option<Rect>| {
+        let rows = area.map_or(0, |a| a.height.saturating_sub(BORDER_ROWS)) as usize;
+        let max = clamp_u16(lines.saturating_sub(rows));
+        if down {
+            offset.saturating_add(MOUSE_SCROLL_LINES).min(max)
+        } else {
+            offset.saturating_sub(MOUSE_SCROLL_LINES)
+        }
+    };
+
+    match app.current_tab {
+        core::Tab::Main if app.is_running || !app.output_lines.is_empty() => {
+            app.output_scroll = scroll(app.output_scroll, app.output_lines.len(), app.content_area);
+        }
+        core::Tab::Main => {
+            let hovering = app
+                .content_area
+                .is_some_and(|area| point_in_rect(mev.column, mev.row, area));
+            if !hovering {
+                return;
+            }
+            if down {
+                if app.selected + 1 < app.suggestions.len() {
+                    app.selected += 1;
+                }
+            } else {
+                app.selected = app.selected.saturating_sub(1);
+            }
+        }
+        core::Tab::History => {
+            let lines = app
+                .history
+                .get(app.selected_history_index)
+                .map_or(0, |entry| entry.output_lines.len());
+            app.history_scroll = scroll(app.history_scroll, lines, app.history_output_area);
+        }
+    }
 }
 
 fn point_in_rect(x: u16, y: u16, r: Rect) -> bool {
@@ -293,7 +875,7 @@ fn point_in_rect(x: u16, y: u16, r: Rect) -> bool {
 }
 
 fn normalized_command_for_display<'a>(text: &'a str) -> Cow<'a, str> {
-    let trimmed_ws = text.trim_end_matches(|c| c == ' ' || c == '\t');
+    let trimmed_ws = text.trim_end_matches([' ', '\t']);
     if trimmed_ws.ends_with('\\') {
         Cow::Owned(trimmed_ws[..trimmed_ws.len().saturating_sub(1)].to_string())
     } else if trimmed_ws.len() != text.len() {
@@ -321,6 +903,48 @@ fn format_command_lines_for_display(text: &str) -> Vec<Line<'static>> {
     lines_from_display_text(normalized.as_ref())
 }
 
+/// Like `format_command_lines_for_display`, but styles the chars at
+/// `match_indices` (char offsets into `text`) so fuzzy matches stand out
+fn highlighted_command_lines(text: &str, match_indices: &[usize]) -> Vec<Line<'static>> {
+    if match_indices.is_empty() {
+        return format_command_lines_for_display(text);
+    }
+    let highlight = Style::default()
+        .fg(Color::Yellow)
+        .add_modifier(Modifier::BOLD);
+    let normalized = normalized_command_for_display(text);
+
+    let mut lines = Vec::new();
+    let mut char_idx = 0;
+    for segment in normalized.split('\n') {
+        if segment.trim().is_empty() {
+            lines.push(Line::from(" "));
+            char_idx += segment.chars().count() + 1;
+            continue;
+        }
+        let mut spans: Vec<Span<'static>> = Vec::new();
+        let mut run = String::new();
+        let mut run_matched = false;
+        for ch in segment.chars() {
+            let matched = match_indices.contains(&char_idx);
+            if matched != run_matched && !run.is_empty() {
+                let style = if run_matched { highlight } else { Style::default() };
+                spans.push(Span::styled(std::mem::take(&mut run), style));
+            }
+            run_matched = matched;
+            run.push(ch);
+            char_idx += 1;
+        }
+        if !run.is_empty() {
+            let style = if run_matched { highlight } else { Style::default() };
+            spans.push(Span::styled(run, style));
+        }
+        lines.push(Line::from(spans));
+        char_idx += 1; // the '\n' separator
+    }
+    lines
+}
+
 fn input_lines_with_cursor(text: &str, cursor: usize) -> Vec<Line<'static>> {
     let highlight = Style::default().add_modifier(Modifier::REVERSED);
 
@@ -343,14 +967,15 @@ fn input_lines_with_cursor(text: &str, cursor: usize) -> Vec<Line<'static>> {
                 spans.push(Span::raw(left.to_string()));
             }
 
-            let mut rest_chars = rest.chars();
-            if let Some(cursor_char) = rest_chars.next() {
-                let cursor_str = cursor_char.to_string();
-                spans.push(Span::styled(cursor_str, highlight));
-                let remaining = rest_chars.as_str();
-                if !remaining.is_empty() {
-                    spans.push(Span::raw(remaining.to_string()));
-                }
+            // Highlight the whole cell: the cursor char plus any zero-width
+            // combining marks that render on top of it
+            let cell_len = cursor_cell_len(rest);
+            let (cursor_str, remaining) = rest.split_at(cell_len);
+            if !cursor_str.is_empty() {
+                spans.push(Span::styled(cursor_str.to_string(), highlight));
+            }
+            if !remaining.is_empty() {
+                spans.push(Span::raw(remaining.to_string()));
             }
         } else if raw_line.is_empty() {
             spans.push(Span::raw(" ".to_string()));
@@ -365,6 +990,29 @@ fn input_lines_with_cursor(text: &str, cursor: usize) -> Vec<Line<'static>> {
     lines
 }
 
+/// Byte length of the char at the start of `rest` together with trailing zero-width chars
+fn cursor_cell_len(rest: &str) -> usize {
+    let mut chars = rest.char_indices();
+    if chars.next().is_none() {
+        return 0;
+    }
+    chars
+        .find(|(_, ch)| ch.width().unwrap_or(0) > 0)
+        .map(|(idx, _)| idx)
+        .unwrap_or(rest.len())
+}
+
+/// Display width (in terminal columns) of the char at `cursor`, at least 1
+fn cursor_char_width(text: &str, cursor: usize) -> u16 {
+    text.get(cursor..)
+        .and_then(|rest| rest.chars().next())
+        .filter(|ch| *ch != '\n')
+        .and_then(|ch| ch.width())
+        .unwrap_or(1)
+        .max(1) as u16
+}
+
+/// Line and display column of `cursor`; wide chars (CJK, emoji) count as 2 columns
 fn cursor_line_col(display_text: &str, cursor: usize) -> (u16, u16) {
     let mut line: u16 = 0;
     let mut col: u16 = 0;
@@ -378,7 +1026,7 @@ fn cursor_line_col(display_text: &str, cursor: usize) -> (u16, u16) {
             line = line.saturating_add(1);
             col = 0;
         } else {
-            col = col.saturating_add(1);
+            col = col.saturating_add(ch.width().unwrap_or(0) as u16);
         }
     }
 
@@ -393,6 +1041,71 @@ fn ui(f: &mut Frame, app: &mut core::App) {
         core::Tab::Main => ui_main_tab(f, app),
         core::Tab::History => ui_history_tab(f, app),
     }
+    if app.show_models {
+        draw_models(f, app);
+    }
+    if let Some(command) = &app.pending_run {
+        draw_confirm_run(f, command);
+    }
+}
+
+/// Centred list of the ensemble's models, each with an on/off checkbox
+fn draw_models(f: &mut Frame, app: &core::App) {
+    let models = app.ensemble.model_states();
+    let size = f.size();
+    let width = size.width.saturating_sub(4).min(44);
+    let height = (clamp_u16(models.len()) + BORDER_ROWS).min(size.height);
+    let area = Rect::new(
+        size.x + (size.width - width) / 2,
+        size.y + (size.height - height) / 2,
+        width,
+        height,
+    );
+    let items: Vec<ListItem> = models
+        .iter()
+        .map(|(name, enabled)| {
+            let check = if *enabled { "x" } else { " " };
+            ListItem::new(format!("[{check}] {name}"))
+        })
+        .collect();
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title("models (Space: toggle  Esc: close)")
+                .borders(Borders::ALL),
+        )
+        .highlight_style(Style::default().add_modifier(Modifier::BOLD | Modifier::REVERSED));
+    let mut state = ListState::default().with_selected(Some(app.models_selected));
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+/// Centred box asking before a command matching a dangerous pattern runs
+fn draw_confirm_run(f: &mut Frame, command: &str) {
+    let size = f.size();
+    let width = size.width.saturating_sub(4).min(72);
+    let height = 6.min(size.height);
+    let area = Rect::new(
+        size.x + (size.width - width) / 2,
+        size.y + (size.height - height) / 2,
+        width,
+        height,
+    );
+    let text = vec![
+        Line::from(Span::styled(command, Style::default().add_modifier(Modifier::BOLD))),
+        Line::from(""),
+        Line::from("y: run it   any other key: cancel"),
+    ];
+    let p = Paragraph::new(text)
+        .wrap(Wrap { trim: false })
+        .block(
+            Block::default()
+                .title("run this command?")
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Red)),
+        );
+    f.render_widget(Clear, area);
+    f.render_widget(p, area);
 }
 
 fn ui_main_tab(f: &mut Frame, app: &mut core::App) {
@@ -404,21 +1117,28 @@ fn ui_main_tab(f: &mut Frame, app: &mut core::App) {
     let vchunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
-            Constraint::Length(1), // tab bar
-            Constraint::Length(3), // input
-            Constraint::Min(10),   // content area (suggestions or output)
+            Constraint::Length(TAB_BAR_ROWS),      // tab bar
+            Constraint::Length(INPUT_ROWS),        // input
+            Constraint::Min(10),                   // content area (suggestions or output)
+            Constraint::Length(preview_rows(app)), // preview of the selected suggestion (hidden: 0)
         ])
         .split(size);
 
     draw_tabs(f, vchunks[0], app);
     draw_input(f, vchunks[1], app);
+    app.content_area = Some(vchunks[2]);
 
     // Show output if running or has output lines, otherwise show suggestions
+    app.suggestions_area = None;
     if app.is_running || !app.output_lines.is_empty() {
         draw_output(f, vchunks[2], app);
     } else {
         draw_suggestions(f, vchunks[2], app);
     }
+
+    if app.show_preview {
+        draw_preview(f, vchunks[3], app);
+    }
 }
 
 fn ui_history_tab(f: &mut Frame, app: &mut core::App) {
@@ -430,8 +1150,8 @@ fn ui_history_tab(f: &mut Frame, app: &mut core::App) {
     let vchunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
-            Constraint::Length(1), // tab bar
-            Constraint::Min(10),   // history browser
+            Constraint::Length(TAB_BAR_ROWS), // tab bar
+            Constraint::Min(10),              // history browser
         ])
         .split(size);
 
@@ -445,6 +1165,7 @@ fn ui_history_tab(f: &mut Frame, app: &mut core::App) {
         ])
         .split(vchunks[1]);
 
+    app.history_output_area = Some(h_chunks[1]);
     draw_history_list(f, h_chunks[0], app);
     draw_history_output(f, h_chunks[1], app);
 }
@@ -506,7 +1227,15 @@ fn draw_tabs(f: &mut Frame, area: Rect, app: &mut core::App) {
 }
 
 fn draw_input(f: &mut Frame, area: Rect, app: &core::App) {
-    let title = "ghosttype ▸ input  (Enter: run  Tab: accept  Ctrl+Tab: switch  PgUp/PgDn: scroll  Ctrl-C/ESC: quit)";
+    let title = "ghosttype ▸ input  (Enter: run  Tab: accept  Alt+Enter: edit  Ctrl+Z/Alt+Z: undo/redo  Ctrl+Y: copy  Ctrl+P: preview  Ctrl+O: models  Ctrl+Tab: switch  PgUp/PgDn: scroll  Ctrl-C/ESC: quit)";
+    let mut title = match (app.vim, app.edit_mode) {
+        (false, _) => title.to_string(),
+        (true, core::EditMode::Insert) => format!("-- INSERT -- {title}"),
+        (true, core::EditMode::Normal) => format!("-- NORMAL -- {title}"),
+    };
+    if let Some(status) = app.status() {
+        title.push_str(&format!("  [{status}]"));
+    }
     let block = Block::default()
         .title(title)
         .borders(Borders::ALL);
@@ -518,13 +1247,44 @@ fn draw_input(f: &mut Frame, area: Rect, app: &core::App) {
         let (line, col) = cursor_line_col(app.input.as_str(), app.cursor);
         let inner_width = area.width - 2;
         let inner_height = area.height - 2;
-        let clamped_col = col.min(inner_width.saturating_sub(1));
+        // Keep a wide cursor glyph fully inside the box
+        let glyph_width = cursor_char_width(app.input.as_str(), app.cursor);
+        let clamped_col = col.min(inner_width.saturating_sub(glyph_width));
         let clamped_line = line.min(inner_height.saturating_sub(1));
         f.set_cursor(area.x + 1 + clamped_col, area.y + 1 + clamped_line);
     }
 }
 
-fn draw_suggestions(f: &mut Frame, area: Rect, app: &core::App) {
+/// Append a dim, right-aligned `[source score]` tag to the first display line
+fn with_source_tag(
+    mut lines: Vec<Line<'static>>,
+    suggestion: &Suggestion,
+    width: u16,
+) -> Vec<Line<'static>> {
+    let (Some(source), Some(first)) = (suggestion.source.as_deref(), lines.first_mut()) else {
+        return lines;
+    };
+    let tag = format!("[{source} {:.1}]", suggestion.score);
+    let used = first.width() + tag.width();
+    let padding = (width as usize).saturating_sub(used).max(1);
+    first.spans.push(Span::raw(" ".repeat(padding)));
+    first
+        .spans
+        .push(Span::styled(tag, Style::default().add_modifier(Modifier::DIM)));
+    lines
+}
+
+const SPINNER_FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+/// UI ticks (33ms each) per spinner frame
+const TICKS_PER_SPINNER_FRAME: usize = 3;
+
+fn spinner_glyph(frame: usize) -> char {
+    SPINNER_FRAMES[(frame / TICKS_PER_SPINNER_FRAME) % SPINNER_FRAMES.len()]
+}
+
+fn draw_suggestions(f: &mut Frame, area: Rect, app: &mut core::App) {
+    app.suggestions_area = Some(area);
+    let inner_width = area.width.saturating_sub(2);
     let items: Vec<ListItem> = app
         .suggestions
         .iter()
@@ -535,43 +1295,122 @@ fn draw_suggestions(f: &mut Frame, area: Rect, app: &core::App) {
             } else {
                 Style::default()
             };
-            ListItem::new(format_command_lines_for_display(s)).style(style)
+            let lines = with_source_tag(
+                highlighted_command_lines(&s.text, &s.match_indices),
+                s,
+                inner_width,
+            );
+            ListItem::new(lines).style(style)
         })
         .collect();
 
-    let list = List::new(items).block(Block::default().title("suggestions").borders(Borders::ALL));
-    f.render_widget(list, area);
+    let title = if app.is_loading_heavy {
+        format!("suggestions {} thinking…", spinner_glyph(app.spinner_frame))
+    } else {
+        "suggestions".to_string()
+    };
+    let list = List::new(items).block(Block::default().title(title).borders(Borders::ALL));
+    // The list moves the offset as needed to keep the selection on screen
+    let mut state = ListState::default()
+        .with_offset(app.suggestions_scroll)
+        .with_selected(Some(app.selected));
+    f.render_stateful_widget(list, area, &mut state);
+    app.suggestions_scroll = state.offset();
 }
 
-fn draw_history_list(f: &mut Frame, area: Rect, app: &core::App) {
-    let items: Vec<ListItem> = app
-        .history
+/// Full, wrapped text of the selected suggestion with its source and score
+fn draw_preview(f: &mut Frame, area: Rect, app: &core::App) {
+    let text = match app.suggestions.get(app.selected) {
+        Some(s) => {
+            let mut lines = lines_from_display_text(&s.text);
+            lines.push(Line::from(Span::styled(
+                format!(
+                    "source: {}  score: {:.3}",
+                    s.source.as_deref().unwrap_or("unknown"),
+                    s.score
+                ),
+                Style::default().add_modifier(Modifier::DIM),
+            )));
+            lines
+        }
+        None => vec![Line::from("(no suggestion selected)")],
+    };
+    let p = Paragraph::new(text)
+        .block(Block::default().title("preview (Ctrl+P: hide)").borders(Borders::ALL))
+        .wrap(Wrap { trim: false });
+    f.render_widget(p, area);
+}
+
+fn draw_history_list(f: &mut Frame, area: Rect, app: &mut core::App) {
+    let rows: Vec<usize> = app.filtered_history_indices().into_iter().rev().collect();
+    let items: Vec<ListItem> = rows
         .iter()
-        .rev()
-        .enumerate()
-        .map(|(display_idx, h)| {
-            let actual_idx = app.history.len().saturating_sub(1).saturating_sub(display_idx);
+        .map(|&actual_idx| {
             let style = if actual_idx == app.selected_history_index {
                 Style::default().add_modifier(Modifier::BOLD | Modifier::REVERSED)
             } else {
                 Style::default()
             };
-            ListItem::new(format_command_lines_for_display(&h.cmd)).style(style)
+            let entry = &app.history[actual_idx];
+            let mut lines = format_command_lines_for_display(&entry.cmd);
+            // Flag runs that failed; successes stay unmarked to keep the list quiet
+            let failed = entry.exit_code.filter(|&code| code != 0);
+            if let (Some(code), Some(first)) = (failed, lines.first_mut()) {
+                first
+                    .spans
+                    .push(Span::styled(format!("  ✗ {code}"), Style::default().fg(Color::Red)));
+            }
+            ListItem::new(lines).style(style)
         })
         .collect();
-    let list = List::new(items).block(Block::default().title("Recent Commands").borders(Borders::ALL));
-    f.render_widget(list, area);
+    let mut title = if app.history_filter_active || !app.history_filter.is_empty() {
+        format!("Recent Commands  /{}", app.history_filter)
+    } else {
+        "Recent Commands (/: filter  Ctrl+F: search output  d: delete)".to_string()
+    };
+    if let Some(status) = app.status() {
+        title.push_str(&format!("  [{status}]"));
+    }
+    let list = List::new(items).block(Block::default().title(title).borders(Borders::ALL));
+    let selected_row = rows.iter().position(|&idx| idx == app.selected_history_index);
+    let mut state = ListState::default()
+        .with_offset(app.history_list_scroll)
+        .with_selected(selected_row);
+    f.render_stateful_widget(list, area, &mut state);
+    app.history_list_scroll = state.offset();
 }
 
 fn draw_history_output(f: &mut Frame, area: Rect, app: &core::App) {
     let (title, text) = if app.history.is_empty() {
         ("Output".to_string(), vec![Line::from("(no history yet)")])
-    } else if let Some(entry) = app.history.get(app.selected_history_index) {
-        let title = format!("Output — {}", entry.cmd);
+    } else if let Some(entry) = app
+        .history
+        .get(app.selected_history_index)
+        .filter(|_| app.filtered_history_indices().contains(&app.selected_history_index))
+    {
+        let mut title = match entry.exit_code {
+            Some(code) => format!("Output — {} [exit code: {code}]", entry.cmd),
+            None => format!("Output — {}", entry.cmd),
+        };
+        let matches = app.history_output_matches();
+        if app.history_search_active || !app.history_search.is_empty() {
+            let position = if matches.is_empty() {
+                "no matches".to_string()
+            } else {
+                format!("{}/{}", app.history_search_match.min(matches.len() - 1) + 1, matches.len())
+            };
+            title.push_str(&format!("  ^F {} [{position}]", app.history_search));
+        }
         let text = if entry.output_lines.is_empty() {
             vec![Line::from("(no output)")]
         } else {
-            entry.output_lines.iter().map(|l| Line::from(l.as_str())).collect()
+            let current = matches.get(app.history_search_match);
+            entry
+                .output_lines
+                .iter()
+                .enumerate()
+                .map(|(idx, line)| highlight_output_matches(line, idx, &matches, current))
+                .collect()
         };
         (title, text)
     } else {
@@ -584,8 +1423,32 @@ fn draw_history_output(f: &mut Frame, area: Rect, app: &core::App) {
     f.render_widget(p, area);
 }
 
+/// Line `idx` of a History output with the search hits on it highlighted,
+/// the `current` one more strongly than the rest
+fn highlight_output_matches<'a>(
+    line: &'a str,
+    idx: usize,
+    matches: &[core::OutputMatch],
+    current: Option<&core::OutputMatch>,
+) -> Line<'a> {
+    let mut spans = Vec::new();
+    let mut last = 0;
+    for m in matches.iter().filter(|m| m.line == idx) {
+        spans.push(Span::raw(&line[last..m.range.start]));
+        let style = if current == Some(m) {
+            Style::default().fg(Color::Black).bg(Color::Yellow)
+        } else {
+            Style::default().add_modifier(Modifier::REVERSED)
+        };
+        spans.push(Span::styled(&line[m.range.clone()], style));
+        last = m.range.end;
+    }
+    spans.push(Span::raw(&line[last..]));
+    Line::from(spans)
+}
+
 fn draw_output(f: &mut Frame, area: Rect, app: &core::App) {
-    let title = if let Some(cmd) = &app.last_run_cmd {
+    let mut title = if let Some(cmd) = &app.last_run_cmd {
         format!(
             "output — {}{}",
             cmd,
@@ -594,6 +1457,10 @@ fn draw_output(f: &mut Frame, area: Rect, app: &core::App) {
     } else {
         "output".to_string()
     };
+    if app.output_dropped > 0 {
+        title.push(' ');
+        title.push_str(&dropped_lines_note(app.output_dropped));
+    }
     let text: Vec<Line> = if app.output_lines.is_empty() {
         vec![Line::from("(no output yet)")]
     } else {
@@ -608,43 +1475,210 @@ fn draw_output(f: &mut Frame, area: Rect, app: &core::App) {
     f.render_widget(p, area);
 }
 
-fn execute_in_terminal(command: &str) -> Result<()> {
-    use std::process::Command;
+/// Bytes of combined stdout/stderr stored per executed command, on top of the
+/// line cap. The newest lines are kept, like the output pane.
+const MAX_CAPTURED_OUTPUT_BYTES: usize = 64 * 1024;
+
+/// How long to keep reading after the command exits. Anything it left running
+/// in the background may hold the pipes open indefinitely.
+const EXIT_DRAIN_GRACE: Duration = Duration::from_millis(200);
+
+/// A command started from the Main tab, its stdout and stderr read line by
+/// line on background threads so the TUI keeps drawing while it runs
+struct RunningCommand {
+    command: String,
+    child: Child,
+    lines: mpsc::Receiver<String>,
+    exited_at: Option<Instant>,
+    // Both streams share one buffer so the stored output keeps their interleaving.
+    // Kept apart from `app.output_lines`, which typing clears.
+    output: VecDeque<String>,
+    dropped: usize,
+    max_lines: usize,
+}
 
-    println!("\n$ {}\n", command);
+impl RunningCommand {
+    /// Run `command` through `shell -lc` with stdin closed, since the TUI
+    /// keeps the terminal. The shell leads its own process group so
+    /// [`RunningCommand::stop`] reaches everything it starts.
+    fn spawn(shell: &str, command: &str, max_lines: usize) -> Result<Self> {
+        let mut child = Command::new(shell)
+            .arg("-lc")
+            .arg(command)
+            .process_group(0)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        let (tx, lines) = mpsc::channel();
+        if let Some(stdout) = child.stdout.take() {
+            let tx = tx.clone();
+            std::thread::spawn(move || forward_lines(stdout, tx));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            std::thread::spawn(move || forward_lines(stderr, tx));
+        }
+        Ok(Self {
+            command: command.to_string(),
+            child,
+            lines,
+            exited_at: None,
+            output: VecDeque::new(),
+            dropped: 0,
+            max_lines,
+        })
+    }
 
-    // Use the user's shell from $SHELL, fallback to /bin/sh
-    let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string());
+    /// Kill the command's whole process group, so pipelines and background
+    /// jobs don't outlive it
+    fn stop(&mut self) -> std::io::Result<()> {
+        let pgid = self.child.id() as libc::pid_t;
+        // SAFETY: kill(2) takes no pointers; a negative pid targets the group
+        if unsafe { libc::kill(-pgid, libc::SIGKILL) } == 0 {
+            Ok(())
+        } else {
+            Err(std::io::Error::last_os_error())
+        }
+    }
 
-    Command::new(shell)
-        .arg("-lc")
-        .arg(command)
-        .status()?;
+    /// Move the lines read so far into `app.output_lines`. Returns the exit
+    /// code (`None` if killed by a signal) once the command has finished and
+    /// its output is drained.
+    fn poll(&mut self, app: &mut core::App) -> Result<Option<Option<i32>>> {
+        let closed = loop {
+            match self.lines.try_recv() {
+                Ok(line) => {
+                    if core::push_capped(&mut self.output, line.clone(), self.max_lines) {
+                        self.dropped += 1;
+                    }
+                    app.push_output_line(line);
+                }
+                Err(mpsc::TryRecvError::Empty) => break false,
+                Err(mpsc::TryRecvError::Disconnected) => break true,
+            }
+        };
+        if self.exited_at.is_none() && self.child.try_wait()?.is_some() {
+            self.exited_at = Some(Instant::now());
+        }
+        match self.exited_at {
+            Some(at) if closed || at.elapsed() >= EXIT_DRAIN_GRACE => {
+                Ok(Some(self.child.wait()?.code()))
+            }
+            _ => Ok(None),
+        }
+    }
 
-    Ok(())
+    /// Output as it is stored in history: the newest lines that fit in
+    /// [`MAX_CAPTURED_OUTPUT_BYTES`], after a note on how many were dropped
+    fn stored_output(&self) -> String {
+        let mut bytes = 0;
+        let kept = self
+            .output
+            .iter()
+            .rev()
+            .take_while(|line| {
+                bytes += line.len() + 1;
+                bytes <= MAX_CAPTURED_OUTPUT_BYTES
+            })
+            .count();
+        let dropped = self.dropped + self.output.len() - kept;
+        let mut lines: Vec<Cow<str>> = Vec::with_capacity(kept + 1);
+        if dropped > 0 {
+            lines.push(Cow::Owned(dropped_lines_note(dropped)));
+        }
+        lines.extend(self.output.range(self.output.len() - kept..).map(|l| Cow::Borrowed(l.as_str())));
+        lines.join("\n")
+    }
 }
 
-fn wait_for_enter() -> Result<()> {
-    use std::io::{self, Write};
+fn dropped_lines_note(dropped: usize) -> String {
+    format!("[{dropped} earlier lines dropped]")
+}
 
-    print!("\nPress Enter to return to ghosttype...");
-    io::stdout().flush()?;
+/// Send each line of `reader` (lossily decoded, without its line ending)
+/// until it closes or nobody is listening
+fn forward_lines(reader: impl Read, tx: mpsc::Sender<String>) {
+    let mut reader = BufReader::new(reader);
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        match reader.read_until(b'\n', &mut buf) {
+            Ok(0) => return,
+            Ok(_) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(_) => return,
+        }
+        let line = String::from_utf8_lossy(&buf);
+        let line = line.trim_end_matches(['\n', '\r']).to_string();
+        if tx.send(line).is_err() {
+            return;
+        }
+    }
+}
 
-    let mut input = String::new();
-    io::stdin().read_line(&mut input)?;
+/// Everything needed to record finished runs
+struct RunRecorder<'a> {
+    pool: Option<&'a SqlitePool>,
+    session_id: &'a str,
+    secrets: &'a SecretFilter,
+}
 
-    Ok(())
+impl RunRecorder<'_> {
+    /// Store the finished run and list it first on the History tab, the way
+    /// it will read there after a restart
+    fn record(&self, app: &mut core::App, run: &RunningCommand, exit_code: Option<i32>) {
+        let output = run.stored_output();
+        if let Some(pool) = self.pool {
+            if let Err(e) = core::persist_command_to_history(
+                pool,
+                &run.command,
+                self.session_id,
+                &output,
+                exit_code,
+                self.secrets,
+            ) {
+                warn!("failed to save command to history: {e:?}");
+            }
+        }
+        let Some(cmd) = self.secrets.apply(&run.command) else {
+            return;
+        };
+        if !app.history.is_empty() {
+            app.selected_history_index += 1;
+        }
+        app.history.insert(
+            0,
+            core::HistoryEntry {
+                cmd: cmd.trim().to_string(),
+                output_lines: output.lines().map(str::to_string).collect(),
+                exit_code,
+            },
+        );
+    }
+}
+
+/// Start `command` unless one is already running
+fn start_command(app: &mut core::App, running: &mut Option<RunningCommand>, command: String) {
+    if running.is_some() {
+        app.set_status("a command is still running (Ctrl+C stops it)");
+        return;
+    }
+    // Use the user's shell from $SHELL, fallback to /bin/sh
+    let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string());
+    match RunningCommand::spawn(&shell, &command, app.max_output_lines) {
+        Ok(run) => {
+            *running = Some(run);
+            app.clear_output();
+            app.output_scroll = 0;
+            app.is_running = true;
+            app.last_run_cmd = Some(command);
+        }
+        Err(err) => app.set_status(format!("failed to run command: {err}")),
+    }
 }
 
-pub fn run_tui_loop(
-    files: Vec<PathBuf>,
-    top: usize,
-    unique: bool,
-    enable_embedding: bool,
-    embedding_model: Option<PathBuf>,
-    enable_llm: bool,
-    llm_model: Option<PathBuf>,
-) -> Result<()> {
+pub fn run_tui_loop(options: TuiOptions) -> Result<()> {
     // Create tokio runtime for async heavy model tasks
     let runtime = tokio::runtime::Runtime::new()?;
     let _enter = runtime.enter(); // Enter runtime context for entire session
@@ -655,58 +1689,631 @@ pub fn run_tui_loop(
         .unwrap()
         .as_millis());
 
-    // Open database pool once for the entire session
-    let pool = match SqlitePool::open_default() {
-        Ok(p) => Some(p),
-        Err(err) => {
-            warn!("failed to open sqlite history store: {err:?}");
-            None
+    // Open database pool once for the entire session, unless running purely in memory
+    let pool = if options.no_db {
+        None
+    } else {
+        match SqlitePool::open_default() {
+            Ok(p) => Some(p),
+            Err(err) => {
+                warn!("failed to open sqlite history store: {err:?}");
+                None
+            }
         }
     };
 
     // Import shell history files into database on startup
     if let Some(ref p) = pool {
-        if let Err(e) = core::import_shell_history_to_db(p, &files) {
+        let secrets = &options.app.secrets;
+        let imported = core::import_shell_history_to_db(p, &options.files, secrets, options.encoding);
+        if let Err(e) = imported {
             warn!("failed to import shell history: {e:?}");
         }
     }
 
-    let mut retained_input: Option<String> = None;
+    // Pick up where the previous session left off, unless that was a while ago
+    let retained_input: Option<String> = pool.as_ref().and_then(|p| {
+        core::load_tui_input(p, core::TUI_INPUT_MAX_AGE)
+            .inspect_err(|e| warn!("failed to restore tui input: {e:?}"))
+            .ok()
+            .flatten()
+    });
 
-    loop {
-        let (run_result, latest_input) = run_tui(
-            files.clone(),
-            top,
-            unique,
-            pool.clone(),
-            enable_embedding,
-            embedding_model.clone(),
-            enable_llm,
-            llm_model.clone(),
-            retained_input.take(),
-        )?;
-
-        retained_input = Some(latest_input.clone());
-
-        match run_result {
-            Some(command) => {
-                execute_in_terminal(&command)?;
-
-                // Save command to database
-                if let Some(ref p) = pool {
-                    if let Err(e) = core::persist_command_to_history(p, &command, &session_id) {
-                        warn!("failed to save command to history: {e:?}");
-                    }
-                }
+    let latest_input = run_tui(&options, pool.clone(), retained_input, &session_id)?;
 
-                wait_for_enter()?;
-                // Loop continues, TUI restarts
-            }
-            None => {
-                // User quit with Ctrl-C or ESC
-                break;
-            }
+    if let Some(ref p) = pool {
+        if let Err(e) = core::save_tui_input(p, &latest_input) {
+            warn!("failed to save tui input: {e:?}");
         }
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_app() -> core::App {
+        let options = core::AppOptions { top: 10, ..core::AppOptions::default() };
+        core::App::new(Vec::new(), None, &options).unwrap()
+    }
+
+    fn press(app: &mut core::App, code: KeyCode) {
+        handle_key(code, KeyModifiers::NONE, app).unwrap();
+    }
+
+    #[test]
+    fn edits_around_multibyte_characters() {
+        let mut app = test_app();
+        for c in "aé日本".chars() {
+            press(&mut app, KeyCode::Char(c));
+        }
+        assert_eq!(app.cursor, app.input.len());
+
+        press(&mut app, KeyCode::Left);
+        press(&mut app, KeyCode::Left);
+        press(&mut app, KeyCode::Char('x'));
+        assert_eq!(app.input, "aéx日本");
+
+        press(&mut app, KeyCode::Backspace);
+        press(&mut app, KeyCode::Backspace);
+        assert_eq!(app.input, "a日本");
+
+        press(&mut app, KeyCode::Delete);
+        assert_eq!(app.input, "a本");
+
+        press(&mut app, KeyCode::Right);
+        press(&mut app, KeyCode::Right);
+        assert_eq!(app.cursor, app.input.len());
+        press(&mut app, KeyCode::Backspace);
+        assert_eq!(app.input, "a");
+    }
+
+    #[test]
+    fn history_delete_requires_confirmation() {
+        let mut app = test_app();
+        app.current_tab = core::Tab::History;
+        app.history = ["rm -rf build", "ls", "rm -rf build"]
+            .into_iter()
+            .map(|cmd| core::HistoryEntry {
+                cmd: cmd.to_string(),
+                output_lines: Vec::new(),
+                exit_code: None,
+            })
+            .collect();
+        app.selected_history_index = 2;
+
+        press(&mut app, KeyCode::Char('d'));
+        assert_eq!(app.history.len(), 3);
+        press(&mut app, KeyCode::Up);
+        press(&mut app, KeyCode::Char('d'));
+        assert_eq!(app.history.len(), 3);
+
+        press(&mut app, KeyCode::Char('d'));
+        let remaining: Vec<&str> = app.history.iter().map(|h| h.cmd.as_str()).collect();
+        assert_eq!(remaining, vec!["ls"]);
+        assert_eq!(app.selected_history_index, 0);
+    }
+
+    #[test]
+    fn history_filter_narrows_and_escape_clears() {
+        let mut app = test_app();
+        app.current_tab = core::Tab::History;
+        app.history = ["git status", "ls -al", "git push"]
+            .into_iter()
+            .map(|cmd| core::HistoryEntry {
+                cmd: cmd.to_string(),
+                output_lines: Vec::new(),
+                exit_code: None,
+            })
+            .collect();
+        app.selected_history_index = 1;
+
+        press(&mut app, KeyCode::Char('/'));
+        for c in "gt".chars() {
+            press(&mut app, KeyCode::Char(c));
+        }
+        assert_eq!(app.filtered_history_indices(), vec![0, 2]);
+        assert_eq!(app.selected_history_index, 0);
+
+        press(&mut app, KeyCode::Up);
+        assert_eq!(app.selected_history_index, 2);
+
+        press(&mut app, KeyCode::Enter);
+        assert!(!app.history_filter_active);
+        assert!(matches!(
+            handle_key(KeyCode::Esc, KeyModifiers::NONE, &mut app).unwrap(),
+            KeyResult::Continue
+        ));
+        assert!(app.history_filter.is_empty());
+        assert_eq!(app.filtered_history_indices().len(), 3);
+    }
+
+    #[test]
+    fn highlights_fuzzy_matched_chars() {
+        let lines = highlighted_command_lines("git status", &[0, 4, 5]);
+        let spans: Vec<(&str, bool)> = lines[0]
+            .spans
+            .iter()
+            .map(|s| (s.content.as_ref(), s.style.add_modifier.contains(Modifier::BOLD)))
+            .collect();
+        assert_eq!(
+            spans,
+            vec![("g", true), ("it ", false), ("st", true), ("atus", false)]
+        );
+    }
+
+    fn press_ctrl(app: &mut core::App, code: KeyCode) {
+        handle_key(code, KeyModifiers::CONTROL, app).unwrap();
+    }
+
+    #[test]
+    fn ctrl_w_deletes_one_word_before_cursor() {
+        let mut app = test_app();
+        app.input = "git commit  -m 日本".to_string();
+        app.cursor = "git commit  ".len();
+
+        press_ctrl(&mut app, KeyCode::Char('w'));
+        assert_eq!(app.input, "git -m 日本");
+        assert_eq!(app.cursor, "git ".len());
+        assert!(app.pending_refresh);
+    }
+
+    #[test]
+    fn word_motion_and_line_kills() {
+        let mut app = test_app();
+        app.input = "ls  日本 -al".to_string();
+        app.cursor = app.input.len();
+
+        press_ctrl(&mut app, KeyCode::Left);
+        assert_eq!(app.cursor, "ls  日本 ".len());
+        press_ctrl(&mut app, KeyCode::Left);
+        assert_eq!(app.cursor, "ls  ".len());
+        press_ctrl(&mut app, KeyCode::Right);
+        assert_eq!(app.cursor, "ls  日本".len());
+
+        press_ctrl(&mut app, KeyCode::Char('k'));
+        assert_eq!(app.input, "ls  日本");
+        press_ctrl(&mut app, KeyCode::Left);
+        press_ctrl(&mut app, KeyCode::Char('u'));
+        assert_eq!(app.input, "日本");
+        assert_eq!(app.cursor, 0);
+    }
+
+    #[test]
+    fn paste_inserts_literal_text_at_cursor() {
+        let mut app = test_app();
+        app.input = "ab".to_string();
+        app.cursor = 1;
+
+        handle_paste("echo \"hello world\"", &mut app);
+        assert_eq!(app.input, "aecho \"hello world\"b");
+        assert_eq!(app.cursor, "aecho \"hello world\"".len());
+        assert!(app.pending_refresh);
+    }
+
+    #[test]
+    fn tab_accept_puts_the_cursor_on_the_first_placeholder() {
+        let mut app = test_app();
+        let accept = |app: &mut core::App, text: &str| {
+            app.suggestions = vec![Suggestion::with_source(text, 1.0, "history")];
+            app.selected = 0;
+            press(app, KeyCode::Tab);
+        };
+
+        accept(&mut app, "kubectl logs <pod> -n {{namespace}}");
+        assert_eq!(app.cursor, "kubectl logs ".len());
+        accept(&mut app, "ssh {{host}} uptime");
+        assert_eq!(app.cursor, "ssh ".len());
+        accept(&mut app, "git status");
+        assert_eq!(app.cursor, app.input.len());
+
+        app.placeholders = core::Placeholders::new(&[r"\bHOST\b".to_string()]).unwrap();
+        accept(&mut app, "ping -c 3 HOST <ignored>");
+        assert_eq!(app.cursor, "ping -c 3 ".len());
+    }
+
+    #[test]
+    fn suggestion_list_scrolls_to_follow_the_selection() {
+        let mut app = test_app();
+        app.suggestions = (0..50)
+            .map(|i| Suggestion::with_source(format!("cmd{i}"), 1.0, "history"))
+            .collect();
+        app.selected = 30;
+
+        let mut terminal = Terminal::new(ratatui::backend::TestBackend::new(40, 12)).unwrap();
+        terminal
+            .draw(|f| draw_suggestions(f, f.size(), &mut app))
+            .unwrap();
+        // Ten rows fit inside the border, with the selection on the last one
+        assert_eq!(app.suggestions_scroll, 21);
+        assert_eq!(suggestion_at(5, 10, &app), Some(30));
+
+        app.selected = 0;
+        terminal
+            .draw(|f| draw_suggestions(f, f.size(), &mut app))
+            .unwrap();
+        assert_eq!(app.suggestions_scroll, 0);
+    }
+
+    #[test]
+    fn resize_clamps_scroll_but_keeps_the_selection() {
+        let mut app = test_app();
+        app.output_lines = (0..20).map(|i| i.to_string()).collect();
+        app.output_scroll = 18;
+        app.suggestions = (0..10)
+            .map(|i| Suggestion::with_source(format!("cmd{i}"), 1.0, "history"))
+            .collect();
+        app.selected = 9;
+
+        // 12 rows leave 6 for the output/suggestion block
+        handle_resize(12, &mut app);
+        assert_eq!(app.output_scroll, 14);
+        assert_eq!(app.selected, 9);
+    }
+
+    #[test]
+    fn preview_pane_toggles_and_shrinks_suggestion_rows() {
+        let mut app = test_app();
+        app.suggestions = (0..10)
+            .map(|i| Suggestion::with_source(format!("cmd{i}"), 1.0, "history"))
+            .collect();
+        app.selected = 9;
+        app.output_lines = (0..20).map(|i| i.to_string()).collect();
+        app.output_scroll = 18;
+
+        press_ctrl(&mut app, KeyCode::Char('p'));
+        assert!(app.show_preview);
+
+        // 18 rows minus tab bar, input, borders and the preview leave 6
+        handle_resize(18, &mut app);
+        assert_eq!(app.output_scroll, 14);
+        assert_eq!(app.selected, 9);
+
+        press_ctrl(&mut app, KeyCode::Char('p'));
+        assert!(!app.show_preview);
+    }
+
+    #[test]
+    fn cursor_columns_use_display_width() {
+        let text = "echo 世界\nls";
+        let cursor = text.find('界').unwrap();
+        assert_eq!(cursor_line_col(text, cursor), (0, 7));
+        assert_eq!(cursor_line_col(text, text.len()), (1, 2));
+        assert_eq!(cursor_char_width(text, cursor), 2);
+        assert_eq!(cursor_char_width(text, 0), 1);
+    }
+
+    #[test]
+    fn highlights_combining_marks_with_cursor_char() {
+        let text = "e\u{301}x";
+        let lines = input_lines_with_cursor(text, 0);
+        assert_eq!(lines[0].spans[0].content, "e\u{301}");
+        assert_eq!(lines[0].spans[1].content, "x");
+    }
+
+    #[test]
+    fn undo_takes_back_typing_runs_and_kills() {
+        let mut app = test_app();
+        for c in "git status".chars() {
+            press(&mut app, KeyCode::Char(c));
+        }
+        press_ctrl(&mut app, KeyCode::Char('w'));
+        assert_eq!(app.input, "git ");
+        press(&mut app, KeyCode::Char('s'));
+        press(&mut app, KeyCode::Char('h'));
+
+        press_ctrl(&mut app, KeyCode::Char('z'));
+        assert_eq!((app.input.as_str(), app.cursor), ("git ", 4));
+        press_ctrl(&mut app, KeyCode::Char('z'));
+        assert_eq!((app.input.as_str(), app.cursor), ("git status", 10));
+        press_ctrl(&mut app, KeyCode::Char('z'));
+        assert_eq!(app.input, "");
+        press_ctrl(&mut app, KeyCode::Char('z'));
+        assert_eq!(app.input, "");
+
+        let ctrl_shift = KeyModifiers::CONTROL | KeyModifiers::SHIFT;
+        handle_key(KeyCode::Char('Z'), ctrl_shift, &mut app).unwrap();
+        handle_key(KeyCode::Char('Z'), ctrl_shift, &mut app).unwrap();
+        assert_eq!((app.input.as_str(), app.cursor), ("git ", 4));
+
+        press_ctrl(&mut app, KeyCode::Char('z'));
+        handle_key(KeyCode::Char('z'), KeyModifiers::ALT, &mut app).unwrap();
+        assert_eq!(app.input, "git ", "Alt+Z redoes where Ctrl+Shift+Z can't be told apart");
+
+        press(&mut app, KeyCode::Char('x'));
+        handle_key(KeyCode::Char('Z'), ctrl_shift, &mut app).unwrap();
+        assert_eq!(app.input, "git x", "a new edit drops the redo history");
+    }
+
+    #[test]
+    fn vim_normal_mode_navigates_suggestions() {
+        let mut app = test_app();
+        app.vim = true;
+        app.suggestions = ["git status", "git stash", "git show"]
+            .into_iter()
+            .map(|text| Suggestion::with_source(text, 1.0, "history"))
+            .collect();
+
+        press(&mut app, KeyCode::Esc);
+        assert_eq!(app.edit_mode, core::EditMode::Normal);
+        press(&mut app, KeyCode::Char('j'));
+        press(&mut app, KeyCode::Char('j'));
+        assert_eq!(app.selected, 2);
+        press(&mut app, KeyCode::Char('k'));
+        assert_eq!(app.selected, 1);
+        press(&mut app, KeyCode::Char('g'));
+        assert_eq!(app.selected, 0);
+        handle_key(KeyCode::Char('G'), KeyModifiers::SHIFT, &mut app).unwrap();
+        assert_eq!(app.selected, 2);
+        assert!(app.input.is_empty());
+
+        press(&mut app, KeyCode::Char('i'));
+        press(&mut app, KeyCode::Char('j'));
+        assert_eq!(app.edit_mode, core::EditMode::Insert);
+        assert_eq!(app.input, "j");
+
+        press(&mut app, KeyCode::Esc);
+        assert!(matches!(
+            handle_key(KeyCode::Esc, KeyModifiers::NONE, &mut app).unwrap(),
+            KeyResult::Quit
+        ));
+    }
+
+    #[test]
+    fn mouse_wheel_scrolls_output_and_moves_selection() {
+        let wheel = |kind, row| MouseEvent {
+            kind,
+            column: 5,
+            row,
+            modifiers: KeyModifiers::NONE,
+        };
+        let mut app = test_app();
+        app.content_area = Some(Rect::new(0, 4, 40, 10));
+        app.suggestions = ["git status", "git stash"]
+            .into_iter()
+            .map(|text| Suggestion::with_source(text, 1.0, "history"))
+            .collect();
+
+        handle_mouse(wheel(MouseEventKind::ScrollDown, 0), &mut app);
+        assert_eq!(app.selected, 0, "pointer outside the list");
+        for _ in 0..3 {
+            handle_mouse(wheel(MouseEventKind::ScrollDown, 6), &mut app);
+        }
+        assert_eq!(app.selected, 1);
+        handle_mouse(wheel(MouseEventKind::ScrollUp, 6), &mut app);
+        assert_eq!(app.selected, 0);
+
+        // 12 lines in an 8-row pane leave 4 lines to scroll
+        app.output_lines = (0..12).map(|i| format!("line {i}")).collect();
+        for _ in 0..3 {
+            handle_mouse(wheel(MouseEventKind::ScrollDown, 0), &mut app);
+        }
+        assert_eq!(app.output_scroll, 4);
+        handle_mouse(wheel(MouseEventKind::ScrollUp, 0), &mut app);
+        assert_eq!(app.output_scroll, 1);
+        assert_eq!(app.selected, 0);
+    }
+
+    #[test]
+    fn history_output_search_jumps_between_hits() {
+        let mut app = test_app();
+        app.current_tab = core::Tab::History;
+        app.history = vec![core::HistoryEntry {
+            cmd: "cargo build".to_string(),
+            output_lines: ["Compiling a", "warning: unused", "Compiling b", "warning: dead code"]
+                .map(String::from)
+                .to_vec(),
+            exit_code: Some(0),
+        }];
+
+        handle_key(KeyCode::Char('f'), KeyModifiers::CONTROL, &mut app).unwrap();
+        for c in "warn".chars() {
+            press(&mut app, KeyCode::Char(c));
+        }
+        assert_eq!(app.history_scroll, 1);
+        press(&mut app, KeyCode::Enter);
+        assert!(!app.history_search_active);
+
+        press(&mut app, KeyCode::Char('n'));
+        assert_eq!(app.history_scroll, 3);
+        handle_key(KeyCode::Char('N'), KeyModifiers::SHIFT, &mut app).unwrap();
+        assert_eq!(app.history_scroll, 1);
+
+        assert!(matches!(
+            handle_key(KeyCode::Esc, KeyModifiers::NONE, &mut app).unwrap(),
+            KeyResult::Continue
+        ));
+        assert!(app.history_search.is_empty());
+    }
+
+    #[test]
+    fn model_overlay_toggles_models_off_and_on() {
+        let mut app = test_app();
+        let history_enabled = |app: &core::App| {
+            app.ensemble
+                .model_states()
+                .into_iter()
+                .find(|(name, _)| name == "history")
+                .map(|(_, enabled)| enabled)
+        };
+        assert_eq!(history_enabled(&app), Some(true));
+
+        handle_key(KeyCode::Char('o'), KeyModifiers::CONTROL, &mut app).unwrap();
+        assert!(app.show_models);
+        press(&mut app, KeyCode::Char(' '));
+        assert_eq!(history_enabled(&app), Some(false));
+        assert_eq!(app.ensemble.disabled_models(), vec!["history"]);
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(history_enabled(&app), Some(true));
+
+        press(&mut app, KeyCode::Esc);
+        assert!(!app.show_models);
+    }
+
+    #[test]
+    fn modified_enter_loads_the_suggestion_without_running_it() {
+        let mut app = test_app();
+        for mods in [KeyModifiers::SHIFT, KeyModifiers::ALT] {
+            app.input = "kub".to_string();
+            app.cursor = app.input.len();
+            app.suggestions = vec![Suggestion::with_source("kubectl logs <pod>", 1.0, "history")];
+            app.selected = 0;
+
+            assert!(matches!(
+                handle_key(KeyCode::Enter, mods, &mut app).unwrap(),
+                KeyResult::Continue
+            ));
+            assert_eq!(app.input, "kubectl logs <pod>");
+            assert_eq!(app.cursor, app.input.len());
+        }
+    }
+
+    #[test]
+    fn dangerous_commands_wait_for_confirmation() {
+        let mut app = test_app();
+        app.input = "git reset --hard origin/main".to_string();
+        let enter = |app: &mut core::App| handle_key(KeyCode::Enter, KeyModifiers::NONE, app).unwrap();
+
+        assert!(matches!(enter(&mut app), KeyResult::Continue));
+        assert_eq!(app.pending_run.as_deref(), Some("git reset --hard origin/main"));
+        // Enter is not a confirmation, and drops the held command
+        assert!(matches!(enter(&mut app), KeyResult::Continue));
+        assert!(app.pending_run.is_none());
+
+        enter(&mut app);
+        match handle_key(KeyCode::Char('y'), KeyModifiers::NONE, &mut app).unwrap() {
+            KeyResult::RunCommand(cmd) => assert_eq!(cmd, "git reset --hard origin/main"),
+            _ => panic!("y should run the held command"),
+        }
+        assert!(app.pending_run.is_none());
+
+        app.input = "git status".to_string();
+        assert!(matches!(enter(&mut app), KeyResult::RunCommand(_)));
+    }
+
+    #[test]
+    fn clicks_select_and_double_clicks_run_suggestions() {
+        let click = |row| MouseEvent {
+            kind: MouseEventKind::Down(event::MouseButton::Left),
+            column: 5,
+            row,
+            modifiers: KeyModifiers::NONE,
+        };
+        let mut app = test_app();
+        app.suggestions_area = Some(Rect::new(0, 4, 40, 10));
+        app.suggestions = ["docker run \\\n  --rm alpine", "git status"]
+            .into_iter()
+            .map(|text| Suggestion::with_source(text, 1.0, "history"))
+            .collect();
+
+        // Row 4 is the top border; the first suggestion spans rows 5 and 6
+        assert!(matches!(handle_mouse(click(4), &mut app), KeyResult::Continue));
+        assert_eq!(app.selected, 0);
+        handle_mouse(click(7), &mut app);
+        assert_eq!(app.selected, 1);
+        handle_mouse(click(6), &mut app);
+        assert_eq!(app.selected, 0);
+
+        match handle_mouse(click(5), &mut app) {
+            KeyResult::RunCommand(cmd) => assert_eq!(cmd, app.suggestions[0].text),
+            _ => panic!("double-click should run the suggestion"),
+        }
+    }
+
+    #[test]
+    fn forwards_lines_without_their_endings() {
+        let (tx, rx) = mpsc::channel();
+        forward_lines(&b"one\r\ntwo\n\xffthree"[..], tx);
+        let lines: Vec<String> = rx.iter().collect();
+        assert_eq!(lines, vec!["one", "two", "\u{fffd}three"]);
+    }
+
+    /// Poll `run` until it finishes, returning its exit code
+    fn wait_for(run: &mut RunningCommand, app: &mut core::App) -> Option<i32> {
+        let started = Instant::now();
+        loop {
+            if let Some(code) = run.poll(app).unwrap() {
+                return code;
+            }
+            assert!(started.elapsed() < Duration::from_secs(10), "command did not finish");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn streams_output_into_the_app_and_records_the_run() {
+        let mut app = test_app();
+        let mut run = RunningCommand::spawn("/bin/sh", "echo out; echo err >&2; exit 3", 100).unwrap();
+        let exit_code = wait_for(&mut run, &mut app);
+        assert_eq!(exit_code, Some(3));
+        for line in ["out", "err"] {
+            assert!(app.output_lines.iter().any(|l| l == line), "{:?}", app.output_lines);
+        }
+
+        let secrets = SecretFilter::default();
+        let recorder = RunRecorder {
+            pool: None,
+            session_id: "test",
+            secrets: &secrets,
+        };
+        recorder.record(&mut app, &run, exit_code);
+        assert_eq!(app.history[0].cmd, "echo out; echo err >&2; exit 3");
+        assert_eq!(app.history[0].exit_code, Some(3));
+        assert_eq!(app.output_lines, app.history[0].output_lines);
+    }
+
+    #[test]
+    fn stopping_a_command_kills_its_background_jobs() {
+        let mut app = test_app();
+        let mut run = RunningCommand::spawn("/bin/sh", "sleep 30 & echo $!; wait", 10).unwrap();
+        let started = Instant::now();
+        while app.output_lines.is_empty() {
+            run.poll(&mut app).unwrap();
+            assert!(started.elapsed() < Duration::from_secs(10), "no pid printed");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let job: libc::pid_t = app.output_lines[0].parse().unwrap();
+
+        run.stop().unwrap();
+        assert_eq!(wait_for(&mut run, &mut app), None, "killed by a signal");
+        // A killed orphan may linger as a zombie until init reaps it
+        let alive = |pid: libc::pid_t| match std::fs::read_to_string(format!("/proc/{pid}/stat")) {
+            Ok(stat) => !stat.contains(") Z "),
+            Err(_) => unsafe { libc::kill(pid, 0) == 0 },
+        };
+        let started = Instant::now();
+        while alive(job) {
+            assert!(started.elapsed() < Duration::from_secs(10), "background job survived");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn output_keeps_only_the_newest_lines() {
+        let mut app = test_app();
+        app.max_output_lines = 10;
+        app.output_scroll = 5;
+        let command = "i=0; while [ $i -lt 30 ]; do echo $i; i=$((i+1)); done";
+        let mut run = RunningCommand::spawn("/bin/sh", command, 10).unwrap();
+        wait_for(&mut run, &mut app);
+
+        let expected: Vec<String> = (20..30).map(|i| i.to_string()).collect();
+        assert_eq!(app.output_lines, expected);
+        assert_eq!(app.output_dropped, 20);
+        assert_eq!(app.output_scroll, 0, "scroll follows the lines it was showing");
+        assert_eq!(
+            run.stored_output(),
+            format!("[20 earlier lines dropped]\n{}", expected.join("\n"))
+        );
+
+        // The byte cap trims stored output further, still from the oldest end
+        let line = "x".repeat(1000);
+        run.output = std::iter::repeat_n(line.clone(), 100).collect();
+        run.dropped = 0;
+        let stored = run.stored_output();
+        assert!(stored.len() <= MAX_CAPTURED_OUTPUT_BYTES + 40);
+        assert!(stored.starts_with("[35 earlier lines dropped]\n"));
+        assert!(stored.ends_with(&line));
+    }
+}